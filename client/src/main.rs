@@ -22,30 +22,37 @@ async fn main() -> anyhow::Result<()> {
     //     .send(display)
     //     .await?;
 
-    let children = display.query_tree(root).await?;
-    dbg!(children);
-
-    x11::request::QueryTree::new(root).write(&mut display)?;
-
-    let window = display.setup.resource_id_base + 1;
-    x11::request::CreateWindow::new(
-        0,
-        window,
-        root,
-        100,
-        50,
-        500,
-        250,
-        5,
-        x11::proto::Class::CopyFromParent,
-        x11::proto::Visual::CopyFromParent,
-    )
-    .write(&mut display)?;
-
-    x11::request::MapWindow::new(window).write(&mut display)?;
+    let tree = display.query_tree(root).await?;
+    dbg!(tree);
+
+    let window = x11::proto::Window(display.setup.resource_id_base + 1);
+    display
+        .send(&x11::request::CreateWindow::new(
+            0,
+            window,
+            root,
+            100,
+            50,
+            500,
+            250,
+            5,
+            x11::proto::Class::CopyFromParent,
+            x11::proto::Visual::CopyFromParent,
+        ))
+        .await?;
+
+    display.map_window(window).await?;
+
+    let mut dispatcher = x11::dispatch::Dispatcher::new();
+
+    dispatcher.on(window, x11::event::Kind::Expose, |_, event| {
+        println!("Expose({:#?})", event);
+        Ok(())
+    });
 
     loop {
-        let message = display.next_message()?;
-        println!("Message({:#?})", message);
+        if let Some(message) = dispatcher.dispatch(&mut display).await? {
+            println!("Message({:#?})", message);
+        }
     }
 }
@@ -0,0 +1,1721 @@
+//! Generates the `protocol` module from the xcb-proto descriptions in
+//! `xml/`: requests and their replies, events, errors, enums and masks,
+//! structs, unions and resource ids, for the core protocol and every
+//! extension. Items using parts of the description language the generator
+//! doesn't handle are left out, with a comment saying why, and listed in
+//! `protocol::SKIPPED`.
+
+use roxmltree::{Document, Node};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::{env, fs, path::Path};
+
+type Result<T> = std::result::Result<T, String>;
+
+/// The lints the generated code doesn't try to satisfy, put on each module
+/// so that they don't reach the hand-written code around it.
+const ALLOW: &str = "#[allow(clippy::all, unused_parens)]";
+
+/// Ids the core protocol shares with `proto`, so that generated and
+/// hand-written code can be mixed.
+const PROTO_RESOURCES: &[&str] = &[
+    "Window",
+    "Pixmap",
+    "Drawable",
+    "Atom",
+    "Colormap",
+    "Cursor",
+    "Font",
+    "Gcontext",
+    "Visualid",
+    "Timestamp",
+];
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+    "mut", "override", "priv", "pub", "ref", "return", "self", "static", "struct", "super",
+    "trait", "true", "type", "typeof", "unsafe", "use", "virtual", "where", "while", "yield",
+    // Names used by the generated code itself.
+    "r", "w",
+];
+
+fn main() {
+    let dir = Path::new("xml");
+    println!("cargo:rerun-if-changed=xml");
+
+    let mut texts = Vec::new();
+    for entry in fs::read_dir(dir).expect("read xml") {
+        let path = entry.expect("read xml").path();
+        if path.extension().is_some_and(|e| e == "xml") {
+            println!("cargo:rerun-if-changed={}", path.display());
+            texts.push(fs::read_to_string(&path).expect("read xml"));
+        }
+    }
+
+    let docs = texts
+        .iter()
+        .map(|text| Document::parse(text).expect("parse xml"))
+        .collect::<Vec<_>>();
+
+    let mut modules = docs
+        .iter()
+        .map(|doc| Module::new(doc.root_element()))
+        .collect::<Vec<_>>();
+    modules.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut generator = Generator::default();
+    let mut out = String::new();
+    for module in order(&modules) {
+        out.push_str(&generator.module(module));
+    }
+
+    writeln!(
+        out,
+        "/// The items of the protocol descriptions that aren't generated, by\n/// module, name and reason."
+    )
+    .unwrap();
+    writeln!(out, "pub const SKIPPED: &[(&str, &str, &str)] = &[").unwrap();
+    for (module, name, reason) in &generator.skipped {
+        writeln!(out, "    ({:?}, {:?}, {:?}),", module, name, reason).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+
+    let path = Path::new(&env::var("OUT_DIR").expect("OUT_DIR")).join("protocol.rs");
+    fs::write(path, out).expect("write protocol.rs");
+}
+
+struct Module<'a> {
+    name: String,
+    root: Node<'a, 'a>,
+    imports: Vec<String>,
+    /// The name known to QueryExtension, and the version.
+    extension: Option<(String, String, String)>,
+}
+
+impl<'a> Module<'a> {
+    fn new(root: Node<'a, 'a>) -> Self {
+        let imports = children(root)
+            .filter(|node| node.has_tag_name("import"))
+            .filter_map(|node| node.text())
+            .map(String::from)
+            .collect();
+
+        let extension = root.attribute("extension-xname").map(|name| {
+            (
+                String::from(name),
+                String::from(root.attribute("major-version").unwrap_or("0")),
+                String::from(root.attribute("minor-version").unwrap_or("0")),
+            )
+        });
+
+        Self {
+            name: String::from(root.attribute("header").expect("header")),
+            root,
+            imports,
+            extension,
+        }
+    }
+}
+
+/// Orders modules so that every module comes after the ones it imports.
+fn order<'m, 'a>(modules: &'m [Module<'a>]) -> Vec<&'m Module<'a>> {
+    fn visit<'m, 'a>(
+        module: &'m Module<'a>,
+        modules: &'m [Module<'a>],
+        done: &mut HashSet<String>,
+        ordered: &mut Vec<&'m Module<'a>>,
+    ) {
+        if !done.insert(module.name.clone()) {
+            return;
+        }
+
+        for import in &module.imports {
+            if let Some(import) = modules.iter().find(|m| &m.name == import) {
+                visit(import, modules, done, ordered);
+            }
+        }
+
+        ordered.push(module);
+    }
+
+    let mut done = HashSet::new();
+    let mut ordered = Vec::new();
+    for module in modules {
+        visit(module, modules, &mut done, &mut ordered);
+    }
+
+    ordered
+}
+
+fn children<'a, 'i>(node: Node<'a, 'i>) -> impl Iterator<Item = Node<'a, 'i>> {
+    node.children().filter(|node| node.is_element())
+}
+
+fn attribute<'a>(node: Node<'a, '_>, name: &str) -> Result<&'a str> {
+    node.attribute(name)
+        .ok_or_else(|| format!("<{}> without {}", node.tag_name().name(), name))
+}
+
+/// `WINDOW` becomes `Window`, `ClientMessageData` stays as it is.
+fn type_name(name: &str) -> String {
+    let upper = !name.chars().any(|c| c.is_ascii_lowercase());
+
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap_or_default().to_ascii_uppercase();
+            match upper {
+                true => first.to_string() + &chars.as_str().to_ascii_lowercase(),
+                false => first.to_string() + chars.as_str(),
+            }
+        })
+        .collect()
+}
+
+fn words(name: &str) -> Vec<String> {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut words = Vec::new();
+    let mut word = String::new();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if c == '_' || c == '-' || c == ' ' {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+
+        let previous = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1);
+        let boundary = c.is_ascii_uppercase()
+            && previous.is_some_and(|p| {
+                p.is_ascii_lowercase()
+                    || p.is_ascii_digit()
+                    || (p.is_ascii_uppercase() && next.is_some_and(|n| n.is_ascii_lowercase()))
+            });
+
+        if boundary && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        word.push(c);
+    }
+
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words
+}
+
+fn field_name(name: &str) -> String {
+    let name = words(name).join("_").to_ascii_lowercase();
+    match KEYWORDS.contains(&name.as_str()) {
+        true => name + "_",
+        false => name,
+    }
+}
+
+fn const_name(name: &str) -> String {
+    let name = words(name).join("_").to_ascii_uppercase();
+    match name.starts_with(|c: char| c.is_ascii_digit()) {
+        true => format!("_{}", name),
+        false => name,
+    }
+}
+
+/// A path to an item of module `module` from within module `cur`.
+fn path(cur: &str, module: &str, name: &str) -> String {
+    match cur == module {
+        true => String::from(name),
+        false => format!("super::{}::{}", module, name),
+    }
+}
+
+fn brief(node: Node) -> Option<String> {
+    let doc = children(node).find(|n| n.has_tag_name("doc"))?;
+    let brief = children(doc).find(|n| n.has_tag_name("brief"))?;
+    let text = brief
+        .text()?
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut chars = text.chars();
+    let first = chars.next()?.to_ascii_uppercase();
+    let text = first.to_string() + chars.as_str();
+
+    Some(match text.ends_with('.') {
+        true => format!("/// {}\n", text),
+        false => format!("/// {}.\n", text),
+    })
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Prim {
+    rust: &'static str,
+    size: usize,
+}
+
+fn prim(name: &str) -> Option<Prim> {
+    let (rust, size) = match name {
+        "CARD8" | "BYTE" | "char" | "void" => ("u8", 1),
+        "INT8" => ("i8", 1),
+        "BOOL" => ("bool", 1),
+        "CARD16" => ("u16", 2),
+        "INT16" => ("i16", 2),
+        "CARD32" => ("u32", 4),
+        "INT32" => ("i32", 4),
+        "CARD64" => ("u64", 8),
+        "INT64" => ("i64", 8),
+        "float" => ("f32", 4),
+        "double" => ("f64", 8),
+        _ => return None,
+    };
+
+    Some(Prim { rust, size })
+}
+
+impl Prim {
+    fn is_unsigned(self) -> bool {
+        matches!(self.rust, "u8" | "u16" | "u32")
+    }
+
+    fn write(self, value: &str) -> String {
+        match self.rust {
+            "u8" => format!("w.write_u8({})?;", value),
+            "i8" => format!("w.write_i8({})?;", value),
+            "bool" => format!("w.write_u8(u8::from({}))?;", value),
+            rust => format!("w.write_{}::<BigEndian>({})?;", rust, value),
+        }
+    }
+}
+
+/// The type of a field, and how to read, write and evaluate it.
+#[derive(Clone)]
+enum Ty {
+    Prim(Prim),
+    /// A resource id: a newtype around u32, by module and name.
+    Id(String, String),
+    /// A struct or union with `read` and `write`, and its size if fixed.
+    Struct(String, String, Option<usize>),
+    /// An integer holding a value of an enum or mask.
+    Enum(Prim, String, String),
+    /// A typedef of another type.
+    Alias(String, String, Box<Ty>),
+}
+
+impl Ty {
+    fn rust(&self, cur: &str) -> String {
+        match self {
+            Ty::Prim(prim) => String::from(prim.rust),
+            Ty::Id(m, name) | Ty::Struct(m, name, _) | Ty::Enum(_, m, name) => path(cur, m, name),
+            Ty::Alias(m, name, _) => path(cur, m, name),
+        }
+    }
+
+    fn size(&self) -> Option<usize> {
+        match self {
+            Ty::Prim(prim) | Ty::Enum(prim, _, _) => Some(prim.size),
+            Ty::Id(_, _) => Some(4),
+            Ty::Struct(_, _, size) => *size,
+            Ty::Alias(_, _, ty) => ty.size(),
+        }
+    }
+
+    fn prim(&self) -> Option<Prim> {
+        match self {
+            Ty::Prim(prim) | Ty::Enum(prim, _, _) => Some(*prim),
+            Ty::Alias(_, _, ty) => ty.prim(),
+            _ => None,
+        }
+    }
+
+    fn is_copy(&self) -> bool {
+        match self {
+            Ty::Struct(_, _, _) => false,
+            Ty::Alias(_, _, ty) => ty.is_copy(),
+            _ => true,
+        }
+    }
+
+    /// Whether a list of this type is just bytes.
+    fn is_byte(&self) -> bool {
+        match self {
+            Ty::Prim(prim) => prim.rust == "u8",
+            Ty::Alias(_, _, ty) => ty.is_byte(),
+            _ => false,
+        }
+    }
+
+    fn read(&self, cur: &str) -> String {
+        match self {
+            Ty::Prim(prim) => format!("r.{}()?", prim.rust),
+            Ty::Id(m, name) => format!("{}(r.u32()?)", path(cur, m, name)),
+            Ty::Struct(m, name, _) => format!("{}::read(r)?", path(cur, m, name)),
+            Ty::Enum(prim, m, name) => match prim.rust {
+                "u32" => format!("{}(r.u32()?)", path(cur, m, name)),
+                rust => format!("{}(u32::from(r.{}()?))", path(cur, m, name), rust),
+            },
+            Ty::Alias(_, _, ty) => ty.read(cur),
+        }
+    }
+
+    /// Writes `value`, a place for structs and a value otherwise.
+    fn write(&self, value: &str) -> String {
+        match self {
+            Ty::Prim(prim) => prim.write(value),
+            Ty::Id(_, _) => format!("w.write_u32::<BigEndian>({}.0)?;", value),
+            Ty::Struct(_, _, _) => format!("{}.write(w)?;", value),
+            Ty::Enum(prim, _, _) => match prim.rust {
+                "u32" => format!("w.write_u32::<BigEndian>({}.0)?;", value),
+                rust => prim.write(&format!("{}::try_from({}.0)?", rust, value)),
+            },
+            Ty::Alias(_, _, ty) => ty.write(value),
+        }
+    }
+
+    /// `value` as an i64, for expressions.
+    fn to_i64(&self, value: &str) -> Result<String> {
+        match self {
+            Ty::Prim(prim) => match prim.rust {
+                "i64" => Ok(String::from(value)),
+                "u64" => Ok(format!("({} as i64)", value)),
+                "f32" | "f64" => Err(String::from("floating point expression")),
+                _ => Ok(format!("i64::from({})", value)),
+            },
+            Ty::Id(_, _) | Ty::Enum(_, _, _) => Ok(format!("i64::from({}.0)", value)),
+            Ty::Struct(_, _, _) => Err(String::from("struct in an expression")),
+            Ty::Alias(_, _, ty) => ty.to_i64(value),
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Expr {
+    Field(String),
+    Value(i64),
+    Op(String, Box<Expr>, Box<Expr>),
+    Unop(Box<Expr>),
+    Popcount(Box<Expr>),
+    /// An enum item, by module, enum and const name.
+    Enum(String, String, String),
+    /// The sum over a list of its elements, or of an expression of them.
+    SumOf(String, Option<Box<Expr>>),
+    /// The list element an expression in `SumOf` is about.
+    Element,
+    /// Code standing in for `Element`.
+    Raw(String),
+}
+
+impl Expr {
+    fn fields<'e>(&'e self, fields: &mut Vec<&'e str>) {
+        match self {
+            Expr::Field(name) => fields.push(name),
+            Expr::Op(_, a, b) => {
+                a.fields(fields);
+                b.fields(fields);
+            }
+            Expr::Unop(a) | Expr::Popcount(a) => a.fields(fields),
+            Expr::Value(_)
+            | Expr::Enum(_, _, _)
+            | Expr::SumOf(_, _)
+            | Expr::Element
+            | Expr::Raw(_) => {}
+        }
+    }
+
+    /// Replaces `Element` with `code`.
+    fn with_element(&self, code: &str) -> Expr {
+        match self {
+            Expr::Element => Expr::Raw(String::from(code)),
+            Expr::Op(op, a, b) => Expr::Op(
+                op.clone(),
+                Box::new(a.with_element(code)),
+                Box::new(b.with_element(code)),
+            ),
+            Expr::Unop(a) => Expr::Unop(Box::new(a.with_element(code))),
+            Expr::Popcount(a) => Expr::Popcount(Box::new(a.with_element(code))),
+            expr => expr.clone(),
+        }
+    }
+
+    /// Renders the expression as an i64. `field` gives the value of a
+    /// field and `list` the code and element type of a list.
+    fn render(
+        &self,
+        cur: &str,
+        field: &dyn Fn(&str) -> Result<String>,
+        list: &dyn Fn(&str) -> Result<(String, Ty)>,
+    ) -> Result<String> {
+        Ok(match self {
+            Expr::Field(name) => field(name)?,
+            Expr::Value(value) => value.to_string(),
+            Expr::Op(op, a, b) => format!(
+                "({} {} {})",
+                a.render(cur, field, list)?,
+                op,
+                b.render(cur, field, list)?
+            ),
+            Expr::Unop(a) => format!("(!{})", a.render(cur, field, list)?),
+            Expr::Popcount(a) => format!("i64::from({}.count_ones())", a.render(cur, field, list)?),
+            Expr::Enum(m, name, item) => format!("i64::from({}::{}.0)", path(cur, m, name), item),
+            Expr::SumOf(name, of) => {
+                let (code, ty) = list(name)?;
+                if !ty.is_copy() {
+                    return Err(String::from("<sumof> over a list of structs"));
+                }
+                let element = ty.to_i64("v")?;
+                let value = match of {
+                    Some(of) => of.with_element(&element).render(cur, field, list)?,
+                    None => element,
+                };
+                format!("{}.iter().map(|&v| {}).sum::<i64>()", code, value)
+            }
+            Expr::Element => return Err(String::from("<listelement-ref> outside <sumof>")),
+            Expr::Raw(code) => code.clone(),
+        })
+    }
+}
+
+struct Case {
+    name: String,
+    bit: String,
+    ty: Ty,
+}
+
+enum Elem {
+    Field(String, Ty),
+    Pad(usize),
+    Align(usize),
+    List(String, Ty, Option<Expr>),
+    ExprField(Ty, Expr),
+    /// A value list: a struct of optional values selected by a mask field.
+    /// Its field, the struct generated for it and the mask field.
+    Switch(String, String, String),
+}
+
+impl Elem {
+    fn size(&self) -> Option<usize> {
+        match self {
+            Elem::Field(_, ty) | Elem::ExprField(ty, _) => ty.size(),
+            Elem::Pad(n) => Some(*n),
+            Elem::List(_, ty, Some(Expr::Value(n))) => Some(ty.size()? * usize::try_from(*n).ok()?),
+            _ => None,
+        }
+    }
+}
+
+/// How a field left out of a struct is written.
+enum Derived {
+    /// The length of a list.
+    Len(String),
+    /// The mask of a value list.
+    Mask(String),
+}
+
+/// The elements of a struct, request, reply, event or error.
+struct Body {
+    elems: Vec<Elem>,
+    derived: HashMap<String, Derived>,
+    /// Code for the value list structs.
+    extra: String,
+    /// Whether `length` is the length of the reply, in four-byte units
+    /// beyond the first 32 bytes.
+    reply: bool,
+}
+
+impl Body {
+    fn field_ty(&self, name: &str) -> Option<&Ty> {
+        self.elems.iter().find_map(|elem| match elem {
+            Elem::Field(n, ty) if n == name => Some(ty),
+            _ => None,
+        })
+    }
+
+    fn is_list(&self, name: &str) -> bool {
+        self.list_ty(name).is_some()
+    }
+
+    fn list_ty(&self, name: &str) -> Option<&Ty> {
+        self.elems.iter().find_map(|elem| match elem {
+            Elem::List(n, ty, _) if n == name => Some(ty),
+            _ => None,
+        })
+    }
+
+    fn fields(&self, cur: &str) -> String {
+        let mut out = String::new();
+        for elem in &self.elems {
+            match elem {
+                Elem::Field(name, ty) if !self.derived.contains_key(name) => {
+                    writeln!(out, "    pub {}: {},", name, ty.rust(cur)).unwrap();
+                }
+                Elem::List(name, ty, _) => {
+                    writeln!(out, "    pub {}: Vec<{}>,", name, ty.rust(cur)).unwrap();
+                }
+                Elem::Switch(name, ty, _) => {
+                    writeln!(out, "    pub {}: {},", name, ty).unwrap();
+                }
+                _ => {}
+            }
+        }
+        out
+    }
+
+    fn field_names(&self) -> String {
+        self.elems
+            .iter()
+            .filter_map(|elem| match elem {
+                Elem::Field(name, _) if !self.derived.contains_key(name) => Some(name.as_str()),
+                Elem::List(name, _, _) | Elem::Switch(name, _, _) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Statements reading `elems` into locals named after the fields.
+    /// Lists without a length run to the end of the message.
+    fn read(&self, cur: &str, elems: &[Elem], open: bool) -> Result<String> {
+        let mut out = String::new();
+
+        for elem in elems {
+            match elem {
+                Elem::Field(name, ty) => writeln!(out, "        let {} = {};", name, ty.read(cur)),
+                Elem::Pad(n) => writeln!(out, "        r.skip({})?;", n),
+                Elem::Align(n) => writeln!(out, "        r.align({})?;", n),
+                Elem::ExprField(ty, _) => writeln!(out, "        let _ = {};", ty.read(cur)),
+
+                Elem::List(name, ty, len) => {
+                    let n = match len {
+                        Some(Expr::Value(n)) => n.to_string(),
+                        Some(len) => format!("usize::try_from({})?", self.read_expr(cur, len)?),
+                        None if open => String::from("r.remaining()"),
+                        None => return Err(String::from("list without a length")),
+                    };
+
+                    match (ty.is_byte(), len) {
+                        (true, _) => writeln!(out, "        let {} = r.bytes({})?.to_vec();", name, n),
+                        (false, None) => writeln!(
+                            out,
+                            "        let mut {name} = Vec::new();\n        while r.remaining() > 0 {{\n            {name}.push({});\n        }}",
+                            ty.read(cur),
+                        ),
+                        (false, Some(_)) => writeln!(
+                            out,
+                            "        let mut {name} = Vec::new();\n        for _ in 0..{} {{\n            {name}.push({});\n        }}",
+                            n,
+                            ty.read(cur),
+                        ),
+                    }
+                }
+
+                Elem::Switch(name, ty, mask) => {
+                    let mask = self.read_expr(cur, &Expr::Field(mask.clone()))?;
+                    writeln!(out, "        let {} = {}::read(r, {})?;", name, ty, mask)
+                }
+            }
+            .unwrap();
+        }
+
+        Ok(out)
+    }
+
+    fn read_expr(&self, cur: &str, expr: &Expr) -> Result<String> {
+        expr.render(
+            cur,
+            &|name| match self.field_ty(name) {
+                Some(ty) => ty.to_i64(name),
+                None if self.reply && name == "length" => Ok(String::from("i64::from(length)")),
+                None => Err(format!("unknown field {}", name)),
+            },
+            &|name| match self.list_ty(name) {
+                Some(ty) => Ok((String::from(name), ty.clone())),
+                None => Err(format!("unknown list {}", name)),
+            },
+        )
+    }
+
+    fn write_expr(&self, cur: &str, expr: &Expr) -> Result<String> {
+        let field = |name: &str| {
+            if let Some(derived) = self.derived.get(name) {
+                return Ok(match derived {
+                    Derived::Len(list) => format!("(self.{}.len() as i64)", list),
+                    Derived::Mask(switch) => format!("i64::from(self.{}.mask())", switch),
+                });
+            }
+
+            if let Some(ty) = self.field_ty(name) {
+                return ty.to_i64(&format!("self.{}", name));
+            }
+
+            // The length of a list without a length field.
+            match name.strip_suffix("_len") {
+                Some(list) if self.is_list(list) => Ok(format!("(self.{}.len() as i64)", list)),
+                _ => Err(format!("unknown field {}", name)),
+            }
+        };
+
+        expr.render(cur, &field, &|name| match self.list_ty(name) {
+            Some(ty) => Ok((format!("self.{}", name), ty.clone())),
+            None => Err(format!("unknown list {}", name)),
+        })
+    }
+
+    /// The value of a field as it is written.
+    fn value(&self, cur: &str, elem: &Elem) -> Result<Option<(Ty, String)>> {
+        Ok(match elem {
+            Elem::Field(name, ty) => match self.derived.get(name) {
+                Some(derived) => {
+                    let prim = ty.prim().ok_or("length of a non-integer type")?;
+                    let value = match derived {
+                        Derived::Len(list) => {
+                            format!("{}::try_from(self.{}.len())?", prim.rust, list)
+                        }
+                        Derived::Mask(switch) if prim.rust == "u32" => {
+                            format!("self.{}.mask()", switch)
+                        }
+                        Derived::Mask(switch) => {
+                            format!("{}::try_from(self.{}.mask())?", prim.rust, switch)
+                        }
+                    };
+                    Some((Ty::Prim(prim), value))
+                }
+                None => Some((ty.clone(), format!("self.{}", name))),
+            },
+
+            Elem::ExprField(ty, expr) => {
+                let prim = ty.prim().ok_or("expression of a non-integer type")?;
+                let expr = self.write_expr(cur, expr)?;
+                let value = match prim.rust {
+                    "bool" => format!("{} != 0", expr),
+                    rust => format!("{} as {}", expr, rust),
+                };
+                Some((Ty::Prim(prim), value))
+            }
+
+            _ => None,
+        })
+    }
+
+    fn write(&self, cur: &str, elems: &[Elem]) -> Result<String> {
+        let mut out = String::new();
+
+        for elem in elems {
+            if let Some((ty, value)) = self.value(cur, elem)? {
+                writeln!(out, "        {}", ty.write(&value)).unwrap();
+                continue;
+            }
+
+            match elem {
+                Elem::Pad(n) => writeln!(out, "        w.resize(w.len() + {}, 0);", n),
+                Elem::Align(n) => writeln!(out, "        super::align(w, {});", n),
+
+                Elem::List(name, ty, len) => {
+                    match len {
+                        Some(Expr::Field(field)) if self.derived.contains_key(field) => {}
+                        Some(len) => writeln!(
+                            out,
+                            "        anyhow::ensure!(self.{name}.len() as i64 == {}, \"wrong length of {name}\");",
+                            self.write_expr(cur, len)?,
+                        )
+                        .unwrap(),
+                        None => {}
+                    }
+
+                    match (ty.is_byte(), ty.is_copy()) {
+                        (true, _) => writeln!(out, "        w.extend_from_slice(&self.{});", name),
+                        (false, true) => writeln!(
+                            out,
+                            "        for &v in &self.{} {{\n            {}\n        }}",
+                            name,
+                            ty.write("v"),
+                        ),
+                        (false, false) => writeln!(
+                            out,
+                            "        for v in &self.{} {{\n            {}\n        }}",
+                            name,
+                            ty.write("v"),
+                        ),
+                    }
+                }
+
+                Elem::Switch(name, _, _) => writeln!(out, "        self.{}.write(w)?;", name),
+                _ => Ok(()),
+            }
+            .unwrap();
+        }
+
+        Ok(out)
+    }
+
+    /// Splits off the first element if it fits in the one byte a header
+    /// has room for.
+    fn split_header(&self) -> (Option<&Elem>, &[Elem]) {
+        match self.elems.first() {
+            Some(first) if first.size() == Some(1) => (Some(first), &self.elems[1..]),
+            _ => (None, &self.elems[..]),
+        }
+    }
+}
+
+/// The Rust name of an enum, and the consts of its items by XML name.
+type Enum = (String, HashMap<String, String>);
+
+#[derive(Default)]
+struct Generator {
+    /// Types by module and XML name.
+    types: HashMap<(String, String), Ty>,
+    /// Enums by module and XML name.
+    enums: HashMap<(String, String), Enum>,
+    /// Items left out, by module and XML name, with the reason.
+    skipped: Vec<(String, String, String)>,
+}
+
+impl Generator {
+    fn scopes<'m>(&self, cur: &'m Module, name: &'m str) -> Vec<(&'m str, &'m str)> {
+        match name.split_once(':') {
+            Some((module, name)) => vec![(module, name)],
+            None => std::iter::once(cur.name.as_str())
+                .chain(cur.imports.iter().map(String::as_str))
+                .chain(std::iter::once("xproto"))
+                .map(|module| (module, name))
+                .collect(),
+        }
+    }
+
+    fn resolve(&self, cur: &Module, name: &str) -> Result<Ty> {
+        if let Some(prim) = prim(name) {
+            return Ok(Ty::Prim(prim));
+        }
+
+        if name == "fd" {
+            return Err(String::from("file descriptors"));
+        }
+
+        self.scopes(cur, name)
+            .into_iter()
+            .find_map(|(m, n)| self.types.get(&(String::from(m), String::from(n))))
+            .cloned()
+            .ok_or_else(|| format!("unknown type {}", name))
+    }
+
+    fn resolve_enum(&self, cur: &Module, name: &str) -> Option<(String, &Enum)> {
+        self.scopes(cur, name).into_iter().find_map(|(m, n)| {
+            self.enums
+                .get(&(String::from(m), String::from(n)))
+                .map(|e| (String::from(m), e))
+        })
+    }
+
+    /// The type of a field or list element, with its enum if it has one.
+    fn field_ty(&self, cur: &Module, node: Node) -> Result<Ty> {
+        let ty = self.resolve(cur, attribute(node, "type")?)?;
+
+        let name = match node.attribute("enum").or_else(|| node.attribute("mask")) {
+            Some(name) => name,
+            None => return Ok(ty),
+        };
+
+        match (ty.prim(), self.resolve_enum(cur, name)) {
+            (Some(prim), Some((module, (rust, _)))) if prim.is_unsigned() => {
+                Ok(Ty::Enum(prim, module, rust.clone()))
+            }
+            _ => Ok(ty),
+        }
+    }
+
+    fn expr(&self, cur: &Module, node: Node) -> Result<Expr> {
+        let child = |i: usize| -> Result<Expr> {
+            let node = children(node).nth(i).ok_or("missing operand")?;
+            self.expr(cur, node)
+        };
+
+        Ok(match node.tag_name().name() {
+            "fieldref" => Expr::Field(field_name(node.text().ok_or("empty fieldref")?)),
+            "value" => {
+                let text = node.text().ok_or("empty value")?.trim();
+                let value = match text.strip_prefix("0x") {
+                    Some(hex) => i64::from_str_radix(hex, 16),
+                    None => text.parse(),
+                };
+                Expr::Value(value.map_err(|e| e.to_string())?)
+            }
+            "op" => {
+                let op = attribute(node, "op")?;
+                if !["+", "-", "*", "/", "&", "<<"].contains(&op) {
+                    return Err(format!("operator {}", op));
+                }
+                Expr::Op(String::from(op), Box::new(child(0)?), Box::new(child(1)?))
+            }
+            "unop" => Expr::Unop(Box::new(child(0)?)),
+            "popcount" => Expr::Popcount(Box::new(child(0)?)),
+            "sumof" => {
+                let list = field_name(attribute(node, "ref")?);
+                let of = match children(node).next() {
+                    Some(_) => Some(Box::new(child(0)?)),
+                    None => None,
+                };
+                Expr::SumOf(list, of)
+            }
+            "listelement-ref" => Expr::Element,
+            "enumref" => {
+                let name = attribute(node, "ref")?;
+                let item = node.text().ok_or("empty enumref")?;
+                let (module, (rust, items)) = self
+                    .resolve_enum(cur, name)
+                    .ok_or_else(|| format!("unknown enum {}", name))?;
+                let item = items
+                    .get(item)
+                    .ok_or_else(|| format!("unknown item {}::{}", name, item))?;
+                Expr::Enum(module, rust.clone(), item.clone())
+            }
+            tag => return Err(format!("<{}> expressions", tag)),
+        })
+    }
+
+    fn body(&self, cur: &Module, node: Node, owner: &str) -> Result<Body> {
+        let mut elems = Vec::new();
+        let mut extra = String::new();
+
+        for child in children(node) {
+            match child.tag_name().name() {
+                "field" => {
+                    let name = field_name(attribute(child, "name")?);
+                    elems.push(Elem::Field(name, self.field_ty(cur, child)?));
+                }
+
+                "pad" => match (child.attribute("bytes"), child.attribute("align")) {
+                    (Some(n), _) => elems.push(Elem::Pad(n.parse().map_err(|_| "bad pad")?)),
+                    (None, Some(n)) => elems.push(Elem::Align(n.parse().map_err(|_| "bad pad")?)),
+                    (None, None) => return Err(String::from("pad without a size")),
+                },
+
+                "list" => {
+                    let name = field_name(attribute(child, "name")?);
+                    let len = match children(child).next() {
+                        Some(len) => Some(self.expr(cur, len)?),
+                        None => None,
+                    };
+                    elems.push(Elem::List(name, self.field_ty(cur, child)?, len));
+                }
+
+                "exprfield" => {
+                    let expr = children(child)
+                        .next()
+                        .ok_or("exprfield without expression")?;
+                    elems.push(Elem::ExprField(
+                        self.field_ty(cur, child)?,
+                        self.expr(cur, expr)?,
+                    ));
+                }
+
+                "switch" => {
+                    let (elem, code) = self.switch(cur, child, owner)?;
+                    elems.push(elem);
+                    extra.push_str(&code);
+                }
+
+                "reply" | "doc" | "required_start_align" => {}
+                tag => return Err(format!("<{}>", tag)),
+            }
+        }
+
+        let mut body = Body {
+            elems,
+            derived: HashMap::new(),
+            extra,
+            reply: false,
+        };
+
+        // Length and mask fields are worked out from the lists and value
+        // lists they describe, unless something else refers to them too.
+        let mut refs = Vec::new();
+        for elem in &body.elems {
+            match elem {
+                Elem::List(_, _, Some(len)) => len.fields(&mut refs),
+                Elem::ExprField(_, expr) => expr.fields(&mut refs),
+                _ => {}
+            }
+        }
+
+        let mut derived = HashMap::new();
+        for elem in &body.elems {
+            let (field, how) = match elem {
+                Elem::List(name, _, Some(Expr::Field(field))) => {
+                    (field, Derived::Len(name.clone()))
+                }
+                Elem::Switch(name, _, mask) => {
+                    refs.push(mask);
+                    (mask, Derived::Mask(name.clone()))
+                }
+                _ => continue,
+            };
+
+            let integer = body
+                .field_ty(field)
+                .and_then(Ty::prim)
+                .is_some_and(|prim| prim.is_unsigned());
+            if integer {
+                derived.insert(field.clone(), how);
+            }
+        }
+
+        derived.retain(|field, _| refs.iter().filter(|r| *r == field).count() == 1);
+        body.derived = derived;
+
+        Ok(body)
+    }
+
+    fn switch(&self, cur: &Module, node: Node, owner: &str) -> Result<(Elem, String)> {
+        let name = field_name(attribute(node, "name")?);
+        let ty = format!("{}{}", owner, camel(&name));
+
+        let mut elems = children(node);
+        let mask = match elems.next().map(|e| self.expr(cur, e)) {
+            Some(Ok(Expr::Field(mask))) => mask,
+            _ => return Err(String::from("switch on an expression")),
+        };
+
+        let mut cases = Vec::new();
+        for case in elems {
+            if !case.has_tag_name("bitcase") {
+                return Err(format!("switch with <{}>", case.tag_name().name()));
+            }
+
+            let mut bits = Vec::new();
+            let mut fields = Vec::new();
+            for child in children(case) {
+                match child.tag_name().name() {
+                    "field" => fields.push(child),
+                    "enumref" | "value" => bits.push(self.expr(cur, child)?),
+                    tag => return Err(format!("bitcase with <{}>", tag)),
+                }
+            }
+
+            let bit = match (bits.as_slice(), fields.as_slice()) {
+                ([Expr::Enum(m, e, item)], [_]) => format!("{}::{}.0", path(&cur.name, m, e), item),
+                ([Expr::Value(value)], [_]) => value.to_string(),
+                _ => return Err(String::from("bitcase with several fields or bits")),
+            };
+
+            cases.push(Case {
+                name: field_name(attribute(fields[0], "name")?),
+                bit,
+                ty: self.field_ty(cur, fields[0])?,
+            });
+        }
+
+        let cur = cur.name.as_str();
+        let mut out = String::new();
+
+        writeln!(out, "#[derive(Debug, Clone, PartialEq, Default)]").unwrap();
+        writeln!(out, "pub struct {} {{", ty).unwrap();
+        for case in &cases {
+            writeln!(out, "    pub {}: Option<{}>,", case.name, case.ty.rust(cur)).unwrap();
+        }
+        writeln!(out, "}}\n\nimpl {} {{", ty).unwrap();
+
+        writeln!(out, "    /// The mask bits of the values that are set.").unwrap();
+        writeln!(
+            out,
+            "    pub fn mask(&self) -> u32 {{\n        let mut mask = 0;"
+        )
+        .unwrap();
+        for case in &cases {
+            writeln!(
+                out,
+                "        if self.{}.is_some() {{\n            mask |= {};\n        }}",
+                case.name, case.bit
+            )
+            .unwrap();
+        }
+        writeln!(out, "        mask\n    }}\n").unwrap();
+
+        writeln!(
+            out,
+            "    pub fn read(r: &mut super::Reader, mask: i64) -> anyhow::Result<Self> {{"
+        )
+        .unwrap();
+        writeln!(out, "        let mut values = Self::default();").unwrap();
+        for case in &cases {
+            writeln!(
+                out,
+                "        if mask & i64::from({}) != 0 {{\n            values.{} = Some({});\n        }}",
+                case.bit,
+                case.name,
+                case.ty.read(cur),
+            )
+            .unwrap();
+        }
+        writeln!(out, "        Ok(values)\n    }}\n").unwrap();
+
+        writeln!(
+            out,
+            "    pub fn write(&self, w: &mut Vec<u8>) -> anyhow::Result<()> {{"
+        )
+        .unwrap();
+        for case in &cases {
+            let value = match case.ty.is_copy() {
+                true => "(*v)",
+                false => "v",
+            };
+            writeln!(
+                out,
+                "        if let Some(v) = &self.{} {{\n            {}\n        }}",
+                case.name,
+                case.ty.write(value),
+            )
+            .unwrap();
+        }
+        writeln!(out, "        Ok(())\n    }}\n}}\n").unwrap();
+
+        Ok((Elem::Switch(name, ty, mask), out))
+    }
+}
+
+/// An event is generic if it comes in a GenericEvent, whose fields start
+/// after the extension, length and event type.
+fn event_kind(node: Node, number: u8) -> Kind {
+    match node.attribute("xge") {
+        Some("true") => Kind::GenericEvent(number),
+        _ => Kind::Event(number, node.attribute("no-sequence-number") != Some("true")),
+    }
+}
+
+fn camel(name: &str) -> String {
+    words(name)
+        .iter()
+        .map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().unwrap_or_default().to_ascii_uppercase();
+            first.to_string() + &chars.as_str().to_ascii_lowercase()
+        })
+        .collect()
+}
+
+/// How a request, event or error is put together.
+enum Kind {
+    Request(String),
+    Event(u8, bool),
+    /// A GenericEvent and its event type.
+    GenericEvent(u8),
+    Error(u8),
+}
+
+impl Generator {
+    fn module(&mut self, module: &Module) -> String {
+        let cur = module.name.as_str();
+        let root = module.root;
+
+        let name = |node: Node| -> String {
+            node.attribute("name")
+                .or_else(|| node.attribute("newname"))
+                .map(String::from)
+                .unwrap_or_default()
+        };
+
+        // Enums and requests get their own names if a type already has
+        // theirs.
+        let types = children(root)
+            .filter(|node| {
+                ["xidtype", "xidunion", "typedef", "struct", "union"]
+                    .contains(&node.tag_name().name())
+            })
+            .map(|node| type_name(&name(node)))
+            .collect::<HashSet<_>>();
+
+        let mut body = String::new();
+
+        for node in children(root).filter(|node| node.has_tag_name("enum")) {
+            body.push_str(&self.enumeration(cur, node, &types));
+        }
+
+        for node in children(root) {
+            let find = |tag: &str| {
+                let target = node.attribute("ref").unwrap_or_default();
+                children(root).find(|n| n.has_tag_name(tag) && n.attribute("name") == Some(target))
+            };
+
+            let number = || -> Result<u8> {
+                let number = attribute(node, "number")?;
+                number.parse().map_err(|_| format!("number {}", number))
+            };
+
+            let result = match node.tag_name().name() {
+                "xidtype" | "xidunion" => self.xid(module, node),
+                "typedef" => self.typedef(module, node),
+                "struct" => self.structure(module, node),
+                "union" => self.union(module, node),
+                "request" => self.request(module, node, &types),
+                "event" if name(node) == "GeGeneric" => {
+                    Err(String::from("it's read as event::Generic"))
+                }
+                "event" => number().and_then(|n| {
+                    let kind = event_kind(node, n);
+                    self.message(module, node, &name(node), kind)
+                }),
+                "eventcopy" => number().and_then(|n| match find("event") {
+                    Some(event) => self.message(module, event, &name(node), event_kind(event, n)),
+                    None => Err(String::from("copy of an unknown event")),
+                }),
+                "error" => {
+                    number().and_then(|n| self.message(module, node, &name(node), Kind::Error(n)))
+                }
+                "errorcopy" => number().and_then(|n| match find("error") {
+                    Some(error) => self.message(module, error, &name(node), Kind::Error(n)),
+                    None => Err(String::from("copy of an unknown error")),
+                }),
+                "eventstruct" => Err(String::from("event structs")),
+                _ => continue,
+            };
+
+            match result {
+                Ok(code) => body.push_str(&code),
+                Err(reason) => {
+                    writeln!(body, "// {} isn't generated: {}.\n", name(node), reason).unwrap();
+                    self.skipped.push((String::from(cur), name(node), reason));
+                }
+            }
+        }
+
+        let mut out = String::new();
+        match &module.extension {
+            Some((xname, major, minor)) => {
+                let title = root.attribute("extension-name").unwrap_or(xname);
+                writeln!(
+                    out,
+                    "/// The {} extension, version {}.{}.",
+                    title, major, minor
+                )
+                .unwrap();
+                writeln!(out, "{}\npub mod {} {{", ALLOW, cur).unwrap();
+                writeln!(
+                    out,
+                    "/// The name of the extension as known to QueryExtension."
+                )
+                .unwrap();
+                writeln!(out, "pub const EXTENSION: &str = {:?};", xname).unwrap();
+                writeln!(out, "pub const MAJOR_VERSION: u32 = {};", major).unwrap();
+                writeln!(out, "pub const MINOR_VERSION: u32 = {};\n", minor).unwrap();
+            }
+            None => {
+                writeln!(out, "/// The core protocol.").unwrap();
+                writeln!(out, "{}\npub mod {} {{", ALLOW, cur).unwrap();
+            }
+        }
+
+        if body.contains("BigEndian") {
+            writeln!(out, "use byteorder::{{BigEndian, WriteBytesExt}};\n").unwrap();
+        } else if body.contains("w.write_u8(") || body.contains("w.write_i8(") {
+            writeln!(out, "use byteorder::WriteBytesExt;\n").unwrap();
+        }
+
+        out.push_str(&body);
+        out.push_str("}\n\n");
+        out
+    }
+
+    fn enumeration(&mut self, cur: &str, node: Node, types: &HashSet<String>) -> String {
+        let xml = node.attribute("name").unwrap_or_default();
+        let mut rust = type_name(xml);
+        if types.contains(&rust) {
+            rust.push_str("Enum");
+        }
+
+        let mut items = HashMap::new();
+        let mut consts = Vec::new();
+        let mut mask = false;
+        let mut next = 0;
+
+        for item in children(node).filter(|n| n.has_tag_name("item")) {
+            let name = item.attribute("name").unwrap_or_default();
+            let value = children(item).find(|n| n.has_tag_name("value") || n.has_tag_name("bit"));
+
+            let value = match value {
+                Some(value) => {
+                    let text = value.text().unwrap_or_default().trim();
+                    let number = match text.strip_prefix("0x") {
+                        Some(hex) => i64::from_str_radix(hex, 16).unwrap_or_default(),
+                        None => text.parse().unwrap_or_default(),
+                    };
+                    match value.has_tag_name("bit") {
+                        true => {
+                            mask = true;
+                            1 << number
+                        }
+                        false => number,
+                    }
+                }
+                None => next,
+            };
+            next = value + 1;
+
+            let item_const = const_name(name);
+            items.insert(String::from(name), item_const.clone());
+            if !consts.iter().any(|(c, _)| *c == item_const) {
+                consts.push((item_const, value as u32));
+            }
+        }
+
+        self.enums.insert(
+            (String::from(cur), String::from(xml)),
+            (rust.clone(), items),
+        );
+
+        let mut out = brief(node).unwrap_or_default();
+        writeln!(
+            out,
+            "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]"
+        )
+        .unwrap();
+        writeln!(out, "pub struct {}(pub u32);\n", rust).unwrap();
+        writeln!(out, "impl {} {{", rust).unwrap();
+        for (name, value) in &consts {
+            writeln!(out, "    pub const {}: Self = Self({});", name, value).unwrap();
+        }
+
+        if mask {
+            writeln!(out, "\n    pub fn contains(self, other: Self) -> bool {{").unwrap();
+            writeln!(out, "        self.0 & other.0 == other.0\n    }}").unwrap();
+            writeln!(out, "}}\n").unwrap();
+            writeln!(out, "impl std::ops::BitOr for {} {{", rust).unwrap();
+            writeln!(out, "    type Output = Self;\n").unwrap();
+            writeln!(out, "    fn bitor(self, other: Self) -> Self {{\n        Self(self.0 | other.0)\n    }}").unwrap();
+            writeln!(out, "}}\n").unwrap();
+            writeln!(out, "impl std::ops::BitOrAssign for {} {{", rust).unwrap();
+            writeln!(out, "    fn bitor_assign(&mut self, other: Self) {{\n        self.0 |= other.0;\n    }}").unwrap();
+        }
+        writeln!(out, "}}\n").unwrap();
+
+        out
+    }
+
+    fn xid(&mut self, module: &Module, node: Node) -> Result<String> {
+        let cur = module.name.as_str();
+        let name = type_name(attribute(node, "name")?);
+
+        let mut out = String::new();
+        if cur == "xproto" && PROTO_RESOURCES.contains(&name.as_str()) {
+            writeln!(out, "pub use crate::proto::{};\n", name).unwrap();
+        } else {
+            writeln!(
+                out,
+                "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]"
+            )
+            .unwrap();
+            writeln!(out, "pub struct {}(pub u32);\n", name).unwrap();
+            writeln!(out, "impl From<u32> for {} {{\n    fn from(v: u32) -> Self {{\n        Self(v)\n    }}\n}}\n", name).unwrap();
+            writeln!(
+                out,
+                "impl From<{}> for u32 {{\n    fn from(v: {}) -> u32 {{\n        v.0\n    }}\n}}\n",
+                name, name
+            )
+            .unwrap();
+
+            for member in children(node).filter(|n| n.has_tag_name("type")) {
+                let member = self.resolve(module, member.text().unwrap_or_default())?;
+                if let Ty::Id(_, _) = member {
+                    let member = member.rust(cur);
+                    writeln!(out, "impl From<{}> for {} {{\n    fn from(v: {}) -> Self {{\n        Self(v.0)\n    }}\n}}\n", member, name, member).unwrap();
+                }
+            }
+        }
+
+        self.types.insert(
+            (String::from(cur), String::from(attribute(node, "name")?)),
+            Ty::Id(String::from(cur), name),
+        );
+
+        Ok(out)
+    }
+
+    fn typedef(&mut self, module: &Module, node: Node) -> Result<String> {
+        let cur = module.name.as_str();
+        let xml = attribute(node, "newname")?;
+        let name = type_name(xml);
+        let target = self.resolve(module, attribute(node, "oldname")?)?;
+
+        let (out, ty) = match cur == "xproto" && PROTO_RESOURCES.contains(&name.as_str()) {
+            true => (
+                format!("pub use crate::proto::{};\n\n", name),
+                Ty::Id(String::from(cur), name),
+            ),
+            false => (
+                format!("pub type {} = {};\n\n", name, target.rust(cur)),
+                Ty::Alias(String::from(cur), name, Box::new(target)),
+            ),
+        };
+
+        self.types
+            .insert((String::from(cur), String::from(xml)), ty);
+
+        Ok(out)
+    }
+
+    fn structure(&mut self, module: &Module, node: Node) -> Result<String> {
+        let cur = module.name.as_str();
+        let xml = attribute(node, "name")?;
+        let name = type_name(xml);
+        let body = self.body(module, node, &name)?;
+
+        let mut out = body.extra.clone();
+        out.push_str(&brief(node).unwrap_or_default());
+        writeln!(out, "#[derive(Debug, Clone, PartialEq, Default)]").unwrap();
+        writeln!(out, "pub struct {} {{\n{}}}\n", name, body.fields(cur)).unwrap();
+        writeln!(out, "impl {} {{", name).unwrap();
+        writeln!(
+            out,
+            "    pub fn read(r: &mut super::Reader) -> anyhow::Result<Self> {{"
+        )
+        .unwrap();
+        out.push_str(&body.read(cur, &body.elems, false)?);
+        writeln!(
+            out,
+            "        Ok(Self {{ {} }})\n    }}\n",
+            body.field_names()
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "    pub fn write(&self, w: &mut Vec<u8>) -> anyhow::Result<()> {{"
+        )
+        .unwrap();
+        out.push_str(&body.write(cur, &body.elems)?);
+        writeln!(out, "        Ok(())\n    }}\n}}\n").unwrap();
+
+        let size = body.elems.iter().map(Elem::size).sum();
+        self.types.insert(
+            (String::from(cur), String::from(xml)),
+            Ty::Struct(String::from(cur), name, size),
+        );
+
+        Ok(out)
+    }
+
+    fn union(&mut self, module: &Module, node: Node) -> Result<String> {
+        let cur = module.name.as_str();
+        let xml = attribute(node, "name")?;
+        let name = type_name(xml);
+
+        let mut size = 0;
+        for member in children(node) {
+            let member_size = match member.tag_name().name() {
+                "field" => self.field_ty(module, member)?.size(),
+                "list" => match children(member).next().map(|len| self.expr(module, len)) {
+                    Some(Ok(Expr::Value(n))) => self
+                        .field_ty(module, member)?
+                        .size()
+                        .map(|size| size * n as usize),
+                    _ => None,
+                },
+                _ => continue,
+            };
+            size = size.max(member_size.ok_or("union member without a fixed size")?);
+        }
+
+        let mut out = brief(node).unwrap_or_default();
+        writeln!(
+            out,
+            "/// The raw bytes of a union, to be read as one of its members."
+        )
+        .unwrap();
+        writeln!(out, "#[derive(Debug, Clone, PartialEq)]").unwrap();
+        writeln!(out, "pub struct {}(pub [u8; {}]);\n", name, size).unwrap();
+        writeln!(out, "impl Default for {} {{\n    fn default() -> Self {{\n        Self([0; {}])\n    }}\n}}\n", name, size).unwrap();
+        writeln!(out, "impl {} {{", name).unwrap();
+        writeln!(
+            out,
+            "    pub fn read(r: &mut super::Reader) -> anyhow::Result<Self> {{"
+        )
+        .unwrap();
+        writeln!(out, "        let mut bytes = [0; {}];\n        bytes.copy_from_slice(r.bytes({})?);\n        Ok(Self(bytes))\n    }}\n", size, size).unwrap();
+        writeln!(
+            out,
+            "    pub fn write(&self, w: &mut Vec<u8>) -> anyhow::Result<()> {{"
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "        w.extend_from_slice(&self.0);\n        Ok(())\n    }}\n}}\n"
+        )
+        .unwrap();
+
+        self.types.insert(
+            (String::from(cur), String::from(xml)),
+            Ty::Struct(String::from(cur), name, Some(size)),
+        );
+
+        Ok(out)
+    }
+
+    fn request(&self, module: &Module, node: Node, types: &HashSet<String>) -> Result<String> {
+        let cur = module.name.as_str();
+        let mut name = type_name(attribute(node, "name")?);
+        if types.contains(&name) {
+            name.push_str("Request");
+        }
+
+        let opcode = attribute(node, "opcode")?;
+        let body = self.body(module, node, &name)?;
+
+        let reply = match children(node).find(|n| n.has_tag_name("reply")) {
+            Some(reply) => Some(self.message(module, reply, &name, Kind::Request(name.clone()))?),
+            None => None,
+        };
+
+        let (header, elems) = match module.extension {
+            Some(_) => (None, &body.elems[..]),
+            None => body.split_header(),
+        };
+
+        let mut out = body.extra.clone();
+        out.push_str(&brief(node).unwrap_or_default());
+        writeln!(out, "#[derive(Debug, Clone, PartialEq, Default)]").unwrap();
+        writeln!(out, "pub struct {} {{\n{}}}\n", name, body.fields(cur)).unwrap();
+        writeln!(out, "impl {} {{", name).unwrap();
+        writeln!(
+            out,
+            "    fn encode_body(&self) -> anyhow::Result<Vec<u8>> {{"
+        )
+        .unwrap();
+        writeln!(out, "        let w = &mut vec![0; 4];").unwrap();
+        out.push_str(&body.write(cur, elems)?);
+        writeln!(
+            out,
+            "        super::align(w, 4);\n        Ok(w.split_off(4))\n    }}\n}}\n"
+        )
+        .unwrap();
+
+        match &module.extension {
+            Some(_) => {
+                writeln!(out, "impl crate::request::ExtensionRequest for {} {{", name).unwrap();
+                writeln!(
+                    out,
+                    "    type Reply = {};\n",
+                    reply.as_ref().map_or("()", |_| "Reply")
+                )
+                .unwrap();
+                writeln!(out, "    const EXTENSION: &'static str = EXTENSION;\n").unwrap();
+                writeln!(out, "    const MINOR_OPCODE: u8 = {};\n", opcode).unwrap();
+            }
+            None => {
+                writeln!(out, "impl crate::request::Request for {} {{", name).unwrap();
+                writeln!(
+                    out,
+                    "    type Reply = {};\n",
+                    reply.as_ref().map_or("()", |_| "Reply")
+                )
+                .unwrap();
+                writeln!(out, "    const OPCODE: u8 = {};\n", opcode).unwrap();
+            }
+        }
+        let out = out.replace(
+            "    type Reply = Reply;",
+            &format!("    type Reply = {}Reply;", name),
+        );
+        let mut out = out;
+
+        writeln!(out, "    fn length(&self) -> usize {{").unwrap();
+        writeln!(
+            out,
+            "        self.encode_body().map_or(0, |body| 1 + body.len() / 4)\n    }}\n"
+        )
+        .unwrap();
+
+        if let Some(data) = header
+            .map(|header| body.data(cur, header))
+            .transpose()?
+            .flatten()
+        {
+            writeln!(
+                out,
+                "    fn data(&self) -> u8 {{\n        {}\n    }}\n",
+                data
+            )
+            .unwrap();
+        }
+
+        writeln!(
+            out,
+            "    fn write_body<W: std::io::Write>(&self, w: &mut W) -> anyhow::Result<()> {{"
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "        w.write_all(&self.encode_body()?)?;\n        Ok(())\n    }}\n}}\n"
+        )
+        .unwrap();
+
+        if let Some(reply) = reply {
+            out.push_str(&reply);
+        }
+
+        Ok(out)
+    }
+
+    /// A reply, event or error: a fixed header followed by the fields.
+    fn message(&self, module: &Module, node: Node, xml: &str, kind: Kind) -> Result<String> {
+        let cur = module.name.as_str();
+        let name = match &kind {
+            Kind::Request(request) => format!("{}Reply", request),
+            Kind::Event(_, _) | Kind::GenericEvent(_) => format!("{}Event", type_name(xml)),
+            Kind::Error(_) => format!("{}Error", type_name(xml)),
+        };
+
+        let mut body = self.body(module, node, &name)?;
+        // Replies and generic events have a length field of their own.
+        body.reply = matches!(kind, Kind::Request(_) | Kind::GenericEvent(_));
+        let (header, elems) = match kind {
+            Kind::Request(_) | Kind::Event(_, true) => body.split_header(),
+            _ => (None, &body.elems[..]),
+        };
+
+        let mut out = body.extra.clone();
+        if !matches!(kind, Kind::Request(_)) {
+            out.push_str(&brief(node).unwrap_or_default());
+        }
+        writeln!(out, "#[derive(Debug, Clone, PartialEq, Default)]").unwrap();
+        writeln!(out, "pub struct {} {{\n{}}}\n", name, body.fields(cur)).unwrap();
+        writeln!(out, "impl {} {{", name).unwrap();
+
+        let (what, arg) = match kind {
+            Kind::Request(_) => ("a reply, starting with its first byte", "reply"),
+            Kind::Event(number, _) => {
+                writeln!(out, "    pub const NUMBER: u8 = {};\n", number).unwrap();
+                ("an event, starting with its code", "event")
+            }
+            Kind::GenericEvent(number) => {
+                writeln!(out, "    /// The event type of the GenericEvent.").unwrap();
+                writeln!(out, "    pub const NUMBER: u16 = {};\n", number).unwrap();
+                ("a GenericEvent, starting with its code", "event")
+            }
+            Kind::Error(number) => {
+                writeln!(out, "    pub const NUMBER: u8 = {};\n", number).unwrap();
+                ("an error, starting with its first byte", "error")
+            }
+        };
+
+        writeln!(out, "    /// Decodes {}.", what).unwrap();
+        writeln!(
+            out,
+            "    pub fn decode({}: &[u8]) -> anyhow::Result<Self> {{",
+            arg
+        )
+        .unwrap();
+        writeln!(out, "        let r = &mut super::Reader::new({});", arg).unwrap();
+
+        let open = body.reply;
+        let rest = body.read(cur, elems, open)?;
+        match kind {
+            Kind::Request(_) | Kind::Event(_, true) => {
+                writeln!(out, "        r.skip(1)?;").unwrap();
+                match header {
+                    Some(header) => {
+                        out.push_str(&body.read(cur, std::slice::from_ref(header), open)?)
+                    }
+                    None => writeln!(out, "        r.skip(1)?;").unwrap(),
+                }
+                writeln!(out, "        r.skip(2)?;").unwrap();
+                if let Kind::Request(_) = kind {
+                    match rest.contains("i64::from(length)") {
+                        true => writeln!(out, "        let length = r.u32()?;").unwrap(),
+                        false => writeln!(out, "        r.skip(4)?;").unwrap(),
+                    }
+                }
+            }
+            Kind::GenericEvent(_) => {
+                writeln!(out, "        r.skip(4)?;").unwrap();
+                match rest.contains("i64::from(length)") {
+                    true => writeln!(out, "        let length = r.u32()?;").unwrap(),
+                    false => writeln!(out, "        r.skip(4)?;").unwrap(),
+                }
+                writeln!(out, "        r.skip(2)?;").unwrap();
+            }
+            Kind::Event(_, false) => writeln!(out, "        r.skip(1)?;").unwrap(),
+            Kind::Error(_) => writeln!(out, "        r.skip(4)?;").unwrap(),
+        }
+
+        out.push_str(&rest);
+        writeln!(out, "        Ok(Self {{ {} }})\n    }}", body.field_names()).unwrap();
+
+        if let Kind::Event(_, sequence) = kind {
+            writeln!(
+                out,
+                "\n    /// Encodes the event for SendEvent. `code` is `NUMBER`, plus the first"
+            )
+            .unwrap();
+            writeln!(out, "    /// event of the extension for extension events.").unwrap();
+            writeln!(
+                out,
+                "    pub fn encode(&self, code: u8) -> anyhow::Result<Vec<u8>> {{"
+            )
+            .unwrap();
+            writeln!(out, "        let w = &mut vec![code];").unwrap();
+            if sequence {
+                match header {
+                    Some(header) => out.push_str(&body.write(cur, std::slice::from_ref(header))?),
+                    None => writeln!(out, "        w.push(0);").unwrap(),
+                }
+                writeln!(out, "        w.resize(w.len() + 2, 0);").unwrap();
+            }
+            out.push_str(&body.write(cur, elems)?);
+            writeln!(
+                out,
+                "        anyhow::ensure!(w.len() <= 32, \"event is too long\");"
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "        w.resize(32, 0);\n        Ok(std::mem::take(w))\n    }}"
+            )
+            .unwrap();
+        }
+
+        writeln!(out, "}}\n").unwrap();
+
+        if let Kind::Request(_) = kind {
+            writeln!(out, "impl crate::reply::Reply for {} {{", name).unwrap();
+            writeln!(
+                out,
+                "    fn read<T: std::io::Read>(r: &mut T) -> anyhow::Result<Self> {{"
+            )
+            .unwrap();
+            writeln!(out, "        // The reply code has already been read.").unwrap();
+            writeln!(out, "        let mut reply = vec![1];").unwrap();
+            writeln!(out, "        r.read_to_end(&mut reply)?;").unwrap();
+            writeln!(out, "        Self::decode(&reply)\n    }}\n}}\n").unwrap();
+        }
+
+        Ok(out)
+    }
+}
+
+impl Body {
+    /// The byte following the opcode of a core request.
+    fn data(&self, cur: &str, elem: &Elem) -> Result<Option<String>> {
+        Ok(match elem {
+            Elem::Field(name, ty) => match self.derived.get(name) {
+                Some(Derived::Len(list)) => Some(format!("self.{}.len() as u8", list)),
+                Some(Derived::Mask(switch)) => Some(format!("self.{}.mask() as u8", switch)),
+                None => Some(match ty.prim().map(|prim| prim.rust) {
+                    Some("bool") => format!("u8::from(self.{})", name),
+                    Some("i8") => format!("self.{} as u8", name),
+                    _ if matches!(ty, Ty::Enum(_, _, _)) => format!("self.{}.0 as u8", name),
+                    Some("u8") => format!("self.{}", name),
+                    _ => return Err(String::from("header field that isn't a byte")),
+                }),
+            },
+            Elem::ExprField(ty, expr) => {
+                let expr = self.write_expr(cur, expr)?;
+                Some(match ty.prim().map(|prim| prim.rust) {
+                    Some("bool") => format!("u8::from({} != 0)", expr),
+                    _ => format!("{} as u8", expr),
+                })
+            }
+            _ => None,
+        })
+    }
+}
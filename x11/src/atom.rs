@@ -1,3 +1,9 @@
+use crate::proto;
+use std::collections::HashMap;
+use std::fmt;
+use std::str;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Atom {
     Primary = 1,
     Secondary,
@@ -68,3 +74,286 @@ pub enum Atom {
     WmClass,
     WmTransientFor,
 }
+
+impl Atom {
+    /// The canonical X name of the atom, like `WM_NAME`.
+    pub fn name(self) -> &'static str {
+        NAMES[u32::from(self) as usize - 1]
+    }
+}
+
+impl TryFrom<u32> for Atom {
+    type Error = anyhow::Error;
+
+    fn try_from(v: u32) -> anyhow::Result<Self> {
+        match v {
+            1 => Ok(Atom::Primary),
+            2 => Ok(Atom::Secondary),
+            3 => Ok(Atom::Arc),
+            4 => Ok(Atom::Atom),
+            5 => Ok(Atom::Bitmap),
+            6 => Ok(Atom::Cardinal),
+            7 => Ok(Atom::Colormap),
+            8 => Ok(Atom::Cursor),
+            9 => Ok(Atom::CutBuffer0),
+            10 => Ok(Atom::CutBuffer1),
+            11 => Ok(Atom::CutBuffer2),
+            12 => Ok(Atom::CutBuffer3),
+            13 => Ok(Atom::CutBuffer4),
+            14 => Ok(Atom::CutBuffer5),
+            15 => Ok(Atom::CutBuffer6),
+            16 => Ok(Atom::CutBuffer7),
+            17 => Ok(Atom::Drawable),
+            18 => Ok(Atom::Font),
+            19 => Ok(Atom::Integer),
+            20 => Ok(Atom::Pixmap),
+            21 => Ok(Atom::Point),
+            22 => Ok(Atom::Rectangle),
+            23 => Ok(Atom::ResourceManager),
+            24 => Ok(Atom::RgbColorMap),
+            25 => Ok(Atom::RgbBestMap),
+            26 => Ok(Atom::RgbBlueMap),
+            27 => Ok(Atom::RgbDefaultMap),
+            28 => Ok(Atom::RgbGrayMap),
+            29 => Ok(Atom::RgbGreenMap),
+            30 => Ok(Atom::RgbRedMap),
+            31 => Ok(Atom::String),
+            32 => Ok(Atom::Visualid),
+            33 => Ok(Atom::Window),
+            34 => Ok(Atom::WmCommand),
+            35 => Ok(Atom::WmHints),
+            36 => Ok(Atom::WmClientMachine),
+            37 => Ok(Atom::WmIconName),
+            38 => Ok(Atom::WmIconSize),
+            39 => Ok(Atom::WmName),
+            40 => Ok(Atom::WmNormalHints),
+            41 => Ok(Atom::WmSizeHints),
+            42 => Ok(Atom::WmZoomHints),
+            43 => Ok(Atom::MinSpace),
+            44 => Ok(Atom::NormSpace),
+            45 => Ok(Atom::MaxSpace),
+            46 => Ok(Atom::EndSpace),
+            47 => Ok(Atom::SuperscriptX),
+            48 => Ok(Atom::SuperscriptY),
+            49 => Ok(Atom::SubscriptX),
+            50 => Ok(Atom::SubscriptY),
+            51 => Ok(Atom::UnderlinePosition),
+            52 => Ok(Atom::UnderlineThickness),
+            53 => Ok(Atom::StrikeoutAscent),
+            54 => Ok(Atom::StrikeoutDescent),
+            55 => Ok(Atom::ItalicAngle),
+            56 => Ok(Atom::XHeight),
+            57 => Ok(Atom::QuadWidth),
+            58 => Ok(Atom::Weight),
+            59 => Ok(Atom::PointSize),
+            60 => Ok(Atom::Resolution),
+            61 => Ok(Atom::Copyright),
+            62 => Ok(Atom::Notice),
+            63 => Ok(Atom::FontName),
+            64 => Ok(Atom::FamilyName),
+            65 => Ok(Atom::FullName),
+            66 => Ok(Atom::CapHeight),
+            67 => Ok(Atom::WmClass),
+            68 => Ok(Atom::WmTransientFor),
+
+            _ => anyhow::bail!("bad Atom: {}", v),
+        }
+    }
+}
+
+impl From<Atom> for u32 {
+    fn from(v: Atom) -> u32 {
+        v as u32
+    }
+}
+
+impl TryFrom<proto::Atom> for Atom {
+    type Error = anyhow::Error;
+
+    fn try_from(v: proto::Atom) -> anyhow::Result<Self> {
+        Atom::try_from(v.0)
+    }
+}
+
+impl From<Atom> for proto::Atom {
+    fn from(v: Atom) -> proto::Atom {
+        proto::Atom(v.into())
+    }
+}
+
+impl PartialEq<Atom> for proto::Atom {
+    fn eq(&self, other: &Atom) -> bool {
+        self.0 == u32::from(*other)
+    }
+}
+
+impl PartialEq<proto::Atom> for Atom {
+    fn eq(&self, other: &proto::Atom) -> bool {
+        u32::from(*self) == other.0
+    }
+}
+
+impl str::FromStr for Atom {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match NAMES.iter().position(|name| *name == s) {
+            Some(i) => Atom::try_from(u32::try_from(i)? + 1),
+            None => anyhow::bail!("bad Atom name: {}", s),
+        }
+    }
+}
+
+impl fmt::Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The names of the predefined atoms, in order starting from atom 1.
+pub const NAMES: [&str; 68] = [
+    "PRIMARY",
+    "SECONDARY",
+    "ARC",
+    "ATOM",
+    "BITMAP",
+    "CARDINAL",
+    "COLORMAP",
+    "CURSOR",
+    "CUT_BUFFER0",
+    "CUT_BUFFER1",
+    "CUT_BUFFER2",
+    "CUT_BUFFER3",
+    "CUT_BUFFER4",
+    "CUT_BUFFER5",
+    "CUT_BUFFER6",
+    "CUT_BUFFER7",
+    "DRAWABLE",
+    "FONT",
+    "INTEGER",
+    "PIXMAP",
+    "POINT",
+    "RECTANGLE",
+    "RESOURCE_MANAGER",
+    "RGB_COLOR_MAP",
+    "RGB_BEST_MAP",
+    "RGB_BLUE_MAP",
+    "RGB_DEFAULT_MAP",
+    "RGB_GRAY_MAP",
+    "RGB_GREEN_MAP",
+    "RGB_RED_MAP",
+    "STRING",
+    "VISUALID",
+    "WINDOW",
+    "WM_COMMAND",
+    "WM_HINTS",
+    "WM_CLIENT_MACHINE",
+    "WM_ICON_NAME",
+    "WM_ICON_SIZE",
+    "WM_NAME",
+    "WM_NORMAL_HINTS",
+    "WM_SIZE_HINTS",
+    "WM_ZOOM_HINTS",
+    "MIN_SPACE",
+    "NORM_SPACE",
+    "MAX_SPACE",
+    "END_SPACE",
+    "SUPERSCRIPT_X",
+    "SUPERSCRIPT_Y",
+    "SUBSCRIPT_X",
+    "SUBSCRIPT_Y",
+    "UNDERLINE_POSITION",
+    "UNDERLINE_THICKNESS",
+    "STRIKEOUT_ASCENT",
+    "STRIKEOUT_DESCENT",
+    "ITALIC_ANGLE",
+    "X_HEIGHT",
+    "QUAD_WIDTH",
+    "WEIGHT",
+    "POINT_SIZE",
+    "RESOLUTION",
+    "COPYRIGHT",
+    "NOTICE",
+    "FONT_NAME",
+    "FAMILY_NAME",
+    "FULL_NAME",
+    "CAP_HEIGHT",
+    "WM_CLASS",
+    "WM_TRANSIENT_FOR",
+];
+
+/// Maps atom names to atoms and back. Starts out with the predefined atoms.
+#[derive(Debug)]
+pub struct Cache {
+    atoms: HashMap<String, proto::Atom>,
+    names: HashMap<proto::Atom, String>,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        let mut cache = Self {
+            atoms: HashMap::new(),
+            names: HashMap::new(),
+        };
+
+        for (atom, name) in (1..).zip(NAMES) {
+            cache.insert(name, proto::Atom(atom));
+        }
+
+        cache
+    }
+}
+
+impl Cache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, name: &str, atom: proto::Atom) {
+        self.atoms.insert(String::from(name), atom);
+        self.names.insert(atom, String::from(name));
+    }
+
+    pub fn atom(&self, name: &str) -> Option<proto::Atom> {
+        self.atoms.get(name).copied()
+    }
+
+    pub fn name(&self, atom: proto::Atom) -> Option<&str> {
+        self.names.get(&atom).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `WmTransientFor` becomes `WM_TRANSIENT_FOR`.
+    fn screaming(atom: Atom) -> String {
+        let mut name = String::new();
+        for (i, c) in format!("{:?}", atom).chars().enumerate() {
+            if i > 0 && c.is_ascii_uppercase() {
+                name.push('_');
+            }
+            name.push(c.to_ascii_uppercase());
+        }
+        name
+    }
+
+    #[test]
+    fn names_match_the_discriminants() -> anyhow::Result<()> {
+        for (v, name) in (1..).zip(NAMES) {
+            let atom = Atom::try_from(v)?;
+
+            assert_eq!(u32::from(atom), v);
+            assert_eq!(atom.name(), name);
+            assert_eq!(screaming(atom), name);
+            assert_eq!(name.parse::<Atom>()?, atom);
+        }
+
+        let after = u32::try_from(NAMES.len())? + 1;
+        assert!(Atom::try_from(after).is_err());
+        assert!(Atom::try_from(0).is_err());
+
+        Ok(())
+    }
+}
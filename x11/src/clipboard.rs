@@ -0,0 +1,1130 @@
+use crate::{atom, display, event, icccm, proto, reply, request};
+use std::collections::HashMap;
+use std::mem;
+use std::time::{Duration, Instant};
+
+pub const CLIPBOARD: &str = "CLIPBOARD";
+pub const TARGETS: &str = "TARGETS";
+pub const MULTIPLE: &str = "MULTIPLE";
+pub const TIMESTAMP: &str = "TIMESTAMP";
+pub const INCR: &str = "INCR";
+pub const ATOM_PAIR: &str = "ATOM_PAIR";
+pub const TEXT: &str = "TEXT";
+pub const UTF8_STRING: &str = "UTF8_STRING";
+
+/// The property on the clipboard window that selections are converted
+/// into.
+const TRANSFER: &str = "_X11_SELECTION";
+
+/// The property on the clipboard window that `timestamp` appends to. It's
+/// never used for anything else, so its notifications can't be mistaken
+/// for those of a transfer.
+const TIMESTAMP_PROPERTY: &str = "_X11_TIMESTAMP";
+
+/// Data bigger than this is sent incrementally with INCR, in chunks of this
+/// size. It's a multiple of four, so chunks never split a value of format
+/// 16 or 32.
+const INCR_CHUNK: usize = 64 * 1024;
+
+/// INCR transfers the requestor hasn't taken a chunk of for this long are
+/// given up.
+const INCR_TIMEOUT: Duration = Duration::from_secs(30);
+
+const PROPERTY_NEW_VALUE: u8 = 0;
+const PROPERTY_DELETED: u8 = 1;
+
+/// A selection converted to some target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Data {
+    pub property_type: proto::Atom,
+    pub format: u8,
+    /// The values, big-endian for formats 16 and 32.
+    pub value: Vec<u8>,
+}
+
+impl Data {
+    /// Data of format 8.
+    pub fn new(property_type: proto::Atom, value: Vec<u8>) -> Self {
+        Self {
+            property_type,
+            format: 8,
+            value,
+        }
+    }
+
+    /// Data of format 32.
+    pub fn u32s(property_type: proto::Atom, values: &[u32]) -> Self {
+        Self {
+            property_type,
+            format: 32,
+            value: values.iter().flat_map(|v| v.to_be_bytes()).collect(),
+        }
+    }
+
+    /// The chunk of an INCR transfer starting at `offset`, empty past the
+    /// end.
+    fn chunk(&self, offset: usize) -> Data {
+        let start = offset.min(self.value.len());
+        let end = (offset + INCR_CHUNK).min(self.value.len());
+
+        Data {
+            property_type: self.property_type,
+            format: self.format,
+            value: self.value[start..end].to_vec(),
+        }
+    }
+
+    fn change(&self, window: proto::Window, property: proto::Atom) -> request::ChangeProperty {
+        match self.format {
+            16 => {
+                let values = self
+                    .value
+                    .chunks_exact(2)
+                    .map(|v| u16::from_be_bytes([v[0], v[1]]))
+                    .collect::<Vec<_>>();
+
+                request::ChangeProperty::u16s(window, property, self.property_type, &values)
+            }
+
+            32 => {
+                let values = self
+                    .value
+                    .chunks_exact(4)
+                    .map(|v| u32::from_be_bytes([v[0], v[1], v[2], v[3]]))
+                    .collect::<Vec<_>>();
+
+                request::ChangeProperty::u32s(window, property, self.property_type, &values)
+            }
+
+            _ => request::ChangeProperty::new(window, property, self.property_type, &self.value),
+        }
+    }
+}
+
+impl From<reply::GetProperty> for Data {
+    fn from(reply: reply::GetProperty) -> Self {
+        Self {
+            property_type: reply.type_,
+            format: reply.format,
+            value: reply.value,
+        }
+    }
+}
+
+/// A selection owned by the clipboard.
+#[derive(Debug)]
+struct Owned {
+    time: proto::Timestamp,
+    targets: HashMap<proto::Atom, Data>,
+}
+
+/// An INCR transfer to another client. Each time the requestor deletes the
+/// property, the next chunk is written to it.
+#[derive(Debug)]
+struct Transfer {
+    requestor: proto::Window,
+    property: proto::Atom,
+    data: Data,
+    offset: usize,
+    /// When the last chunk was written.
+    updated: Instant,
+    /// The events selected on the requestor before the first transfer to
+    /// it, put back once the last one ends.
+    mask: proto::EventMask,
+}
+
+/// Copies and pastes selections such as PRIMARY and CLIPBOARD, following
+/// ICCCM. Events have to be passed to `handle` for as long as the clipboard
+/// owns a selection.
+#[derive(Debug)]
+pub struct Clipboard {
+    window: proto::Window,
+    owned: HashMap<proto::Atom, Owned>,
+    transfers: Vec<Transfer>,
+}
+
+impl Clipboard {
+    /// Uses `window`, usually an unmapped window of the caller's own, to own
+    /// selections and to receive converted ones. PropertyChange is selected
+    /// on it.
+    pub async fn new(
+        display: &mut display::Display,
+        window: proto::Window,
+    ) -> anyhow::Result<Self> {
+        display.watch_properties(window).await?;
+
+        Ok(Self {
+            window,
+            owned: HashMap::new(),
+            transfers: Vec::new(),
+        })
+    }
+
+    pub fn owns(&self, selection: proto::Atom) -> bool {
+        self.owned.contains_key(&selection)
+    }
+
+    /// Gets the current server time, which `copy` needs, by appending
+    /// nothing to a property and waiting for its PropertyNotify.
+    pub async fn timestamp(
+        &mut self,
+        display: &mut display::Display,
+        timeout: Duration,
+    ) -> anyhow::Result<proto::Timestamp> {
+        let window = self.window;
+        let property = display.intern_atom(TIMESTAMP_PROPERTY).await?;
+
+        let matches = move |event: &event::Event| match event {
+            event::Event::PropertyNotify(notify) => {
+                notify.window == window
+                    && notify.atom == property
+                    && notify.state == PROPERTY_NEW_VALUE
+            }
+            _ => false,
+        };
+
+        // A notification left over from an earlier call that timed out
+        // would carry an old time.
+        display.discard_events(matches).await?;
+
+        display
+            .send(
+                request::ChangeProperty::new(window, property, atom::Atom::String.into(), &[])
+                    .mode(proto::PropertyMode::Append),
+            )
+            .await?;
+
+        let event = display.wait_for_event(matches, timeout).await?;
+
+        match event {
+            Some(event::Event::PropertyNotify(notify)) => Ok(notify.time),
+            _ => anyhow::bail!("timed out waiting for a timestamp"),
+        }
+    }
+
+    /// Converts `selection` to `target`. Returns `None` if there is no owner
+    /// or it can't convert to the target. Large data sent incrementally
+    /// with INCR is put back together.
+    pub async fn paste(
+        &mut self,
+        display: &mut display::Display,
+        selection: proto::Atom,
+        target: proto::Atom,
+        time: proto::Timestamp,
+        timeout: Duration,
+    ) -> anyhow::Result<Option<Data>> {
+        // Asking ourselves would wait for an answer nobody sends.
+        if let Some(owned) = self.owned.get(&selection) {
+            return Ok(owned.targets.get(&target).cloned());
+        }
+
+        let window = self.window;
+        let property = display.intern_atom(TRANSFER).await?;
+
+        display
+            .send(&request::ConvertSelection::new(
+                window,
+                selection,
+                target,
+                Some(property),
+                time,
+            ))
+            .await?;
+
+        let event = display
+            .wait_for_event(
+                |event| match event {
+                    event::Event::SelectionNotify(notify) => {
+                        notify.requestor == window && notify.selection == selection
+                    }
+                    _ => false,
+                },
+                timeout,
+            )
+            .await?;
+
+        let property = match event {
+            Some(event::Event::SelectionNotify(notify)) => match notify.property {
+                Some(property) => property,
+                None => return Ok(None),
+            },
+            _ => anyhow::bail!("timed out waiting for the selection"),
+        };
+
+        let value = match display.get_property(window, property, None).await? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        display.delete_property(window, property).await?;
+
+        let incr = display.intern_atom(INCR).await?;
+        if value.type_ != incr {
+            return Ok(Some(value.into()));
+        }
+
+        // Deleting the INCR property started the transfer. Every chunk is
+        // a new value of the property, and an empty one ends it.
+        let mut data: Option<Data> = None;
+
+        loop {
+            let event = display
+                .wait_for_event(
+                    |event| match event {
+                        event::Event::PropertyNotify(notify) => {
+                            notify.window == window
+                                && notify.atom == property
+                                && notify.state == PROPERTY_NEW_VALUE
+                        }
+                        _ => false,
+                    },
+                    timeout,
+                )
+                .await?;
+
+            anyhow::ensure!(event.is_some(), "timed out during an INCR transfer");
+
+            // Notifications from before the transfer started find the
+            // property deleted.
+            let chunk = match display.get_property(window, property, None).await? {
+                Some(chunk) => chunk,
+                None => continue,
+            };
+            display.delete_property(window, property).await?;
+
+            if chunk.value.is_empty() {
+                return Ok(Some(data.unwrap_or_else(|| chunk.into())));
+            }
+
+            match &mut data {
+                Some(data) => data.value.extend(chunk.value),
+                None => data = Some(chunk.into()),
+            }
+        }
+    }
+
+    /// Pastes `selection` as text, preferring UTF8_STRING over STRING.
+    pub async fn paste_text(
+        &mut self,
+        display: &mut display::Display,
+        selection: proto::Atom,
+        time: proto::Timestamp,
+        timeout: Duration,
+    ) -> anyhow::Result<Option<String>> {
+        let utf8_string = display.intern_atom(UTF8_STRING).await?;
+
+        for target in [utf8_string, atom::Atom::String.into()] {
+            let data = match self
+                .paste(display, selection, target, time, timeout)
+                .await?
+            {
+                Some(data) if data.format == 8 => data,
+                _ => continue,
+            };
+
+            let name = display.atom_name(data.property_type).await?;
+            if let Some(encoding) = icccm::Encoding::from_name(&name) {
+                return Ok(Some(icccm::decode_text(encoding, &data.value)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Takes ownership of `selection`, offering it as `targets`. `time` has
+    /// to be a real timestamp, like that of the event that caused the copy
+    /// or one from `timestamp`; ICCCM doesn't allow CurrentTime. Returns
+    /// whether the clipboard got ownership.
+    pub async fn copy(
+        &mut self,
+        display: &mut display::Display,
+        selection: proto::Atom,
+        time: proto::Timestamp,
+        targets: HashMap<proto::Atom, Data>,
+    ) -> anyhow::Result<bool> {
+        display
+            .send(&request::SetSelectionOwner::new(
+                Some(self.window),
+                selection,
+                time,
+            ))
+            .await?;
+
+        let owner = display
+            .send(&request::GetSelectionOwner::new(selection))
+            .await?
+            .owner;
+
+        if owner != self.window {
+            return Ok(false);
+        }
+
+        self.owned.insert(selection, Owned { time, targets });
+
+        Ok(true)
+    }
+
+    /// Copies text, offered as UTF8_STRING, TEXT and STRING.
+    pub async fn copy_text(
+        &mut self,
+        display: &mut display::Display,
+        selection: proto::Atom,
+        time: proto::Timestamp,
+        text: &str,
+    ) -> anyhow::Result<bool> {
+        let utf8_string = display.intern_atom(UTF8_STRING).await?;
+        let text_target = display.intern_atom(TEXT).await?;
+
+        let utf8 = Data::new(utf8_string, text.as_bytes().to_vec());
+        let latin1 = text
+            .chars()
+            .map(|c| u8::try_from(c).unwrap_or(b'?'))
+            .collect();
+
+        let targets = HashMap::from([
+            (utf8_string, utf8.clone()),
+            (text_target, utf8),
+            (
+                atom::Atom::String.into(),
+                Data::new(atom::Atom::String.into(), latin1),
+            ),
+        ]);
+
+        self.copy(display, selection, time, targets).await
+    }
+
+    /// Gives up ownership of `selection`.
+    pub async fn clear(
+        &mut self,
+        display: &mut display::Display,
+        selection: proto::Atom,
+        time: proto::Timestamp,
+    ) -> anyhow::Result<()> {
+        if self.owned.remove(&selection).is_some() {
+            display
+                .send(&request::SetSelectionOwner::new(None, selection, time))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Handles requests for the selections the clipboard owns, the loss of
+    /// ownership, and the progress of INCR transfers. Returns whether the
+    /// event was one of these. Transfers to requestors that were destroyed
+    /// or stopped taking chunks are dropped.
+    pub async fn handle(
+        &mut self,
+        display: &mut display::Display,
+        event: &event::Event,
+    ) -> anyhow::Result<bool> {
+        let (expired, transfers): (Vec<_>, Vec<_>) = mem::take(&mut self.transfers)
+            .into_iter()
+            .partition(|transfer| transfer.updated.elapsed() >= INCR_TIMEOUT);
+        self.transfers = transfers;
+
+        for transfer in &expired {
+            self.restore_events(display, transfer).await?;
+        }
+
+        match event {
+            event::Event::SelectionRequest(request) if request.owner == self.window => {
+                self.answer(display, request).await?;
+                Ok(true)
+            }
+
+            event::Event::SelectionClear(clear) if clear.owner == self.window => {
+                self.owned.remove(&clear.selection);
+                Ok(true)
+            }
+
+            event::Event::PropertyNotify(notify) if notify.state == PROPERTY_DELETED => {
+                self.continue_transfer(display, notify).await
+            }
+
+            event::Event::DestroyNotify(destroy) => {
+                let transfers = self.transfers.len();
+                self.transfers
+                    .retain(|transfer| transfer.requestor != destroy.window);
+                Ok(self.transfers.len() != transfers)
+            }
+
+            _ => Ok(false),
+        }
+    }
+
+    async fn answer(
+        &mut self,
+        display: &mut display::Display,
+        request: &event::SelectionRequest,
+    ) -> anyhow::Result<()> {
+        // Clients written before ICCCM 2.0 leave out the property.
+        let property = request.property.unwrap_or(request.target);
+        let multiple = display.intern_atom(MULTIPLE).await?;
+
+        // Requests from before the clipboard got the selection are refused.
+        let owned = self.owned.get(&request.selection).map(|owned| owned.time);
+        let converted = match owned {
+            Some(time)
+                if request.time == proto::Timestamp::CURRENT_TIME || request.time >= time =>
+            {
+                if request.target == multiple {
+                    self.convert_multiple(display, request.selection, request.requestor, property)
+                        .await?
+                } else {
+                    self.convert(
+                        display,
+                        request.selection,
+                        request.requestor,
+                        request.target,
+                        property,
+                    )
+                    .await?
+                }
+            }
+            _ => false,
+        };
+
+        let notify = event::SelectionNotify {
+            time: request.time,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property: Some(property).filter(|_| converted),
+        };
+
+        display
+            .send(&request::SendEvent::new(
+                proto::Destination::Window(request.requestor),
+                notify.encode(),
+            ))
+            .await
+    }
+
+    /// Stores the selection converted to `target` in `property` on
+    /// `requestor`. Returns whether it could be converted.
+    async fn convert(
+        &mut self,
+        display: &mut display::Display,
+        selection: proto::Atom,
+        requestor: proto::Window,
+        target: proto::Atom,
+        property: proto::Atom,
+    ) -> anyhow::Result<bool> {
+        let atoms = display
+            .intern_atoms(&[TARGETS, MULTIPLE, TIMESTAMP])
+            .await?;
+        let (targets, multiple, timestamp) = (atoms[0], atoms[1], atoms[2]);
+
+        let owned = match self.owned.get(&selection) {
+            Some(owned) => owned,
+            None => return Ok(false),
+        };
+
+        let data = if target == targets {
+            let atoms = [targets, multiple, timestamp]
+                .iter()
+                .chain(owned.targets.keys())
+                .map(|atom| atom.0)
+                .collect::<Vec<_>>();
+
+            Data::u32s(atom::Atom::Atom.into(), &atoms)
+        } else if target == timestamp {
+            Data::u32s(atom::Atom::Integer.into(), &[owned.time.0])
+        } else {
+            match owned.targets.get(&target) {
+                Some(data) => data.clone(),
+                None => return Ok(false),
+            }
+        };
+
+        self.send_data(display, requestor, property, data).await?;
+
+        Ok(true)
+    }
+
+    /// Answers MULTIPLE, whose property lists pairs of targets and
+    /// properties. Pairs that can't be converted get their property
+    /// replaced with None.
+    async fn convert_multiple(
+        &mut self,
+        display: &mut display::Display,
+        selection: proto::Atom,
+        requestor: proto::Window,
+        property: proto::Atom,
+    ) -> anyhow::Result<bool> {
+        let atom_pair = display.intern_atom(ATOM_PAIR).await?;
+        let multiple = display.intern_atom(MULTIPLE).await?;
+
+        let mut pairs = match display
+            .get_property(requestor, property, Some(atom_pair))
+            .await?
+        {
+            Some(value) => value.u32s()?,
+            None => return Ok(false),
+        };
+
+        for pair in pairs.chunks_exact_mut(2) {
+            let (target, target_property) = (proto::Atom(pair[0]), proto::Atom(pair[1]));
+
+            let converted = target_property.0 != 0
+                && target != multiple
+                && self
+                    .convert(display, selection, requestor, target, target_property)
+                    .await?;
+
+            if !converted {
+                pair[1] = 0;
+            }
+        }
+
+        display
+            .send(&request::ChangeProperty::u32s(
+                requestor, property, atom_pair, &pairs,
+            ))
+            .await?;
+
+        Ok(true)
+    }
+
+    async fn send_data(
+        &mut self,
+        display: &mut display::Display,
+        requestor: proto::Window,
+        property: proto::Atom,
+        data: Data,
+    ) -> anyhow::Result<()> {
+        if data.value.len() <= INCR_CHUNK {
+            return display.send(&data.change(requestor, property)).await;
+        }
+
+        // The requestor deleting the property is the signal for the next
+        // chunk, and its window going away ends the transfer.
+        let mask = match self.transfers.iter().find(|t| t.requestor == requestor) {
+            Some(transfer) => transfer.mask,
+            None => {
+                display
+                    .select_more_events(
+                        requestor,
+                        proto::EventMask::PROPERTY_CHANGE | proto::EventMask::STRUCTURE_NOTIFY,
+                    )
+                    .await?
+            }
+        };
+
+        let incr = display.intern_atom(INCR).await?;
+        let size = u32::try_from(data.value.len()).unwrap_or(u32::MAX);
+
+        display
+            .send(&request::ChangeProperty::u32s(
+                requestor,
+                property,
+                incr,
+                &[size],
+            ))
+            .await?;
+
+        self.transfers.push(Transfer {
+            requestor,
+            property,
+            data,
+            offset: 0,
+            updated: Instant::now(),
+            mask,
+        });
+
+        Ok(())
+    }
+
+    async fn continue_transfer(
+        &mut self,
+        display: &mut display::Display,
+        notify: &event::PropertyNotify,
+    ) -> anyhow::Result<bool> {
+        let i = match self
+            .transfers
+            .iter()
+            .position(|t| t.requestor == notify.window && t.property == notify.atom)
+        {
+            Some(i) => i,
+            None => return Ok(false),
+        };
+
+        let transfer = &mut self.transfers[i];
+        let chunk = transfer.data.chunk(transfer.offset);
+        transfer.offset += chunk.value.len();
+        transfer.updated = Instant::now();
+
+        let (requestor, property) = (transfer.requestor, transfer.property);
+        display.send(&chunk.change(requestor, property)).await?;
+
+        // The empty chunk ends the transfer.
+        if chunk.value.is_empty() {
+            let transfer = self.transfers.remove(i);
+            self.restore_events(display, &transfer).await?;
+        }
+
+        Ok(true)
+    }
+
+    /// Puts back the events selected on the requestor of a transfer that
+    /// ended, unless another transfer to it is still going on.
+    async fn restore_events(
+        &self,
+        display: &mut display::Display,
+        transfer: &Transfer,
+    ) -> anyhow::Result<()> {
+        if self
+            .transfers
+            .iter()
+            .any(|t| t.requestor == transfer.requestor)
+        {
+            return Ok(());
+        }
+
+        display
+            .restore_events(transfer.requestor, transfer.mask)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake;
+    use crate::request::{
+        ChangeProperty, ChangeWindowAttributes, ConvertSelection, DeleteProperty, GetProperty,
+        GetSelectionOwner, GetWindowAttributes, Request, SendEvent, SetSelectionOwner,
+    };
+
+    const WINDOW: proto::Window = proto::Window(0x20_0001);
+    const REQUESTOR: proto::Window = proto::Window(0x40_0001);
+
+    /// A clipboard on `WINDOW`, on a display connected to a fake server.
+    async fn clipboard() -> anyhow::Result<(Clipboard, display::Display, fake::Server)> {
+        let (mut display, mut server) = fake::connect()?;
+
+        let (clipboard, ()) = tokio::try_join!(Clipboard::new(&mut display, WINDOW), async {
+            server.expect(GetWindowAttributes::OPCODE).await?;
+            server
+                .reply_window_attributes(proto::EventMask::empty())
+                .await?;
+            server.expect(ChangeWindowAttributes::OPCODE).await?;
+            anyhow::Ok(())
+        })?;
+
+        Ok((clipboard, display, server))
+    }
+
+    /// Takes CLIPBOARD at time 100, offering `value` as UTF8_STRING.
+    async fn own(
+        clipboard: &mut Clipboard,
+        display: &mut display::Display,
+        server: &mut fake::Server,
+        value: &[u8],
+    ) -> anyhow::Result<proto::Atom> {
+        let selection = server.atom(CLIPBOARD);
+        let utf8_string = server.atom(UTF8_STRING);
+        let targets = HashMap::from([(utf8_string, Data::new(utf8_string, value.to_vec()))]);
+
+        let (owned, ()) = tokio::try_join!(
+            clipboard.copy(display, selection, proto::Timestamp(100), targets),
+            async {
+                server.expect(SetSelectionOwner::OPCODE).await?;
+                server.expect(GetSelectionOwner::OPCODE).await?;
+                server.reply(0, &WINDOW.0.to_be_bytes()).await?;
+                anyhow::Ok(())
+            }
+        )?;
+        assert!(owned);
+
+        Ok(selection)
+    }
+
+    fn selection_request(
+        time: u32,
+        selection: proto::Atom,
+        target: proto::Atom,
+        property: proto::Atom,
+    ) -> anyhow::Result<event::Event> {
+        fake::decode(&fake::selection_request(
+            proto::Timestamp(time),
+            WINDOW,
+            REQUESTOR,
+            selection,
+            target,
+            property,
+        ))
+    }
+
+    /// The property of the SelectionNotify sent to `REQUESTOR`.
+    fn notified(request: &fake::Request) -> anyhow::Result<Option<proto::Atom>> {
+        match request.event()? {
+            event::Event::SelectionNotify(notify) if notify.requestor == REQUESTOR => {
+                Ok(notify.property)
+            }
+            event => anyhow::bail!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn data_is_changed_with_its_format() -> anyhow::Result<()> {
+        let (window, property, property_type) = (WINDOW, proto::Atom(100), proto::Atom(101));
+
+        let data = Data::new(property_type, b"abc".to_vec());
+        let change = data.change(window, property);
+        assert_eq!((change.format, change.data_len), (8, 3));
+        assert_eq!(change.data, b"abc");
+
+        let data = Data {
+            property_type,
+            format: 16,
+            value: vec![0, 1, 0, 2],
+        };
+        assert_eq!(
+            data.change(window, property).encode()?,
+            ChangeProperty::u16s(window, property, property_type, &[1, 2]).encode()?
+        );
+
+        let data = Data::u32s(property_type, &[1, 0x0102_0304]);
+        let change = data.change(window, property);
+        assert_eq!((change.format, change.data_len), (32, 2));
+        assert_eq!(change.data, [0, 0, 0, 1, 1, 2, 3, 4]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn chunks_cover_the_data_and_end_empty() {
+        let data = Data::new(proto::Atom(100), vec![7; 2 * INCR_CHUNK + 3]);
+
+        let sizes = [0, INCR_CHUNK, 2 * INCR_CHUNK, 2 * INCR_CHUNK + 3]
+            .iter()
+            .map(|&offset| data.chunk(offset).value.len())
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, [INCR_CHUNK, INCR_CHUNK, 3, 0]);
+
+        // Chunks of format 32 never split a value.
+        let data = Data::u32s(proto::Atom(100), &vec![1; INCR_CHUNK / 4 + 1]);
+        let chunk = data.chunk(INCR_CHUNK);
+        assert_eq!((chunk.format, chunk.value), (32, vec![0, 0, 0, 1]));
+    }
+
+    #[tokio::test]
+    async fn timestamp_skips_stale_notifications() -> anyhow::Result<()> {
+        let (mut clipboard, mut display, mut server) = clipboard().await?;
+        let property = server.atom(TIMESTAMP_PROPERTY);
+
+        // Left over from an earlier call that gave up waiting.
+        let stale =
+            fake::property_notify(WINDOW, property, proto::Timestamp(5), PROPERTY_NEW_VALUE);
+        server.send(&stale).await?;
+
+        let (time, ()) = tokio::try_join!(
+            clipboard.timestamp(&mut display, Duration::from_secs(5)),
+            async {
+                server.expect(ChangeProperty::OPCODE).await?;
+                let notify = fake::property_notify(
+                    WINDOW,
+                    property,
+                    proto::Timestamp(42),
+                    PROPERTY_NEW_VALUE,
+                );
+                server.send(&notify).await?;
+                anyhow::Ok(())
+            }
+        )?;
+        assert_eq!(time, proto::Timestamp(42));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn targets_and_timestamp_are_answered() -> anyhow::Result<()> {
+        let (mut clipboard, mut display, mut server) = clipboard().await?;
+        let selection = own(&mut clipboard, &mut display, &mut server, b"hi").await?;
+
+        let targets = server.atom(TARGETS);
+        let multiple = server.atom(MULTIPLE);
+        let timestamp = server.atom(TIMESTAMP);
+        let utf8_string = server.atom(UTF8_STRING);
+        let property = server.atom("PROPERTY");
+
+        let request = selection_request(200, selection, targets, property)?;
+        let (handled, (change, notify)) =
+            tokio::try_join!(clipboard.handle(&mut display, &request), async {
+                let change = server.expect(ChangeProperty::OPCODE).await?;
+                let notify = server.expect(SendEvent::OPCODE).await?;
+                anyhow::Ok((change, notify))
+            })?;
+        assert!(handled);
+        assert_eq!(
+            change.data,
+            ChangeProperty::atoms(
+                REQUESTOR,
+                property,
+                &[targets, multiple, timestamp, utf8_string]
+            )
+            .encode()?
+        );
+        assert_eq!(notified(&notify)?, Some(property));
+
+        let request = selection_request(200, selection, timestamp, property)?;
+        let (_, (change, notify)) =
+            tokio::try_join!(clipboard.handle(&mut display, &request), async {
+                let change = server.expect(ChangeProperty::OPCODE).await?;
+                let notify = server.expect(SendEvent::OPCODE).await?;
+                anyhow::Ok((change, notify))
+            })?;
+        assert_eq!(
+            change.data,
+            ChangeProperty::u32s(REQUESTOR, property, atom::Atom::Integer.into(), &[100])
+                .encode()?
+        );
+        assert_eq!(notified(&notify)?, Some(property));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn requests_from_before_ownership_are_refused() -> anyhow::Result<()> {
+        let (mut clipboard, mut display, mut server) = clipboard().await?;
+        let selection = own(&mut clipboard, &mut display, &mut server, b"hi").await?;
+        let utf8_string = server.atom(UTF8_STRING);
+        let property = server.atom("PROPERTY");
+
+        // Nothing is stored, and the requestor is told so.
+        let request = selection_request(50, selection, utf8_string, property)?;
+        let (_, notify) = tokio::try_join!(
+            clipboard.handle(&mut display, &request),
+            server.expect(SendEvent::OPCODE)
+        )?;
+        assert_eq!(notified(&notify)?, None);
+
+        // CurrentTime is taken to be now.
+        let request = selection_request(0, selection, utf8_string, property)?;
+        let (_, (change, notify)) =
+            tokio::try_join!(clipboard.handle(&mut display, &request), async {
+                let change = server.expect(ChangeProperty::OPCODE).await?;
+                let notify = server.expect(SendEvent::OPCODE).await?;
+                anyhow::Ok((change, notify))
+            })?;
+        assert_eq!(
+            change.data,
+            ChangeProperty::new(REQUESTOR, property, utf8_string, b"hi").encode()?
+        );
+        assert_eq!(notified(&notify)?, Some(property));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn multiple_refuses_the_pairs_it_cant_convert() -> anyhow::Result<()> {
+        let (mut clipboard, mut display, mut server) = clipboard().await?;
+        let selection = own(&mut clipboard, &mut display, &mut server, b"hi").await?;
+
+        let multiple = server.atom(MULTIPLE);
+        let atom_pair = server.atom(ATOM_PAIR);
+        let utf8_string = server.atom(UTF8_STRING);
+        let unknown = server.atom("UNKNOWN");
+        let property = server.atom("PROPERTY");
+        let (first, second, third) = (server.atom("P1"), server.atom("P2"), server.atom("P3"));
+
+        let pairs = [
+            utf8_string,
+            first,
+            unknown,
+            second,
+            multiple,
+            third,
+            utf8_string,
+            proto::Atom(0),
+        ];
+        let value = pairs
+            .iter()
+            .flat_map(|atom| atom.0.to_be_bytes())
+            .collect::<Vec<_>>();
+
+        let request = selection_request(200, selection, multiple, property)?;
+        let (_, (get, converted, answer, notify)) =
+            tokio::try_join!(clipboard.handle(&mut display, &request), async {
+                let get = server.expect(GetProperty::OPCODE).await?;
+                server.reply_property(32, atom_pair, 0, &value).await?;
+                let converted = server.expect(ChangeProperty::OPCODE).await?;
+                let answer = server.expect(ChangeProperty::OPCODE).await?;
+                let notify = server.expect(SendEvent::OPCODE).await?;
+                anyhow::Ok((get, converted, answer, notify))
+            })?;
+
+        assert_eq!(
+            (get.u32(4), get.u32(8), get.u32(12)),
+            (REQUESTOR.0, property.0, atom_pair.0)
+        );
+        assert_eq!(
+            converted.data,
+            ChangeProperty::new(REQUESTOR, first, utf8_string, b"hi").encode()?
+        );
+
+        let refused = [utf8_string, first, unknown, proto::Atom(0)]
+            .iter()
+            .chain(&[multiple, proto::Atom(0), utf8_string, proto::Atom(0)])
+            .map(|atom| atom.0)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            answer.data,
+            ChangeProperty::u32s(REQUESTOR, property, atom_pair, &refused).encode()?
+        );
+        assert_eq!(notified(&notify)?, Some(property));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn large_data_is_sent_with_incr() -> anyhow::Result<()> {
+        let (mut clipboard, mut display, mut server) = clipboard().await?;
+        let value = (0..2 * INCR_CHUNK + 3).map(|i| i as u8).collect::<Vec<_>>();
+        let selection = own(&mut clipboard, &mut display, &mut server, &value).await?;
+
+        let incr = server.atom(INCR);
+        let utf8_string = server.atom(UTF8_STRING);
+        let property = server.atom("PROPERTY");
+
+        let request = selection_request(200, selection, utf8_string, property)?;
+        let (_, (select, start, notify)) =
+            tokio::try_join!(clipboard.handle(&mut display, &request), async {
+                server.expect(GetWindowAttributes::OPCODE).await?;
+                server
+                    .reply_window_attributes(proto::EventMask::KEY_PRESS)
+                    .await?;
+                let select = server.expect(ChangeWindowAttributes::OPCODE).await?;
+                let start = server.expect(ChangeProperty::OPCODE).await?;
+                let notify = server.expect(SendEvent::OPCODE).await?;
+                anyhow::Ok((select, start, notify))
+            })?;
+
+        let mask = proto::EventMask::KEY_PRESS
+            | proto::EventMask::PROPERTY_CHANGE
+            | proto::EventMask::STRUCTURE_NOTIFY;
+        assert_eq!(
+            select.data,
+            ChangeWindowAttributes::new(REQUESTOR)
+                .event_mask(mask)
+                .encode()?
+        );
+        assert_eq!(
+            start.data,
+            ChangeProperty::u32s(REQUESTOR, property, incr, &[value.len() as u32]).encode()?
+        );
+        assert_eq!(notified(&notify)?, Some(property));
+
+        // Each deletion of the property asks for the next chunk.
+        let deleted = fake::decode(&fake::property_notify(
+            REQUESTOR,
+            property,
+            proto::Timestamp(300),
+            PROPERTY_DELETED,
+        ))?;
+
+        for chunk in value.chunks(INCR_CHUNK) {
+            let (handled, change) = tokio::try_join!(
+                clipboard.handle(&mut display, &deleted),
+                server.expect(ChangeProperty::OPCODE)
+            )?;
+            assert!(handled);
+            assert_eq!(
+                change.data,
+                ChangeProperty::new(REQUESTOR, property, utf8_string, chunk).encode()?
+            );
+        }
+
+        // The empty chunk ends the transfer, and the requestor gets its
+        // event mask back.
+        let (_, (end, restore)) =
+            tokio::try_join!(clipboard.handle(&mut display, &deleted), async {
+                let end = server.expect(ChangeProperty::OPCODE).await?;
+                let restore = server.expect(ChangeWindowAttributes::OPCODE).await?;
+                anyhow::Ok((end, restore))
+            })?;
+        assert_eq!(
+            end.data,
+            ChangeProperty::new(REQUESTOR, property, utf8_string, &[]).encode()?
+        );
+        assert_eq!(
+            restore.data,
+            ChangeWindowAttributes::new(REQUESTOR)
+                .event_mask(proto::EventMask::KEY_PRESS)
+                .encode()?
+        );
+
+        assert!(!clipboard.handle(&mut display, &deleted).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn incr_data_is_put_back_together() -> anyhow::Result<()> {
+        let (mut clipboard, mut display, mut server) = clipboard().await?;
+
+        let selection = server.atom(CLIPBOARD);
+        let utf8_string = server.atom(UTF8_STRING);
+        let incr = server.atom(INCR);
+        let property = server.atom(TRANSFER);
+
+        let new_value =
+            fake::property_notify(WINDOW, property, proto::Timestamp(300), PROPERTY_NEW_VALUE);
+
+        let (data, ()) = tokio::try_join!(
+            clipboard.paste(
+                &mut display,
+                selection,
+                utf8_string,
+                proto::Timestamp(200),
+                Duration::from_secs(5)
+            ),
+            async {
+                let convert = server.expect(ConvertSelection::OPCODE).await?;
+                assert_eq!(
+                    convert.data,
+                    ConvertSelection::new(
+                        WINDOW,
+                        selection,
+                        utf8_string,
+                        Some(property),
+                        proto::Timestamp(200)
+                    )
+                    .encode()?
+                );
+
+                let notify = event::SelectionNotify {
+                    time: proto::Timestamp(200),
+                    requestor: WINDOW,
+                    selection,
+                    target: utf8_string,
+                    property: Some(property),
+                };
+                server.send(&notify.encode()).await?;
+
+                server.expect(GetProperty::OPCODE).await?;
+                server
+                    .reply_property(32, incr, 0, &11u32.to_be_bytes())
+                    .await?;
+                server.expect(DeleteProperty::OPCODE).await?;
+
+                // The last chunk isn't a multiple of four bytes.
+                for chunk in [&b"hello "[..], b"world", b""] {
+                    server.send(&new_value).await?;
+                    server.expect(GetProperty::OPCODE).await?;
+                    server.reply_property(8, utf8_string, 0, chunk).await?;
+
+                    let delete = server.expect(DeleteProperty::OPCODE).await?;
+                    assert_eq!(delete.data, DeleteProperty::new(WINDOW, property).encode()?);
+                }
+
+                anyhow::Ok(())
+            }
+        )?;
+
+        assert_eq!(data, Some(Data::new(utf8_string, b"hello world".to_vec())));
+
+        Ok(())
+    }
+}
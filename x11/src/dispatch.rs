@@ -0,0 +1,195 @@
+use crate::{display, event, proto};
+use std::collections::HashMap;
+
+pub type Handler<D = display::Display> =
+    Box<dyn FnMut(&mut D, &event::Event) -> anyhow::Result<()>>;
+
+/// Routes events to handlers registered per window and event kind. An event
+/// goes to the handlers of the window it's about and of the window it was
+/// reported on, so handlers on a parent see the structure events of its
+/// children. All handlers for a window are dropped once its DestroyNotify
+/// has been dispatched.
+pub struct Dispatcher<D = display::Display> {
+    handlers: HashMap<(proto::Window, event::Kind), Vec<Handler<D>>>,
+}
+
+impl<D> Default for Dispatcher<D> {
+    fn default() -> Self {
+        Self {
+            handlers: HashMap::new(),
+        }
+    }
+}
+
+impl<D> Dispatcher<D> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn on<F>(&mut self, window: proto::Window, kind: event::Kind, handler: F) -> &mut Self
+    where
+        F: FnMut(&mut D, &event::Event) -> anyhow::Result<()> + 'static,
+    {
+        self.handlers
+            .entry((window, kind))
+            .or_default()
+            .push(Box::new(handler));
+        self
+    }
+
+    pub fn remove(&mut self, window: proto::Window) {
+        self.handlers.retain(|(w, _), _| *w != window);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+
+    /// Calls the handlers registered for an event, those of the window it
+    /// was reported on first. Returns whether there were any. A failing
+    /// handler stops the ones after it.
+    pub fn handle(&mut self, display: &mut D, event: &event::Event) -> anyhow::Result<bool> {
+        let window = match event.window() {
+            Some(window) => window,
+            None => return Ok(false),
+        };
+
+        let mut windows = vec![window];
+        if let Some(reported) = event.event_window() {
+            if reported != window {
+                windows.insert(0, reported);
+            }
+        }
+
+        let handled = self.call(display, event, &windows);
+
+        // The window is gone whether or not its handlers succeeded.
+        if let event::Event::DestroyNotify(_) = event {
+            self.remove(window);
+        }
+
+        handled
+    }
+
+    fn call(
+        &mut self,
+        display: &mut D,
+        event: &event::Event,
+        windows: &[proto::Window],
+    ) -> anyhow::Result<bool> {
+        let mut handled = false;
+        for &window in windows {
+            if let Some(handlers) = self.handlers.get_mut(&(window, event.kind())) {
+                for handler in handlers.iter_mut() {
+                    handler(display, event)?;
+                }
+                handled = true;
+            }
+        }
+
+        Ok(handled)
+    }
+}
+
+impl Dispatcher {
+    /// Reads the next message and dispatches it. Returns the message if it
+    /// wasn't an event with a registered handler.
+    pub async fn dispatch(
+        &mut self,
+        display: &mut display::Display,
+    ) -> anyhow::Result<Option<display::Message>> {
+        match display.next_message().await? {
+            display::Message::Event(event) => {
+                if self.handle(display, &event)? {
+                    Ok(None)
+                } else {
+                    Ok(Some(display::Message::Event(event)))
+                }
+            }
+
+            message => Ok(Some(message)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map_request(parent: u32, window: u32) -> event::Event {
+        event::Event::MapRequest(event::MapRequest {
+            parent: proto::Window(parent),
+            window: proto::Window(window),
+        })
+    }
+
+    #[test]
+    fn parent_receives_child_map_request() -> anyhow::Result<()> {
+        let mut dispatcher = Dispatcher::<Vec<proto::Window>>::new();
+        dispatcher.on(proto::Window(1), event::Kind::MapRequest, |seen, event| {
+            seen.extend(event.window());
+            Ok(())
+        });
+
+        let mut seen = Vec::new();
+        assert!(dispatcher.handle(&mut seen, &map_request(1, 2))?);
+        assert!(!dispatcher.handle(&mut seen, &map_request(3, 4))?);
+        assert_eq!(seen, [proto::Window(2)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parent_and_child_handlers_both_run() -> anyhow::Result<()> {
+        let mut dispatcher = Dispatcher::<Vec<u32>>::new();
+        dispatcher
+            .on(proto::Window(1), event::Kind::MapRequest, |seen, _| {
+                seen.push(1);
+                Ok(())
+            })
+            .on(proto::Window(2), event::Kind::MapRequest, |seen, _| {
+                seen.push(2);
+                Ok(())
+            });
+
+        let mut seen = Vec::new();
+        dispatcher.handle(&mut seen, &map_request(1, 2))?;
+        assert_eq!(seen, [1, 2]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn destroy_notify_drops_child_handlers() -> anyhow::Result<()> {
+        let mut dispatcher = Dispatcher::<()>::new();
+        dispatcher.on(proto::Window(2), event::Kind::MapRequest, |_, _| Ok(()));
+
+        let destroy = event::Event::DestroyNotify(event::DestroyNotify {
+            event: proto::Window(1),
+            window: proto::Window(2),
+        });
+        dispatcher.handle(&mut (), &destroy)?;
+
+        assert!(dispatcher.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn destroy_notify_drops_handlers_even_if_one_fails() {
+        let mut dispatcher = Dispatcher::<()>::new();
+        dispatcher
+            .on(proto::Window(2), event::Kind::DestroyNotify, |_, _| {
+                anyhow::bail!("failed")
+            })
+            .on(proto::Window(2), event::Kind::MapRequest, |_, _| Ok(()));
+
+        let destroy = event::Event::DestroyNotify(event::DestroyNotify {
+            event: proto::Window(1),
+            window: proto::Window(2),
+        });
+        assert!(dispatcher.handle(&mut (), &destroy).is_err());
+
+        assert!(dispatcher.is_empty());
+    }
+}
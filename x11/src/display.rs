@@ -1,53 +1,148 @@
-use crate::{error, event, proto, request, setup};
+use crate::request::{ExtensionRequest, Request};
+use crate::{atom, error, event, property, proto, protocol, reply, request, setup, stream};
+use std::collections::{HashMap, VecDeque};
 use std::env;
-use std::io::{self, Read, Seek};
-use std::os::unix::net::UnixStream;
-use std::time::Duration;
-use tokio::task;
+use std::io;
+use std::marker::PhantomData;
+use std::os::unix;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::UnixStream;
+use tokio::sync::{broadcast, mpsc};
+use tokio::{runtime, task, time};
 
 #[derive(Debug)]
 pub enum Message {
-    Error(error::Error),
-    Reply(u16),
+    Error(u16, error::Error),
+    /// A reply with its sequence number, starting after the reply code.
+    Reply(u16, Vec<u8>),
     Event(event::Event),
 }
 
-pub struct Display {
-    conn: UnixStream,
-    pub setup: setup::Setup,
+/// Opt-in event compression applied by `Display::next_message`. Event
+/// stream subscribers always see the uncompressed events.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Compression {
+    /// Coalesce consecutive MotionNotify events for the same window into the
+    /// latest one.
+    pub motion: bool,
+    /// Coalesce consecutive ConfigureNotify events for the same window into
+    /// the latest one.
+    pub configure: bool,
+    /// Merge a burst of Expose events for a window into a single
+    /// `Event::ExposeRegion`, delivered once the event with count 0 arrives.
+    pub expose: bool,
 }
 
-impl io::Read for Display {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.conn.read(buf)
-    }
+/// A request that has been sent, but whose reply hasn't been read yet.
+#[derive(Debug)]
+#[must_use]
+pub struct Cookie<T> {
+    sequence: u16,
+    reply: PhantomData<T>,
 }
 
-impl io::Write for Display {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.conn.write(buf)
+impl<T> Cookie<T> {
+    pub fn sequence(&self) -> u16 {
+        self.sequence
     }
+}
+
+/// Events queued for the display by the task reading the connection. A
+/// display that is only used through subscribers never takes them, so past
+/// `stream::CAPACITY` events are left to the subscribers alone.
+#[derive(Debug, Default)]
+struct Backlog {
+    events: AtomicUsize,
+    /// Whether events were left out since the display last looked.
+    dropped: AtomicBool,
+}
+
+/// A connection to an X server. Messages are read by a task of their own,
+/// which publishes events to subscribers as soon as they arrive and queues
+/// every message, in order, for the display. The display keeps at most
+/// `stream::CAPACITY` events it hasn't handed out; like a subscriber that
+/// falls behind, it skips events past that.
+pub struct Display {
+    conn: OwnedWriteHalf,
+    messages: mpsc::UnboundedReceiver<anyhow::Result<Message>>,
+    backlog: Arc<Backlog>,
+    reader: task::JoinHandle<()>,
+    events: broadcast::Sender<event::Event>,
+    pending: VecDeque<Message>,
+    compression: Compression,
+    sequence: u16,
+    /// Write timeout of the connection, and how long the reader waits for
+    /// the rest of a message once its first byte has arrived.
+    timeout: Duration,
+    atoms: atom::Cache,
+    properties: property::Cache,
+    /// QueryExtension replies by extension name, None for extensions the
+    /// server doesn't have.
+    extensions: HashMap<&'static str, Option<protocol::xproto::QueryExtensionReply>>,
+    pub setup: setup::Setup,
+}
 
-    fn flush(&mut self) -> io::Result<()> {
-        self.conn.flush()
+impl Drop for Display {
+    fn drop(&mut self) {
+        self.reader.abort();
     }
 }
 
 impl Display {
+    /// Connects to the display named by `DISPLAY`. Has to be called from
+    /// within a Tokio runtime, which runs the task reading the connection.
     pub fn open() -> anyhow::Result<Self> {
-        let addr = env::var("DISPLAY")?;
-        let mut conn = UnixStream::connect(&addr)?;
+        let runtime = runtime::Handle::try_current()?;
 
-        {
-            let timeout = Duration::from_secs(5);
-            conn.set_write_timeout(Some(timeout))?;
-            conn.set_read_timeout(Some(timeout))?;
-        }
+        let name = env::var("DISPLAY")?;
+        let mut conn = unix::net::UnixStream::connect(socket_path(&name))?;
+
+        let timeout = Duration::from_secs(5);
+        conn.set_write_timeout(Some(timeout))?;
+        conn.set_read_timeout(Some(timeout))?;
 
         let setup = setup::Setup::new(&mut conn)?;
-        println!("{:?}", setup);
 
-        Ok(Self { conn, setup })
+        conn.set_nonblocking(true)?;
+        let _runtime = runtime.enter();
+        Ok(Self::new(UnixStream::from_std(conn)?, setup, timeout))
+    }
+
+    /// Takes over a connection whose setup has already been read. Has to be
+    /// called from within a Tokio runtime.
+    pub(crate) fn new(conn: UnixStream, setup: setup::Setup, timeout: Duration) -> Self {
+        let (reader, conn) = conn.into_split();
+
+        let (events, _) = broadcast::channel(stream::CAPACITY);
+        let (sender, messages) = mpsc::unbounded_channel();
+        let backlog = Arc::new(Backlog::default());
+        let reader = tokio::spawn(read_messages(
+            reader,
+            timeout,
+            sender,
+            backlog.clone(),
+            events.clone(),
+        ));
+
+        Self {
+            conn,
+            messages,
+            backlog,
+            reader,
+            events,
+            pending: VecDeque::new(),
+            compression: Compression::default(),
+            sequence: 0,
+            timeout,
+            atoms: atom::Cache::new(),
+            properties: property::Cache::new(),
+            extensions: HashMap::new(),
+            setup,
+        }
     }
 
     pub fn screen(&self) -> Option<&setup::Screen> {
@@ -58,42 +153,1148 @@ impl Display {
         self.screen().map(|screen| screen.root_window)
     }
 
-    pub fn next_message(&mut self) -> anyhow::Result<Message> {
-        self.conn.set_read_timeout(None)?;
+    /// Subscribes to the events read from the connection. Events are
+    /// published as soon as they arrive, whether or not anything reads
+    /// messages from the display.
+    pub fn subscribe(&self) -> stream::EventStream {
+        stream::EventStream::new(self.events.subscribe())
+    }
+
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
+    pub async fn next_message(&mut self) -> anyhow::Result<Message> {
+        let message = match self.pending.pop_front() {
+            Some(message) => message,
+            None => match self.read_message(None).await? {
+                Some(message) => message,
+                None => anyhow::bail!("no message"),
+            },
+        };
 
-        let mut buf: [u8; 32] = [0; 32];
-        self.conn.read_exact(&mut buf)?;
+        match message {
+            Message::Event(event) => Ok(Message::Event(self.compress(event).await?)),
+            message => Ok(message),
+        }
+    }
+
+    async fn compress(&mut self, event: event::Event) -> anyhow::Result<event::Event> {
+        use event::Event;
+
+        match event {
+            Event::MotionNotify(mut motion) if self.compression.motion => {
+                while let Some(Message::Event(Event::MotionNotify(next))) =
+                    self.peek_message().await?
+                {
+                    if next.event != motion.event {
+                        break;
+                    }
+
+                    if let Some(Message::Event(Event::MotionNotify(next))) =
+                        self.pending.pop_front()
+                    {
+                        motion = next;
+                    }
+                }
+
+                Ok(Event::MotionNotify(motion))
+            }
+
+            Event::ConfigureNotify(mut configure) if self.compression.configure => {
+                while let Some(Message::Event(Event::ConfigureNotify(next))) =
+                    self.peek_message().await?
+                {
+                    if next.window != configure.window {
+                        break;
+                    }
+
+                    if let Some(Message::Event(Event::ConfigureNotify(next))) =
+                        self.pending.pop_front()
+                    {
+                        configure = next;
+                    }
+                }
+
+                Ok(Event::ConfigureNotify(configure))
+            }
 
-        Ok(match buf[0] {
-            proto::RESPONSE_ERROR => {
-                let mut cursor = io::Cursor::new(&buf[1..]);
-                let error = error::Error::read(&mut cursor)?;
-                Message::Error(error)
+            Event::Expose(expose) if self.compression.expose => {
+                let mut region = event::ExposeRegion::new(expose.window);
+                let mut count = expose.count;
+                region.add(expose.into());
+
+                // The rest of the burst follows; anything else read in the
+                // meantime is delivered after the region.
+                let mut other = Vec::new();
+                while count > 0 {
+                    let message = match self.pending.pop_front() {
+                        Some(message) => message,
+                        None => match self.read_message(None).await? {
+                            Some(message) => message,
+                            None => anyhow::bail!("no message"),
+                        },
+                    };
+
+                    match message {
+                        Message::Event(Event::Expose(next)) if next.window == region.window => {
+                            count = next.count;
+                            region.add(next.into());
+                        }
+                        message => other.push(message),
+                    }
+                }
+
+                for message in other.into_iter().rev() {
+                    self.pending.push_front(message);
+                }
+
+                Ok(Event::ExposeRegion(region))
             }
 
-            proto::RESPONSE_REPLY => {
-                use byteorder::{BigEndian, ReadBytesExt};
+            event => Ok(event),
+        }
+    }
+
+    /// Returns the next message without removing it, reading one from the
+    /// connection only if it's already available.
+    async fn peek_message(&mut self) -> anyhow::Result<Option<&Message>> {
+        if self.pending.is_empty() {
+            if let Some(message) = self.read_message(Some(Duration::ZERO)).await? {
+                self.keep(message);
+            }
+        }
+
+        Ok(self.pending.front())
+    }
+
+    /// Waits until an event matching `predicate` arrives, or returns `None`
+    /// once `timeout` has passed. Messages read while waiting that don't
+    /// match are kept and returned in order by later calls to
+    /// `next_message`.
+    pub async fn wait_for_event<F>(
+        &mut self,
+        predicate: F,
+        timeout: Duration,
+    ) -> anyhow::Result<Option<event::Event>>
+    where
+        F: Fn(&event::Event) -> bool,
+    {
+        let position = self.pending.iter().position(|message| match message {
+            Message::Event(event) => predicate(event),
+            _ => false,
+        });
+
+        if let Some(Message::Event(event)) = position.and_then(|i| self.pending.remove(i)) {
+            return Ok(Some(event));
+        }
+
+        let deadline = Instant::now() + timeout;
 
-                let mut cursor = io::Cursor::new(&buf[1..]);
-                let _data = cursor.read_u8()?;
-                let seq = cursor.read_u16::<BigEndian>()?;
-                cursor.seek(io::SeekFrom::Start(0))?;
-                Message::Reply(seq)
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(None);
             }
 
-            _code => {
-                let mut cursor = io::Cursor::new(&buf);
-                let event = event::Event::read(&mut cursor)?;
-                Message::Event(event)
+            match self.read_message(Some(remaining)).await? {
+                Some(Message::Event(event)) if predicate(&event) => return Ok(Some(event)),
+                Some(message) => self.keep(message),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Takes the next message read from the connection, or returns `None`
+    /// if nothing arrived within `timeout`. A zero timeout doesn't wait at
+    /// all.
+    async fn read_message(&mut self, timeout: Option<Duration>) -> anyhow::Result<Option<Message>> {
+        let received = match timeout {
+            Some(timeout) if timeout.is_zero() => match self.messages.try_recv() {
+                Ok(message) => Some(message),
+                Err(mpsc::error::TryRecvError::Empty) => return Ok(None),
+                Err(mpsc::error::TryRecvError::Disconnected) => None,
+            },
+            Some(timeout) => match time::timeout(timeout, self.messages.recv()).await {
+                Ok(received) => received,
+                Err(_) => return Ok(None),
+            },
+            None => self.messages.recv().await,
+        };
+
+        let message = match received {
+            Some(message) => message?,
+            None => anyhow::bail!("connection closed"),
+        };
+
+        if let Message::Event(event) = &message {
+            self.backlog.events.fetch_sub(1, Ordering::AcqRel);
+            self.properties.handle(event);
+        }
+
+        // A PropertyNotify may have been among the events left out.
+        if self.backlog.dropped.swap(false, Ordering::AcqRel) {
+            self.properties.invalidate();
+        }
+
+        Ok(Some(message))
+    }
+
+    /// Drops the events matching `predicate` that have been read but not
+    /// yet handed out by `next_message`.
+    pub(crate) async fn discard_events<F>(&mut self, predicate: F) -> anyhow::Result<()>
+    where
+        F: Fn(&event::Event) -> bool,
+    {
+        while let Some(message) = self.read_message(Some(Duration::ZERO)).await? {
+            self.keep(message);
+        }
+
+        self.pending.retain(|message| match message {
+            Message::Event(event) => !predicate(event),
+            _ => true,
+        });
+
+        Ok(())
+    }
+
+    /// Keeps a message for `next_message`. Past `stream::CAPACITY` kept
+    /// messages, the oldest event is dropped; replies and errors are only
+    /// there because someone waits for them and are always kept.
+    fn keep(&mut self, message: Message) {
+        self.pending.push_back(message);
+
+        if self.pending.len() > stream::CAPACITY {
+            let oldest = self
+                .pending
+                .iter()
+                .position(|message| matches!(message, Message::Event(_)));
+
+            if let Some(i) = oldest {
+                self.pending.remove(i);
             }
+        }
+    }
+
+    /// Sends a request and, if it has one, waits for its reply. Errors for
+    /// requests without a reply are reported later by `next_message`.
+    pub async fn send<R: Request>(&mut self, request: &R) -> anyhow::Result<R::Reply> {
+        let cookie = self.send_request(request).await?;
+        self.wait_reply(cookie).await
+    }
+
+    /// Sends a request without waiting for its reply, so that several
+    /// requests can be in flight at once.
+    pub async fn send_request<R: Request>(
+        &mut self,
+        request: &R,
+    ) -> anyhow::Result<Cookie<R::Reply>> {
+        let sequence = self.write_request(request).await?;
+
+        Ok(Cookie {
+            sequence,
+            reply: PhantomData,
         })
     }
 
-    pub async fn query_tree(
+    /// Waits for the reply to a request sent with `send_request`. Replies
+    /// can be waited for in any order.
+    pub async fn wait_reply<T: reply::Reply>(&mut self, cookie: Cookie<T>) -> anyhow::Result<T> {
+        if !T::EXPECTED {
+            return T::read(&mut io::empty());
+        }
+
+        let data = self.read_reply(cookie.sequence).await?;
+        T::read(&mut io::Cursor::new(data))
+    }
+
+    /// Looks up an extension, once per connection. Returns None if the
+    /// server doesn't have it.
+    pub async fn query_extension(
+        &mut self,
+        name: &'static str,
+    ) -> anyhow::Result<Option<protocol::xproto::QueryExtensionReply>> {
+        if let Some(extension) = self.extensions.get(name) {
+            return Ok(extension.clone());
+        }
+
+        let request = protocol::xproto::QueryExtension {
+            name: name.as_bytes().to_vec(),
+        };
+        let reply = self.send(&request).await?;
+        let extension = reply.present.then_some(reply);
+
+        self.extensions.insert(name, extension.clone());
+        Ok(extension)
+    }
+
+    /// Sends a request of an extension and, if it has one, waits for its
+    /// reply. Fails if the server doesn't have the extension.
+    pub async fn send_extension<R: ExtensionRequest>(
+        &mut self,
+        request: &R,
+    ) -> anyhow::Result<R::Reply> {
+        let cookie = self.send_extension_request(request).await?;
+        self.wait_reply(cookie).await
+    }
+
+    /// Sends a request of an extension without waiting for its reply.
+    pub async fn send_extension_request<R: ExtensionRequest>(
+        &mut self,
+        request: &R,
+    ) -> anyhow::Result<Cookie<R::Reply>> {
+        let extension = match self.query_extension(R::EXTENSION).await? {
+            Some(extension) => extension,
+            None => anyhow::bail!("the server doesn't have the {} extension", R::EXTENSION),
+        };
+
+        self.check_length(request.length())?;
+        let sequence = self
+            .write_encoded(&request.encode(extension.major_opcode)?)
+            .await?;
+
+        Ok(Cookie {
+            sequence,
+            reply: PhantomData,
+        })
+    }
+
+    async fn write_request<R: Request>(&mut self, request: &R) -> anyhow::Result<u16> {
+        self.check_length(request.length())?;
+        self.write_encoded(&request.encode()?).await
+    }
+
+    fn check_length(&self, length: usize) -> anyhow::Result<()> {
+        let max = usize::from(self.setup.maximum_request_length);
+        anyhow::ensure!(
+            length <= max,
+            "request length {} exceeds maximum of {}",
+            length,
+            max
+        );
+
+        Ok(())
+    }
+
+    /// Writes an encoded request. Returns its sequence number.
+    async fn write_encoded(&mut self, buf: &[u8]) -> anyhow::Result<u16> {
+        time::timeout(self.timeout, self.conn.write_all(buf)).await??;
+
+        self.sequence = self.sequence.wrapping_add(1);
+        Ok(self.sequence)
+    }
+
+    /// Waits for the reply to the request with sequence number `seq`.
+    /// Everything else read in the meantime is kept for `next_message`.
+    async fn read_reply(&mut self, seq: u16) -> anyhow::Result<Vec<u8>> {
+        let position = self.pending.iter().position(|message| match message {
+            Message::Reply(s, _) | Message::Error(s, _) => *s == seq,
+            _ => false,
+        });
+
+        let mut message = position.and_then(|i| self.pending.remove(i));
+
+        loop {
+            match message {
+                Some(Message::Reply(s, data)) if s == seq => return Ok(data),
+                Some(Message::Error(s, error)) if s == seq => return Err(error.into()),
+                Some(other) => self.keep(other),
+                None => {}
+            }
+
+            message = self.read_message(None).await?;
+        }
+    }
+
+    /// Interns an atom, creating it if it doesn't exist yet. Atoms are
+    /// cached for the lifetime of the connection.
+    pub async fn intern_atom(&mut self, name: &str) -> anyhow::Result<proto::Atom> {
+        if let Some(atom) = self.atoms.atom(name) {
+            return Ok(atom);
+        }
+
+        match self.send(&request::InternAtom::new(name)).await?.atom {
+            proto::Atom(0) => anyhow::bail!("no atom for {}", name),
+            atom => {
+                self.atoms.insert(name, atom);
+                Ok(atom)
+            }
+        }
+    }
+
+    /// Looks up an atom without creating it.
+    pub async fn atom_if_exists(&mut self, name: &str) -> anyhow::Result<Option<proto::Atom>> {
+        if let Some(atom) = self.atoms.atom(name) {
+            return Ok(Some(atom));
+        }
+
+        let atom = self
+            .send(request::InternAtom::new(name).only_if_exists(true))
+            .await?
+            .atom;
+        let atom = Some(atom).filter(|atom| atom.0 != 0);
+
+        if let Some(atom) = atom {
+            self.atoms.insert(name, atom);
+        }
+
+        Ok(atom)
+    }
+
+    /// Interns several atoms in a single round trip.
+    pub async fn intern_atoms(&mut self, names: &[&str]) -> anyhow::Result<Vec<proto::Atom>> {
+        let mut cookies = Vec::new();
+        for name in names {
+            if self.atoms.atom(name).is_none() {
+                cookies.push((
+                    *name,
+                    self.send_request(&request::InternAtom::new(name)).await?,
+                ));
+            }
+        }
+
+        for (name, cookie) in cookies {
+            match self.wait_reply(cookie).await?.atom {
+                proto::Atom(0) => anyhow::bail!("no atom for {}", name),
+                atom => self.atoms.insert(name, atom),
+            }
+        }
+
+        names
+            .iter()
+            .map(|name| {
+                self.atoms
+                    .atom(name)
+                    .ok_or_else(|| anyhow::anyhow!("no atom for {}", name))
+            })
+            .collect()
+    }
+
+    pub async fn atom_name(&mut self, atom: proto::Atom) -> anyhow::Result<String> {
+        if let Some(name) = self.atoms.name(atom) {
+            return Ok(String::from(name));
+        }
+
+        let name = self.send(&request::GetAtomName::new(atom)).await?.name;
+        let name = String::from_utf8(name)?;
+        self.atoms.insert(&name, atom);
+
+        Ok(name)
+    }
+
+    /// Gets the whole value of a property, reading it in chunks if it's
+    /// large. Returns `None` if the property doesn't exist or doesn't have
+    /// the requested type.
+    pub async fn get_property(
+        &mut self,
+        window: proto::Window,
+        property: proto::Atom,
+        property_type: Option<proto::Atom>,
+    ) -> anyhow::Result<Option<reply::GetProperty>> {
+        // In units of four bytes.
+        const CHUNK: u32 = 16 * 1024;
+
+        let mut request = request::GetProperty::new(window, property);
+        request.property_type(property_type).range(0, CHUNK);
+
+        let mut reply = self.send(&request).await?;
+        if !reply.exists() || property_type.is_some_and(|t| reply.type_ != t) {
+            return Ok(None);
+        }
+
+        while reply.bytes_after > 0 {
+            let offset = u32::try_from(reply.value.len() / 4)?;
+            request.range(offset, CHUNK);
+
+            let chunk = self.send(&request).await?;
+            anyhow::ensure!(
+                chunk.type_ == reply.type_ && chunk.format == reply.format,
+                "property changed while reading it"
+            );
+
+            reply.value.extend(chunk.value);
+            reply.value_len += chunk.value_len;
+            reply.bytes_after = chunk.bytes_after;
+        }
+
+        Ok(Some(reply))
+    }
+
+    /// Gets a property through the property cache. The first call for a
+    /// window selects PropertyChange on it, which keeps the cached values up
+    /// to date.
+    pub async fn property(
+        &mut self,
+        window: proto::Window,
+        atom: proto::Atom,
+    ) -> anyhow::Result<Option<reply::GetProperty>> {
+        self.watch_properties(window).await?;
+
+        // Take in the messages read so far, so that a PropertyNotify a
+        // subscriber has already seen is reflected in the cache.
+        while let Some(message) = self.read_message(Some(Duration::ZERO)).await? {
+            self.keep(message);
+        }
+
+        if let Some(value) = self.properties.get(window, atom) {
+            return Ok(value.clone());
+        }
+
+        let value = self.get_property(window, atom, None).await?;
+        self.properties.insert(window, atom, value.clone());
+
+        Ok(value)
+    }
+
+    /// Subscribes to the PropertyNotify events for one property. The new
+    /// value can be read with `property`.
+    pub async fn property_changes(
+        &mut self,
+        window: proto::Window,
+        atom: proto::Atom,
+    ) -> anyhow::Result<stream::EventStream> {
+        self.watch_properties(window).await?;
+
+        Ok(self
+            .subscribe()
+            .window(window)
+            .filter(move |event| match event {
+                event::Event::PropertyNotify(notify) => notify.atom == atom,
+                _ => false,
+            }))
+    }
+
+    /// Drops the cached properties of a window. This happens by itself when
+    /// its DestroyNotify is read.
+    pub fn forget_properties(&mut self, window: proto::Window) {
+        self.properties.forget(window);
+    }
+
+    /// Adds PropertyChange to the events this client selects on a window,
+    /// keeping the ones already selected.
+    pub(crate) async fn watch_properties(&mut self, window: proto::Window) -> anyhow::Result<()> {
+        if self.properties.is_watched(window) {
+            return Ok(());
+        }
+
+        self.select_more_events(window, proto::EventMask::PROPERTY_CHANGE)
+            .await?;
+        self.properties.watch(window);
+
+        Ok(())
+    }
+
+    /// Adds `events` to the events this client selects on a window, keeping
+    /// the ones already selected. Returns the events selected before.
+    pub(crate) async fn select_more_events(
+        &mut self,
+        window: proto::Window,
+        events: proto::EventMask,
+    ) -> anyhow::Result<proto::EventMask> {
+        let mask = self.get_window_attributes(window).await?.your_event_mask;
+        let mask = proto::EventMask::from_bits_truncate(mask.0);
+        if !mask.contains(events) {
+            self.send(request::ChangeWindowAttributes::new(window).event_mask(mask | events))
+                .await?;
+        }
+
+        Ok(mask)
+    }
+
+    /// Puts back the events selected on a window before
+    /// `select_more_events`. PropertyChange stays selected on windows the
+    /// property cache has started watching since.
+    pub(crate) async fn restore_events(
         &mut self,
         window: proto::Window,
-    ) -> anyhow::Result<Vec<proto::Window>> {
-        request::QueryTree::new(window).write(self);
-        anyhow::bail!("nop");
+        mut mask: proto::EventMask,
+    ) -> anyhow::Result<()> {
+        if self.properties.is_watched(window) {
+            mask |= proto::EventMask::PROPERTY_CHANGE;
+        }
+
+        self.send(request::ChangeWindowAttributes::new(window).event_mask(mask))
+            .await
+    }
+
+    pub async fn delete_property(
+        &mut self,
+        window: proto::Window,
+        property: proto::Atom,
+    ) -> anyhow::Result<()> {
+        self.send(&request::DeleteProperty::new(window, property))
+            .await
+    }
+
+    pub async fn list_properties(
+        &mut self,
+        window: proto::Window,
+    ) -> anyhow::Result<Vec<proto::Atom>> {
+        Ok(self
+            .send(&request::ListProperties::new(window))
+            .await?
+            .atoms)
+    }
+
+    pub async fn rotate_properties(
+        &mut self,
+        window: proto::Window,
+        delta: i16,
+        properties: &[proto::Atom],
+    ) -> anyhow::Result<()> {
+        self.send(&request::RotateProperties::new(window, delta, properties))
+            .await
+    }
+
+    pub async fn destroy_window(&mut self, window: proto::Window) -> anyhow::Result<()> {
+        self.send(&request::DestroyWindow::new(window)).await
+    }
+
+    pub async fn destroy_subwindows(&mut self, window: proto::Window) -> anyhow::Result<()> {
+        self.send(&request::DestroySubwindows::new(window)).await
+    }
+
+    pub async fn change_save_set(
+        &mut self,
+        mode: proto::SaveSetMode,
+        window: proto::Window,
+    ) -> anyhow::Result<()> {
+        self.send(&request::ChangeSaveSet::new(mode, window)).await
+    }
+
+    pub async fn reparent_window(
+        &mut self,
+        window: proto::Window,
+        parent: proto::Window,
+        x: i16,
+        y: i16,
+    ) -> anyhow::Result<()> {
+        self.send(&request::ReparentWindow::new(window, parent, x, y))
+            .await
+    }
+
+    pub async fn map_window(&mut self, window: proto::Window) -> anyhow::Result<()> {
+        self.send(&request::MapWindow::new(window)).await
+    }
+
+    pub async fn map_subwindows(&mut self, window: proto::Window) -> anyhow::Result<()> {
+        self.send(&request::MapSubwindows::new(window)).await
+    }
+
+    pub async fn unmap_window(&mut self, window: proto::Window) -> anyhow::Result<()> {
+        self.send(&request::UnmapWindow::new(window)).await
+    }
+
+    pub async fn unmap_subwindows(&mut self, window: proto::Window) -> anyhow::Result<()> {
+        self.send(&request::UnmapSubwindows::new(window)).await
+    }
+
+    pub async fn circulate_window(
+        &mut self,
+        direction: proto::CirculateDirection,
+        window: proto::Window,
+    ) -> anyhow::Result<()> {
+        self.send(&request::CirculateWindow::new(direction, window))
+            .await
+    }
+
+    pub async fn get_window_attributes(
+        &mut self,
+        window: proto::Window,
+    ) -> anyhow::Result<reply::GetWindowAttributes> {
+        self.send(&request::GetWindowAttributes::new(window)).await
+    }
+
+    pub async fn query_tree(&mut self, window: proto::Window) -> anyhow::Result<reply::QueryTree> {
+        self.send(&request::QueryTree::new(window)).await
+    }
+
+    pub async fn get_geometry(
+        &mut self,
+        drawable: impl Into<proto::Drawable>,
+    ) -> anyhow::Result<reply::GetGeometry> {
+        self.send(&request::GetGeometry::new(drawable.into())).await
+    }
+
+    /// Converts coordinates relative to `src_window` into coordinates
+    /// relative to `dst_window`.
+    pub async fn translate_coordinates(
+        &mut self,
+        src_window: proto::Window,
+        dst_window: proto::Window,
+        src_x: i16,
+        src_y: i16,
+    ) -> anyhow::Result<reply::TranslateCoordinates> {
+        self.send(&request::TranslateCoordinates::new(
+            src_window, dst_window, src_x, src_y,
+        ))
+        .await
+    }
+}
+
+/// Reads messages from the connection until it fails or the display is
+/// dropped. Events are published to subscribers once they have been queued
+/// for the display, so that the display has them by the time a subscriber
+/// looks.
+async fn read_messages(
+    mut conn: OwnedReadHalf,
+    timeout: Duration,
+    messages: mpsc::UnboundedSender<anyhow::Result<Message>>,
+    backlog: Arc<Backlog>,
+    events: broadcast::Sender<event::Event>,
+) {
+    loop {
+        let message = read_message(&mut conn, timeout).await;
+
+        let event = match &message {
+            Ok(Message::Event(event)) => Some(event.clone()),
+            _ => None,
+        };
+        let failed = message.is_err();
+
+        let queue = match event {
+            Some(_) if backlog.events.load(Ordering::Acquire) >= stream::CAPACITY => {
+                backlog.dropped.store(true, Ordering::Release);
+                false
+            }
+            Some(_) => {
+                backlog.events.fetch_add(1, Ordering::AcqRel);
+                true
+            }
+            None => true,
+        };
+
+        if queue && (messages.send(message).is_err() || failed) {
+            return;
+        }
+
+        if let Some(event) = event {
+            // No subscribers is not an error.
+            let _ = events.send(event);
+        }
+    }
+}
+
+/// Reads one message. The first byte is waited for as long as it takes;
+/// the rest is already on its way and has to arrive within `timeout`.
+async fn read_message(conn: &mut OwnedReadHalf, timeout: Duration) -> anyhow::Result<Message> {
+    let mut buf: [u8; 32] = [0; 32];
+
+    if conn.read(&mut buf[..1]).await? == 0 {
+        anyhow::bail!("connection closed");
+    }
+
+    time::timeout(timeout, conn.read_exact(&mut buf[1..])).await??;
+
+    let seq = u16::from_be_bytes([buf[2], buf[3]]);
+
+    Ok(match buf[0] {
+        proto::RESPONSE_ERROR => {
+            let mut cursor = io::Cursor::new(&buf[1..]);
+            let error = error::Error::read(&mut cursor)?;
+            Message::Error(seq, error)
+        }
+
+        proto::RESPONSE_REPLY => {
+            let length = u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]);
+
+            let mut data = buf[1..].to_vec();
+            data.resize(31 + usize::try_from(length)? * 4, 0);
+            time::timeout(timeout, conn.read_exact(&mut data[31..])).await??;
+
+            Message::Reply(seq, data)
+        }
+
+        proto::GENERIC_EVENT => {
+            let length = u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]);
+
+            let mut data = buf.to_vec();
+            data.resize(32 + usize::try_from(length)? * 4, 0);
+            time::timeout(timeout, conn.read_exact(&mut data[32..])).await??;
+
+            let mut cursor = io::Cursor::new(&data);
+            Message::Event(event::Event::read(&mut cursor)?)
+        }
+
+        _code => {
+            let mut cursor = io::Cursor::new(&buf);
+            Message::Event(event::Event::read(&mut cursor)?)
+        }
+    })
+}
+
+/// Maps a display name like `:0` or `:1.0` to the local socket of the
+/// server. Anything else is taken to be a socket path.
+fn socket_path(name: &str) -> String {
+    match name.strip_prefix(':') {
+        Some(display) => {
+            let number = display.split('.').next().unwrap_or(display);
+            format!("/tmp/.X11-unix/X{}", number)
+        }
+        None => String::from(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake;
+
+    #[tokio::test]
+    async fn events_are_published_without_reading_the_display() -> anyhow::Result<()> {
+        let (server, client) = UnixStream::pair()?;
+        let (reader, _writer) = client.into_split();
+        let (_, mut server) = server.into_split();
+
+        let (events, mut subscriber) = broadcast::channel(stream::CAPACITY);
+        let (sender, mut messages) = mpsc::unbounded_channel();
+        let reader = tokio::spawn(read_messages(
+            reader,
+            Duration::from_secs(5),
+            sender,
+            Arc::default(),
+            events,
+        ));
+
+        let message = event::ClientMessage::new(proto::Window(7), proto::Atom(1), [1, 2, 3, 4, 5]);
+        server.write_all(&message.encode()).await?;
+
+        match time::timeout(Duration::from_secs(5), subscriber.recv()).await?? {
+            event::Event::ClientMessage(message) => assert_eq!(message.window, proto::Window(7)),
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        // The display still gets every message, in order.
+        assert!(matches!(
+            messages.try_recv(),
+            Ok(Ok(Message::Event(event::Event::ClientMessage(_))))
+        ));
+
+        drop(server);
+        reader.await?;
+        assert!(matches!(messages.recv().await, Some(Err(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn events_past_the_backlog_only_reach_subscribers() -> anyhow::Result<()> {
+        let (server, client) = UnixStream::pair()?;
+        let (reader, _writer) = client.into_split();
+        let (_, mut server) = server.into_split();
+
+        let (events, mut subscriber) = broadcast::channel(2 * stream::CAPACITY);
+        let (sender, mut messages) = mpsc::unbounded_channel();
+        let backlog = Arc::new(Backlog::default());
+        let reader = tokio::spawn(read_messages(
+            reader,
+            Duration::from_secs(5),
+            sender,
+            backlog.clone(),
+            events,
+        ));
+
+        let message = event::ClientMessage::new(proto::Window(7), proto::Atom(1), [1, 2, 3, 4, 5]);
+        for _ in 0..stream::CAPACITY + 10 {
+            server.write_all(&message.encode()).await?;
+        }
+
+        // A reply after the dropped events is still queued.
+        let mut reply = vec![proto::RESPONSE_REPLY, 0, 0, 1];
+        reply.resize(32, 0);
+        server.write_all(&reply).await?;
+        drop(server);
+        reader.await?;
+
+        for _ in 0..stream::CAPACITY + 10 {
+            subscriber.try_recv()?;
+        }
+
+        for _ in 0..stream::CAPACITY {
+            assert!(matches!(messages.try_recv(), Ok(Ok(Message::Event(_)))));
+        }
+        assert!(matches!(messages.try_recv(), Ok(Ok(Message::Reply(1, _)))));
+        assert!(backlog.dropped.load(Ordering::Acquire));
+
+        Ok(())
+    }
+
+    fn client_message(window: u32) -> [u8; 32] {
+        event::ClientMessage::new(proto::Window(window), proto::Atom(1), [0; 5]).encode()
+    }
+
+    #[tokio::test]
+    async fn skipped_messages_come_back_in_order() -> anyhow::Result<()> {
+        let (mut display, mut server) = fake::connect()?;
+
+        let first = display
+            .send_request(&request::GetGeometry::new(proto::Drawable(1)))
+            .await?;
+        let second = display
+            .send_request(&request::GetGeometry::new(proto::Drawable(2)))
+            .await?;
+        display.map_window(proto::Window(3)).await?;
+
+        for _ in 0..3 {
+            server.request().await?;
+        }
+        server.send(&client_message(7)).await?;
+        server.reply_to(2, 24, &fake::geometry(200)).await?;
+        server.error(3, 3).await?;
+        server.send(&client_message(8)).await?;
+        server.reply_to(1, 24, &fake::geometry(100)).await?;
+
+        // Waiting for the first reply reads past everything else.
+        assert_eq!(display.wait_reply(first).await?.width, 100);
+        assert_eq!(display.wait_reply(second).await?.width, 200);
+
+        let mut windows = Vec::new();
+        for _ in 0..3 {
+            match display.next_message().await? {
+                Message::Event(event::Event::ClientMessage(message)) => {
+                    windows.push(message.window.0)
+                }
+                Message::Error(3, error::Error::Window(3)) => windows.push(0),
+                message => panic!("unexpected message {:?}", message),
+            }
+        }
+        assert_eq!(windows, [7, 0, 8]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn waiting_for_an_event_gives_up_at_the_deadline() -> anyhow::Result<()> {
+        let (mut display, mut server) = fake::connect()?;
+        server.send(&client_message(7)).await?;
+
+        let event = display
+            .wait_for_event(
+                |event| matches!(event, event::Event::MapNotify(_)),
+                Duration::from_millis(50),
+            )
+            .await?;
+        assert!(event.is_none());
+
+        // What was read while waiting is still there.
+        assert!(matches!(
+            display.next_message().await?,
+            Message::Event(event::Event::ClientMessage(_))
+        ));
+
+        Ok(())
+    }
+
+    /// Has the display read everything the server has sent so far.
+    async fn sync(display: &mut Display, server: &mut fake::Server) -> anyhow::Result<()> {
+        tokio::try_join!(display.get_geometry(proto::Drawable(1)), async {
+            server.expect(request::GetGeometry::OPCODE).await?;
+            server.reply(24, &fake::geometry(1)).await
+        })?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn motion_is_compressed_per_window() -> anyhow::Result<()> {
+        let (mut display, mut server) = fake::connect()?;
+        display.set_compression(Compression {
+            motion: true,
+            ..Compression::default()
+        });
+
+        let (first, second) = (proto::Window(7), proto::Window(8));
+        for (window, x) in [(first, 1), (first, 2), (second, 5), (first, 3)] {
+            server.send(&fake::motion_notify(window, x, x)).await?;
+        }
+        sync(&mut display, &mut server).await?;
+
+        let mut motion = Vec::new();
+        for _ in 0..3 {
+            match display.next_message().await? {
+                Message::Event(event::Event::MotionNotify(event)) => {
+                    motion.push((event.event, event.event_x))
+                }
+                message => panic!("unexpected message {:?}", message),
+            }
+        }
+        assert_eq!(motion, [(first, 2), (second, 5), (first, 3)]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn configure_is_compressed_per_window() -> anyhow::Result<()> {
+        let (mut display, mut server) = fake::connect()?;
+        display.set_compression(Compression {
+            configure: true,
+            ..Compression::default()
+        });
+
+        let (first, second) = (proto::Window(7), proto::Window(8));
+        for (window, width) in [(first, 10), (first, 20), (first, 30), (second, 40)] {
+            server
+                .send(&fake::configure_notify(window, width, 1))
+                .await?;
+        }
+        sync(&mut display, &mut server).await?;
+
+        let mut configure = Vec::new();
+        for _ in 0..2 {
+            match display.next_message().await? {
+                Message::Event(event::Event::ConfigureNotify(event)) => {
+                    configure.push((event.window, event.width))
+                }
+                message => panic!("unexpected message {:?}", message),
+            }
+        }
+        assert_eq!(configure, [(first, 30), (second, 40)]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn expose_is_merged_once_the_burst_ends() -> anyhow::Result<()> {
+        let (mut display, mut server) = fake::connect()?;
+        display.set_compression(Compression {
+            expose: true,
+            ..Compression::default()
+        });
+
+        let window = proto::Window(7);
+        let rectangle = |x, y, width, height| proto::Rectangle {
+            x,
+            y,
+            width,
+            height,
+        };
+
+        server
+            .send(&fake::expose(window, rectangle(0, 0, 100, 100), 2))
+            .await?;
+        server
+            .send(&fake::motion_notify(proto::Window(8), 1, 1))
+            .await?;
+        server
+            .send(&fake::expose(window, rectangle(100, 0, 50, 50), 1))
+            .await?;
+
+        // The region is only delivered once the last Expose arrives.
+        let (message, ()) = tokio::try_join!(display.next_message(), async {
+            time::sleep(Duration::from_millis(20)).await;
+            server
+                .send(&fake::expose(window, rectangle(10, 10, 20, 20), 0))
+                .await?;
+            server.send(&client_message(9)).await
+        })?;
+
+        match message {
+            Message::Event(event::Event::ExposeRegion(region)) => {
+                assert_eq!(region.window, window);
+                assert_eq!(
+                    region.rectangles,
+                    [rectangle(0, 0, 100, 100), rectangle(100, 0, 50, 50)]
+                );
+            }
+            message => panic!("unexpected message {:?}", message),
+        }
+
+        // What came in between follows, in order.
+        assert!(matches!(
+            display.next_message().await?,
+            Message::Event(event::Event::MotionNotify(_))
+        ));
+        assert!(matches!(
+            display.next_message().await?,
+            Message::Event(event::Event::ClientMessage(_))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn large_properties_are_read_in_chunks() -> anyhow::Result<()> {
+        const CHUNK: usize = 64 * 1024;
+
+        let (mut display, mut server) = fake::connect()?;
+        let (window, property) = (proto::Window(7), proto::Atom(100));
+        let property_type = atom::Atom::String.into();
+
+        // The last chunk isn't a multiple of four bytes.
+        let value = (0..2 * CHUNK + 6).map(|i| i as u8).collect::<Vec<_>>();
+
+        let (reply, offsets) = tokio::try_join!(
+            display.get_property(window, property, Some(property_type)),
+            async {
+                let mut offsets = Vec::new();
+                for chunk in value.chunks(CHUNK) {
+                    let request = server.expect(request::GetProperty::OPCODE).await?;
+                    offsets.push((request.u32(16), request.u32(20)));
+
+                    let offset = usize::try_from(request.u32(16))? * 4;
+                    let after = u32::try_from(value.len() - offset - chunk.len())?;
+                    server
+                        .reply_property(8, property_type, after, chunk)
+                        .await?;
+                }
+                anyhow::Ok(offsets)
+            }
+        )?;
+
+        assert_eq!(offsets, [(0, 16384), (16384, 16384), (32768, 16384)]);
+
+        let reply = reply.expect("the property exists");
+        assert_eq!(reply.format, 8);
+        assert_eq!(reply.value_len as usize, value.len());
+        assert_eq!(reply.bytes_after, 0);
+        assert!(reply.value == value);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_message_cut_short_times_out() -> anyhow::Result<()> {
+        let (server, client) = UnixStream::pair()?;
+        let (mut reader, _writer) = client.into_split();
+        let (_keep, mut server) = server.into_split();
+
+        server.write_all(&[proto::RESPONSE_REPLY, 0, 0, 1]).await?;
+
+        let result = read_message(&mut reader, Duration::from_millis(10)).await;
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn generic_events_are_read_whole() -> anyhow::Result<()> {
+        let (server, client) = UnixStream::pair()?;
+        let (mut reader, _writer) = client.into_split();
+        let (_keep, mut server) = server.into_split();
+
+        // Two more words after the first 32 bytes.
+        let mut generic = vec![proto::GENERIC_EVENT, 131, 0, 1, 0, 0, 0, 2, 0, 7];
+        generic.resize(40, 0xab);
+        server.write_all(&generic).await?;
+
+        let message = event::ClientMessage::new(proto::Window(7), proto::Atom(1), [1, 2, 3, 4, 5]);
+        server.write_all(&message.encode()).await?;
+
+        match read_message(&mut reader, Duration::from_secs(5)).await? {
+            Message::Event(event::Event::Generic(event)) => {
+                assert_eq!(event.extension, 131);
+                assert_eq!(event.evtype, 7);
+                assert_eq!(event.data, generic);
+            }
+            message => panic!("unexpected message {:?}", message),
+        }
+
+        // The stream is still in sync.
+        assert!(matches!(
+            read_message(&mut reader, Duration::from_secs(5)).await?,
+            Message::Event(event::Event::ClientMessage(_))
+        ));
+
+        Ok(())
     }
 }
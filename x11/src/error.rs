@@ -1,31 +1,56 @@
 use std::io::Read;
 
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum Error {
+    #[error("bad request")]
     Request,
+    #[error("bad value {0}")]
     Value(u32),
+    #[error("bad window {0:#x}")]
     Window(u32),
+    #[error("bad pixmap {0:#x}")]
     Pixmap(u32),
+    #[error("bad atom {0}")]
     Atom(u32),
+    #[error("bad cursor {0:#x}")]
     Cursor(u32),
+    #[error("bad font {0:#x}")]
     Font(u32),
+    #[error("bad match")]
     Match,
+    #[error("bad drawable {0:#x}")]
     Drawable(u32),
+    #[error("bad access")]
     Access,
+    #[error("bad alloc")]
     Alloc,
+    #[error("bad colormap {0:#x}")]
     Colormap(u32),
+    #[error("bad graphics context {0:#x}")]
     GContext(u32),
+    #[error("bad resource id choice {0:#x}")]
     IDChoice(u32),
+    #[error("bad name")]
     Name,
+    #[error("bad length")]
     Length,
+    #[error("bad implementation")]
     Implementation,
-    Unknown(u8),
+    /// An error this module doesn't decode, such as one of an extension:
+    /// its code and all 32 bytes, for the generated `protocol` errors.
+    #[error("unknown error {0}")]
+    Unknown(u8, [u8; 32]),
 }
 
 impl Error {
+    /// Reads an error, starting after the leading zero byte.
     pub fn read<T: Read>(r: &mut T) -> anyhow::Result<Self> {
         use byteorder::{BigEndian, ReadBytesExt};
 
+        let mut error = [0; 32];
+        r.read_exact(&mut error[1..])?;
+        let r = &mut std::io::Cursor::new(&error[1..]);
+
         let code = r.read_u8()?;
         let _sequence_number = r.read_u16::<BigEndian>()?;
 
@@ -55,7 +80,7 @@ impl Error {
             16 => Ok(Error::Length),
             17 => Ok(Error::Implementation),
 
-            code => Ok(Error::Unknown(code)),
+            code => Ok(Error::Unknown(code, error)),
         }
     }
 }
@@ -1,7 +1,53 @@
+use crate::proto;
 use std::io;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Event {
+    KeyPress(Input),
+    KeyRelease(Input),
+    ButtonPress(Input),
+    ButtonRelease(Input),
+    MotionNotify(Input),
+    EnterNotify(Crossing),
+    LeaveNotify(Crossing),
+    FocusIn(Focus),
+    FocusOut(Focus),
+    KeymapNotify(KeymapNotify),
+    Expose(Expose),
+    GraphicsExposure(GraphicsExposure),
+    NoExposure(NoExposure),
+    VisibilityNotify(VisibilityNotify),
+    CreateNotify(CreateNotify),
+    DestroyNotify(DestroyNotify),
+    UnmapNotify(UnmapNotify),
+    MapNotify(MapNotify),
+    MapRequest(MapRequest),
+    ReparentNotify(ReparentNotify),
+    ConfigureNotify(ConfigureNotify),
+    ConfigureRequest(ConfigureRequest),
+    GravityNotify(GravityNotify),
+    ResizeRequest(ResizeRequest),
+    CirculateNotify(Circulate),
+    CirculateRequest(Circulate),
+    PropertyNotify(PropertyNotify),
+    SelectionClear(SelectionClear),
+    SelectionRequest(SelectionRequest),
+    SelectionNotify(SelectionNotify),
+    ColormapNotify(ColormapNotify),
+    ClientMessage(ClientMessage),
+    MappingNotify(MappingNotify),
+    /// A burst of Expose events merged by `display::Compression`.
+    ExposeRegion(ExposeRegion),
+    /// An event of an extension, such as XInput2, that can be longer than
+    /// 32 bytes.
+    Generic(Generic),
+    /// An event this module doesn't decode, such as one of an extension:
+    /// its code and all 32 bytes, for the generated `protocol` events.
+    Unknown(u8, [u8; 32]),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
     KeyPress,
     KeyRelease,
     ButtonPress,
@@ -35,6 +81,7 @@ pub enum Event {
     ColormapNotify,
     ClientMessage,
     MappingNotify,
+    Generic,
     Unknown(u8),
 }
 
@@ -42,42 +89,1102 @@ impl Event {
     pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
         use byteorder::ReadBytesExt;
 
-        Ok(match r.read_u8()? {
-            2 => Event::KeyPress,
-            3 => Event::KeyRelease,
-            4 => Event::ButtonPress,
-            5 => Event::ButtonRelease,
-            6 => Event::MotionNotify,
-            7 => Event::EnterNotify,
-            8 => Event::LeaveNotify,
-            9 => Event::FocusIn,
-            10 => Event::FocusOut,
-            11 => Event::KeymapNotify,
-            12 => Event::Expose,
-            13 => Event::GraphicsExposure,
-            14 => Event::NoExposure,
-            15 => Event::VisibilityNotify,
-            16 => Event::CreateNotify,
-            17 => Event::DestroyNotify,
-            18 => Event::UnmapNotify,
-            19 => Event::MapNotify,
-            20 => Event::MapRequest,
-            21 => Event::ReparentNotify,
-            22 => Event::ConfigureNotify,
-            23 => Event::ConfigureRequest,
-            24 => Event::GravityNotify,
-            25 => Event::ResizeRequest,
-            26 => Event::CirculateNotify,
-            27 => Event::CirculateRequest,
-            28 => Event::PropertyNotify,
-            29 => Event::SelectionClear,
-            30 => Event::SelectionRequest,
-            31 => Event::SelectionNotify,
-            32 => Event::ColormapNotify,
-            33 => Event::ClientMessage,
-            34 => Event::MappingNotify,
-
-            code => Event::Unknown(code),
+        // The most significant bit is set for events sent with SendEvent.
+        let first = r.read_u8()?;
+        Ok(match first & 0x7f {
+            2 => Event::KeyPress(Input::read(r)?),
+            3 => Event::KeyRelease(Input::read(r)?),
+            4 => Event::ButtonPress(Input::read(r)?),
+            5 => Event::ButtonRelease(Input::read(r)?),
+            6 => Event::MotionNotify(Input::read(r)?),
+            7 => Event::EnterNotify(Crossing::read(r)?),
+            8 => Event::LeaveNotify(Crossing::read(r)?),
+            9 => Event::FocusIn(Focus::read(r)?),
+            10 => Event::FocusOut(Focus::read(r)?),
+            11 => Event::KeymapNotify(KeymapNotify::read(r)?),
+            12 => Event::Expose(Expose::read(r)?),
+            13 => Event::GraphicsExposure(GraphicsExposure::read(r)?),
+            14 => Event::NoExposure(NoExposure::read(r)?),
+            15 => Event::VisibilityNotify(VisibilityNotify::read(r)?),
+            16 => Event::CreateNotify(CreateNotify::read(r)?),
+            17 => Event::DestroyNotify(DestroyNotify::read(r)?),
+            18 => Event::UnmapNotify(UnmapNotify::read(r)?),
+            19 => Event::MapNotify(MapNotify::read(r)?),
+            20 => Event::MapRequest(MapRequest::read(r)?),
+            21 => Event::ReparentNotify(ReparentNotify::read(r)?),
+            22 => Event::ConfigureNotify(ConfigureNotify::read(r)?),
+            23 => Event::ConfigureRequest(ConfigureRequest::read(r)?),
+            24 => Event::GravityNotify(GravityNotify::read(r)?),
+            25 => Event::ResizeRequest(ResizeRequest::read(r)?),
+            26 => Event::CirculateNotify(Circulate::read(r)?),
+            27 => Event::CirculateRequest(Circulate::read(r)?),
+            28 => Event::PropertyNotify(PropertyNotify::read(r)?),
+            29 => Event::SelectionClear(SelectionClear::read(r)?),
+            30 => Event::SelectionRequest(SelectionRequest::read(r)?),
+            31 => Event::SelectionNotify(SelectionNotify::read(r)?),
+            32 => Event::ColormapNotify(ColormapNotify::read(r)?),
+            33 => Event::ClientMessage(ClientMessage::read(r)?),
+            34 => Event::MappingNotify(MappingNotify::read(r)?),
+            proto::GENERIC_EVENT => Event::Generic(Generic::read(r)?),
+
+            code => {
+                let mut event = [0; 32];
+                event[0] = first;
+                r.read_exact(&mut event[1..])?;
+                Event::Unknown(code, event)
+            }
+        })
+    }
+
+    pub fn kind(&self) -> Kind {
+        match self {
+            Event::KeyPress(_) => Kind::KeyPress,
+            Event::KeyRelease(_) => Kind::KeyRelease,
+            Event::ButtonPress(_) => Kind::ButtonPress,
+            Event::ButtonRelease(_) => Kind::ButtonRelease,
+            Event::MotionNotify(_) => Kind::MotionNotify,
+            Event::EnterNotify(_) => Kind::EnterNotify,
+            Event::LeaveNotify(_) => Kind::LeaveNotify,
+            Event::FocusIn(_) => Kind::FocusIn,
+            Event::FocusOut(_) => Kind::FocusOut,
+            Event::KeymapNotify(_) => Kind::KeymapNotify,
+            Event::Expose(_) | Event::ExposeRegion(_) => Kind::Expose,
+            Event::GraphicsExposure(_) => Kind::GraphicsExposure,
+            Event::NoExposure(_) => Kind::NoExposure,
+            Event::VisibilityNotify(_) => Kind::VisibilityNotify,
+            Event::CreateNotify(_) => Kind::CreateNotify,
+            Event::DestroyNotify(_) => Kind::DestroyNotify,
+            Event::UnmapNotify(_) => Kind::UnmapNotify,
+            Event::MapNotify(_) => Kind::MapNotify,
+            Event::MapRequest(_) => Kind::MapRequest,
+            Event::ReparentNotify(_) => Kind::ReparentNotify,
+            Event::ConfigureNotify(_) => Kind::ConfigureNotify,
+            Event::ConfigureRequest(_) => Kind::ConfigureRequest,
+            Event::GravityNotify(_) => Kind::GravityNotify,
+            Event::ResizeRequest(_) => Kind::ResizeRequest,
+            Event::CirculateNotify(_) => Kind::CirculateNotify,
+            Event::CirculateRequest(_) => Kind::CirculateRequest,
+            Event::PropertyNotify(_) => Kind::PropertyNotify,
+            Event::SelectionClear(_) => Kind::SelectionClear,
+            Event::SelectionRequest(_) => Kind::SelectionRequest,
+            Event::SelectionNotify(_) => Kind::SelectionNotify,
+            Event::ColormapNotify(_) => Kind::ColormapNotify,
+            Event::ClientMessage(_) => Kind::ClientMessage,
+            Event::MappingNotify(_) => Kind::MappingNotify,
+            Event::Generic(_) => Kind::Generic,
+            Event::Unknown(code, _) => Kind::Unknown(*code),
+        }
+    }
+
+    /// The window an event is about. For structure events selected on a
+    /// parent this is the child window, not the window the event was
+    /// reported on.
+    pub fn window(&self) -> Option<proto::Window> {
+        match self {
+            Event::KeyPress(e)
+            | Event::KeyRelease(e)
+            | Event::ButtonPress(e)
+            | Event::ButtonRelease(e)
+            | Event::MotionNotify(e) => Some(e.event),
+            Event::EnterNotify(e) | Event::LeaveNotify(e) => Some(e.event),
+            Event::FocusIn(e) | Event::FocusOut(e) => Some(e.event),
+            Event::KeymapNotify(_) => None,
+            Event::Expose(e) => Some(e.window),
+            Event::ExposeRegion(e) => Some(e.window),
+            // These are about a drawable, which needn't be a window.
+            Event::GraphicsExposure(_) | Event::NoExposure(_) => None,
+            Event::VisibilityNotify(e) => Some(e.window),
+            Event::CreateNotify(e) => Some(e.window),
+            Event::DestroyNotify(e) => Some(e.window),
+            Event::UnmapNotify(e) => Some(e.window),
+            Event::MapNotify(e) => Some(e.window),
+            Event::MapRequest(e) => Some(e.window),
+            Event::ReparentNotify(e) => Some(e.window),
+            Event::ConfigureNotify(e) => Some(e.window),
+            Event::ConfigureRequest(e) => Some(e.window),
+            Event::GravityNotify(e) => Some(e.window),
+            Event::ResizeRequest(e) => Some(e.window),
+            Event::CirculateNotify(e) | Event::CirculateRequest(e) => Some(e.window),
+            Event::PropertyNotify(e) => Some(e.window),
+            Event::SelectionClear(e) => Some(e.owner),
+            Event::SelectionRequest(e) => Some(e.owner),
+            Event::SelectionNotify(e) => Some(e.requestor),
+            Event::ColormapNotify(e) => Some(e.window),
+            Event::ClientMessage(e) => Some(e.window),
+            Event::MappingNotify(_) => None,
+            Event::Generic(_) => None,
+            Event::Unknown(_, _) => None,
+        }
+    }
+
+    /// The window an event was reported on. For structure events selected
+    /// on a parent with SubstructureNotify or SubstructureRedirect this is
+    /// the parent; otherwise it's the same as `window`.
+    pub fn event_window(&self) -> Option<proto::Window> {
+        match self {
+            Event::CreateNotify(e) => Some(e.parent),
+            Event::DestroyNotify(e) => Some(e.event),
+            Event::UnmapNotify(e) => Some(e.event),
+            Event::MapNotify(e) => Some(e.event),
+            Event::MapRequest(e) => Some(e.parent),
+            Event::ReparentNotify(e) => Some(e.event),
+            Event::ConfigureNotify(e) => Some(e.event),
+            Event::ConfigureRequest(e) => Some(e.parent),
+            Event::GravityNotify(e) => Some(e.event),
+            Event::CirculateNotify(e) | Event::CirculateRequest(e) => Some(e.event),
+            event => event.window(),
+        }
+    }
+}
+
+/// KeyPress, KeyRelease, ButtonPress, ButtonRelease and MotionNotify.
+#[derive(Debug, Clone)]
+pub struct Input {
+    pub detail: u8,
+    pub time: proto::Timestamp,
+    pub root: proto::Window,
+    pub event: proto::Window,
+    pub child: Option<proto::Window>,
+    pub root_x: i16,
+    pub root_y: i16,
+    pub event_x: i16,
+    pub event_y: i16,
+    pub state: proto::KeyButMask,
+    pub same_screen: bool,
+}
+
+impl Input {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        let detail = r.read_u8()?;
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let time = proto::Timestamp(r.read_u32::<BigEndian>()?);
+        let root = proto::Window(r.read_u32::<BigEndian>()?);
+        let event = proto::Window(r.read_u32::<BigEndian>()?);
+        let child = none_if_zero(r.read_u32::<BigEndian>()?);
+        let root_x = r.read_i16::<BigEndian>()?;
+        let root_y = r.read_i16::<BigEndian>()?;
+        let event_x = r.read_i16::<BigEndian>()?;
+        let event_y = r.read_i16::<BigEndian>()?;
+        let state = proto::KeyButMask::from_bits_truncate(r.read_u16::<BigEndian>()?);
+        let same_screen = r.read_u8()? != 0;
+
+        Ok(Input {
+            detail,
+            time,
+            root,
+            event,
+            child,
+            root_x,
+            root_y,
+            event_x,
+            event_y,
+            state,
+            same_screen,
+        })
+    }
+}
+
+/// EnterNotify and LeaveNotify.
+#[derive(Debug, Clone)]
+pub struct Crossing {
+    pub detail: u8,
+    pub time: proto::Timestamp,
+    pub root: proto::Window,
+    pub event: proto::Window,
+    pub child: Option<proto::Window>,
+    pub root_x: i16,
+    pub root_y: i16,
+    pub event_x: i16,
+    pub event_y: i16,
+    pub state: proto::KeyButMask,
+    pub mode: u8,
+    pub focus: bool,
+    pub same_screen: bool,
+}
+
+impl Crossing {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        let detail = r.read_u8()?;
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let time = proto::Timestamp(r.read_u32::<BigEndian>()?);
+        let root = proto::Window(r.read_u32::<BigEndian>()?);
+        let event = proto::Window(r.read_u32::<BigEndian>()?);
+        let child = none_if_zero(r.read_u32::<BigEndian>()?);
+        let root_x = r.read_i16::<BigEndian>()?;
+        let root_y = r.read_i16::<BigEndian>()?;
+        let event_x = r.read_i16::<BigEndian>()?;
+        let event_y = r.read_i16::<BigEndian>()?;
+        let state = proto::KeyButMask::from_bits_truncate(r.read_u16::<BigEndian>()?);
+        let mode = r.read_u8()?;
+        let flags = r.read_u8()?; // same-screen, focus
+
+        Ok(Crossing {
+            detail,
+            time,
+            root,
+            event,
+            child,
+            root_x,
+            root_y,
+            event_x,
+            event_y,
+            state,
+            mode,
+            focus: flags & 0x01 != 0,
+            same_screen: flags & 0x02 != 0,
+        })
+    }
+}
+
+/// FocusIn and FocusOut.
+#[derive(Debug, Clone)]
+pub struct Focus {
+    pub detail: u8,
+    pub event: proto::Window,
+    pub mode: u8,
+}
+
+impl Focus {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        let detail = r.read_u8()?;
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let event = proto::Window(r.read_u32::<BigEndian>()?);
+        let mode = r.read_u8()?;
+
+        Ok(Focus {
+            detail,
+            event,
+            mode,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KeymapNotify {
+    pub keys: [u8; 31],
+}
+
+impl KeymapNotify {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        let mut keys = [0; 31];
+        r.read_exact(&mut keys)?;
+
+        Ok(KeymapNotify { keys })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Expose {
+    pub window: proto::Window,
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+    pub count: u16,
+}
+
+impl Expose {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        r.read_u8()?; // unused
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let window = proto::Window(r.read_u32::<BigEndian>()?);
+        let x = r.read_u16::<BigEndian>()?;
+        let y = r.read_u16::<BigEndian>()?;
+        let width = r.read_u16::<BigEndian>()?;
+        let height = r.read_u16::<BigEndian>()?;
+        let count = r.read_u16::<BigEndian>()?;
+
+        Ok(Expose {
+            window,
+            x,
+            y,
+            width,
+            height,
+            count,
+        })
+    }
+}
+
+impl From<Expose> for proto::Rectangle {
+    fn from(expose: Expose) -> Self {
+        proto::Rectangle {
+            x: expose.x as i16,
+            y: expose.y as i16,
+            width: expose.width,
+            height: expose.height,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExposeRegion {
+    pub window: proto::Window,
+    pub rectangles: Vec<proto::Rectangle>,
+}
+
+impl ExposeRegion {
+    pub fn new(window: proto::Window) -> Self {
+        Self {
+            window,
+            rectangles: Vec::new(),
+        }
+    }
+
+    /// Adds a rectangle to the region, unless it's already covered by one of
+    /// the rectangles in it. Rectangles it covers are removed.
+    pub fn add(&mut self, rectangle: proto::Rectangle) {
+        if self.rectangles.iter().any(|r| r.contains(&rectangle)) {
+            return;
+        }
+
+        self.rectangles.retain(|r| !rectangle.contains(r));
+        self.rectangles.push(rectangle);
+    }
+
+    /// The smallest rectangle containing the whole region.
+    pub fn extents(&self) -> Option<proto::Rectangle> {
+        self.rectangles.iter().copied().reduce(|a, b| a.union(&b))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphicsExposure {
+    pub drawable: proto::Drawable,
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+    pub minor_opcode: u16,
+    pub count: u16,
+    pub major_opcode: u8,
+}
+
+impl GraphicsExposure {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        r.read_u8()?; // unused
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let drawable = proto::Drawable(r.read_u32::<BigEndian>()?);
+        let x = r.read_u16::<BigEndian>()?;
+        let y = r.read_u16::<BigEndian>()?;
+        let width = r.read_u16::<BigEndian>()?;
+        let height = r.read_u16::<BigEndian>()?;
+        let minor_opcode = r.read_u16::<BigEndian>()?;
+        let count = r.read_u16::<BigEndian>()?;
+        let major_opcode = r.read_u8()?;
+
+        Ok(GraphicsExposure {
+            drawable,
+            x,
+            y,
+            width,
+            height,
+            minor_opcode,
+            count,
+            major_opcode,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NoExposure {
+    pub drawable: proto::Drawable,
+    pub minor_opcode: u16,
+    pub major_opcode: u8,
+}
+
+impl NoExposure {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        r.read_u8()?; // unused
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let drawable = proto::Drawable(r.read_u32::<BigEndian>()?);
+        let minor_opcode = r.read_u16::<BigEndian>()?;
+        let major_opcode = r.read_u8()?;
+
+        Ok(NoExposure {
+            drawable,
+            minor_opcode,
+            major_opcode,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VisibilityNotify {
+    pub window: proto::Window,
+    pub state: u8,
+}
+
+impl VisibilityNotify {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        r.read_u8()?; // unused
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let window = proto::Window(r.read_u32::<BigEndian>()?);
+        let state = r.read_u8()?;
+
+        Ok(VisibilityNotify { window, state })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateNotify {
+    pub parent: proto::Window,
+    pub window: proto::Window,
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    pub border_width: u16,
+    pub override_redirect: bool,
+}
+
+impl CreateNotify {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        r.read_u8()?; // unused
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let parent = proto::Window(r.read_u32::<BigEndian>()?);
+        let window = proto::Window(r.read_u32::<BigEndian>()?);
+        let x = r.read_i16::<BigEndian>()?;
+        let y = r.read_i16::<BigEndian>()?;
+        let width = r.read_u16::<BigEndian>()?;
+        let height = r.read_u16::<BigEndian>()?;
+        let border_width = r.read_u16::<BigEndian>()?;
+        let override_redirect = r.read_u8()? != 0;
+
+        Ok(CreateNotify {
+            parent,
+            window,
+            x,
+            y,
+            width,
+            height,
+            border_width,
+            override_redirect,
         })
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct DestroyNotify {
+    pub event: proto::Window,
+    pub window: proto::Window,
+}
+
+impl DestroyNotify {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        r.read_u8()?; // unused
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let event = proto::Window(r.read_u32::<BigEndian>()?);
+        let window = proto::Window(r.read_u32::<BigEndian>()?);
+
+        Ok(DestroyNotify { event, window })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UnmapNotify {
+    pub event: proto::Window,
+    pub window: proto::Window,
+    pub from_configure: bool,
+}
+
+impl UnmapNotify {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        r.read_u8()?; // unused
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let event = proto::Window(r.read_u32::<BigEndian>()?);
+        let window = proto::Window(r.read_u32::<BigEndian>()?);
+        let from_configure = r.read_u8()? != 0;
+
+        Ok(UnmapNotify {
+            event,
+            window,
+            from_configure,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MapNotify {
+    pub event: proto::Window,
+    pub window: proto::Window,
+    pub override_redirect: bool,
+}
+
+impl MapNotify {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        r.read_u8()?; // unused
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let event = proto::Window(r.read_u32::<BigEndian>()?);
+        let window = proto::Window(r.read_u32::<BigEndian>()?);
+        let override_redirect = r.read_u8()? != 0;
+
+        Ok(MapNotify {
+            event,
+            window,
+            override_redirect,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MapRequest {
+    pub parent: proto::Window,
+    pub window: proto::Window,
+}
+
+impl MapRequest {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        r.read_u8()?; // unused
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let parent = proto::Window(r.read_u32::<BigEndian>()?);
+        let window = proto::Window(r.read_u32::<BigEndian>()?);
+
+        Ok(MapRequest { parent, window })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReparentNotify {
+    pub event: proto::Window,
+    pub window: proto::Window,
+    pub parent: proto::Window,
+    pub x: i16,
+    pub y: i16,
+    pub override_redirect: bool,
+}
+
+impl ReparentNotify {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        r.read_u8()?; // unused
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let event = proto::Window(r.read_u32::<BigEndian>()?);
+        let window = proto::Window(r.read_u32::<BigEndian>()?);
+        let parent = proto::Window(r.read_u32::<BigEndian>()?);
+        let x = r.read_i16::<BigEndian>()?;
+        let y = r.read_i16::<BigEndian>()?;
+        let override_redirect = r.read_u8()? != 0;
+
+        Ok(ReparentNotify {
+            event,
+            window,
+            parent,
+            x,
+            y,
+            override_redirect,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigureNotify {
+    pub event: proto::Window,
+    pub window: proto::Window,
+    pub above_sibling: Option<proto::Window>,
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    pub border_width: u16,
+    pub override_redirect: bool,
+}
+
+impl ConfigureNotify {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        r.read_u8()?; // unused
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let event = proto::Window(r.read_u32::<BigEndian>()?);
+        let window = proto::Window(r.read_u32::<BigEndian>()?);
+        let above_sibling = none_if_zero(r.read_u32::<BigEndian>()?);
+        let x = r.read_i16::<BigEndian>()?;
+        let y = r.read_i16::<BigEndian>()?;
+        let width = r.read_u16::<BigEndian>()?;
+        let height = r.read_u16::<BigEndian>()?;
+        let border_width = r.read_u16::<BigEndian>()?;
+        let override_redirect = r.read_u8()? != 0;
+
+        Ok(ConfigureNotify {
+            event,
+            window,
+            above_sibling,
+            x,
+            y,
+            width,
+            height,
+            border_width,
+            override_redirect,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigureRequest {
+    pub stack_mode: proto::StackMode,
+    pub parent: proto::Window,
+    pub window: proto::Window,
+    pub sibling: Option<proto::Window>,
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    pub border_width: u16,
+    pub value_mask: proto::ConfigureMask,
+}
+
+impl ConfigureRequest {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        let stack_mode = r.read_u8()?.try_into()?;
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let parent = proto::Window(r.read_u32::<BigEndian>()?);
+        let window = proto::Window(r.read_u32::<BigEndian>()?);
+        let sibling = none_if_zero(r.read_u32::<BigEndian>()?);
+        let x = r.read_i16::<BigEndian>()?;
+        let y = r.read_i16::<BigEndian>()?;
+        let width = r.read_u16::<BigEndian>()?;
+        let height = r.read_u16::<BigEndian>()?;
+        let border_width = r.read_u16::<BigEndian>()?;
+        let value_mask = proto::ConfigureMask::from_bits_truncate(r.read_u16::<BigEndian>()?);
+
+        Ok(ConfigureRequest {
+            stack_mode,
+            parent,
+            window,
+            sibling,
+            x,
+            y,
+            width,
+            height,
+            border_width,
+            value_mask,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GravityNotify {
+    pub event: proto::Window,
+    pub window: proto::Window,
+    pub x: i16,
+    pub y: i16,
+}
+
+impl GravityNotify {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        r.read_u8()?; // unused
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let event = proto::Window(r.read_u32::<BigEndian>()?);
+        let window = proto::Window(r.read_u32::<BigEndian>()?);
+        let x = r.read_i16::<BigEndian>()?;
+        let y = r.read_i16::<BigEndian>()?;
+
+        Ok(GravityNotify {
+            event,
+            window,
+            x,
+            y,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ResizeRequest {
+    pub window: proto::Window,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl ResizeRequest {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        r.read_u8()?; // unused
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let window = proto::Window(r.read_u32::<BigEndian>()?);
+        let width = r.read_u16::<BigEndian>()?;
+        let height = r.read_u16::<BigEndian>()?;
+
+        Ok(ResizeRequest {
+            window,
+            width,
+            height,
+        })
+    }
+}
+
+/// CirculateNotify and CirculateRequest. For a request, `event` is the
+/// parent window.
+#[derive(Debug, Clone)]
+pub struct Circulate {
+    pub event: proto::Window,
+    pub window: proto::Window,
+    pub place: u8,
+}
+
+impl Circulate {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        r.read_u8()?; // unused
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let event = proto::Window(r.read_u32::<BigEndian>()?);
+        let window = proto::Window(r.read_u32::<BigEndian>()?);
+        r.read_u32::<BigEndian>()?; // unused
+        let place = r.read_u8()?;
+
+        Ok(Circulate {
+            event,
+            window,
+            place,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PropertyNotify {
+    pub window: proto::Window,
+    pub atom: proto::Atom,
+    pub time: proto::Timestamp,
+    pub state: u8,
+}
+
+impl PropertyNotify {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        r.read_u8()?; // unused
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let window = proto::Window(r.read_u32::<BigEndian>()?);
+        let atom = proto::Atom(r.read_u32::<BigEndian>()?);
+        let time = proto::Timestamp(r.read_u32::<BigEndian>()?);
+        let state = r.read_u8()?;
+
+        Ok(PropertyNotify {
+            window,
+            atom,
+            time,
+            state,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SelectionClear {
+    pub time: proto::Timestamp,
+    pub owner: proto::Window,
+    pub selection: proto::Atom,
+}
+
+impl SelectionClear {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        r.read_u8()?; // unused
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let time = proto::Timestamp(r.read_u32::<BigEndian>()?);
+        let owner = proto::Window(r.read_u32::<BigEndian>()?);
+        let selection = proto::Atom(r.read_u32::<BigEndian>()?);
+
+        Ok(SelectionClear {
+            time,
+            owner,
+            selection,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SelectionRequest {
+    pub time: proto::Timestamp,
+    pub owner: proto::Window,
+    pub requestor: proto::Window,
+    pub selection: proto::Atom,
+    pub target: proto::Atom,
+    pub property: Option<proto::Atom>,
+}
+
+impl SelectionRequest {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        r.read_u8()?; // unused
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let time = proto::Timestamp(r.read_u32::<BigEndian>()?);
+        let owner = proto::Window(r.read_u32::<BigEndian>()?);
+        let requestor = proto::Window(r.read_u32::<BigEndian>()?);
+        let selection = proto::Atom(r.read_u32::<BigEndian>()?);
+        let target = proto::Atom(r.read_u32::<BigEndian>()?);
+        let property = none_if_zero(r.read_u32::<BigEndian>()?);
+
+        Ok(SelectionRequest {
+            time,
+            owner,
+            requestor,
+            selection,
+            target,
+            property,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SelectionNotify {
+    pub time: proto::Timestamp,
+    pub requestor: proto::Window,
+    pub selection: proto::Atom,
+    pub target: proto::Atom,
+    pub property: Option<proto::Atom>,
+}
+
+impl SelectionNotify {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        r.read_u8()?; // unused
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let time = proto::Timestamp(r.read_u32::<BigEndian>()?);
+        let requestor = proto::Window(r.read_u32::<BigEndian>()?);
+        let selection = proto::Atom(r.read_u32::<BigEndian>()?);
+        let target = proto::Atom(r.read_u32::<BigEndian>()?);
+        let property = none_if_zero(r.read_u32::<BigEndian>()?);
+
+        Ok(SelectionNotify {
+            time,
+            requestor,
+            selection,
+            target,
+            property,
+        })
+    }
+
+    /// Encodes the event for SendEvent.
+    pub fn encode(&self) -> [u8; 32] {
+        let mut event = [0; 32];
+        event[0] = 31; // SelectionNotify
+        event[4..8].copy_from_slice(&self.time.0.to_be_bytes());
+        event[8..12].copy_from_slice(&self.requestor.0.to_be_bytes());
+        event[12..16].copy_from_slice(&self.selection.0.to_be_bytes());
+        event[16..20].copy_from_slice(&self.target.0.to_be_bytes());
+        event[20..24].copy_from_slice(&self.property.map_or(0, |atom| atom.0).to_be_bytes());
+        event
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ColormapNotify {
+    pub window: proto::Window,
+    pub colormap: Option<proto::Colormap>,
+    pub new: bool,
+    pub installed: bool,
+}
+
+impl ColormapNotify {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        r.read_u8()?; // unused
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let window = proto::Window(r.read_u32::<BigEndian>()?);
+        let colormap = none_if_zero(r.read_u32::<BigEndian>()?);
+        let new = r.read_u8()? != 0;
+        let installed = r.read_u8()? != 0;
+
+        Ok(ColormapNotify {
+            window,
+            colormap,
+            new,
+            installed,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientMessage {
+    pub format: u8,
+    pub window: proto::Window,
+    pub message_type: proto::Atom,
+    pub data: [u8; 20],
+}
+
+impl ClientMessage {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        let format = r.read_u8()?;
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let window = proto::Window(r.read_u32::<BigEndian>()?);
+        let message_type = proto::Atom(r.read_u32::<BigEndian>()?);
+
+        let mut data = [0; 20];
+        r.read_exact(&mut data)?;
+
+        Ok(ClientMessage {
+            format,
+            window,
+            message_type,
+            data,
+        })
+    }
+
+    /// A message of format 32.
+    pub fn new(window: proto::Window, message_type: proto::Atom, data: [u32; 5]) -> Self {
+        let mut bytes = [0; 20];
+        for (chunk, v) in bytes.chunks_exact_mut(4).zip(data) {
+            chunk.copy_from_slice(&v.to_be_bytes());
+        }
+
+        ClientMessage {
+            format: 32,
+            window,
+            message_type,
+            data: bytes,
+        }
+    }
+
+    /// The data of a message of format 32.
+    pub fn longs(&self) -> [u32; 5] {
+        let mut data = [0; 5];
+        for (v, chunk) in data.iter_mut().zip(self.data.chunks_exact(4)) {
+            *v = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        data
+    }
+
+    /// Encodes the message for SendEvent.
+    pub fn encode(&self) -> [u8; 32] {
+        let mut event = [0; 32];
+        event[0] = 33; // ClientMessage
+        event[1] = self.format;
+        event[4..8].copy_from_slice(&self.window.0.to_be_bytes());
+        event[8..12].copy_from_slice(&self.message_type.0.to_be_bytes());
+        event[12..].copy_from_slice(&self.data);
+        event
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MappingNotify {
+    pub request: u8,
+    pub first_keycode: u8,
+    pub count: u8,
+}
+
+impl MappingNotify {
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        r.read_u8()?; // unused
+        let _sequence_number = r.read_u16::<BigEndian>()?;
+        let request = r.read_u8()?;
+        let first_keycode = r.read_u8()?;
+        let count = r.read_u8()?;
+
+        Ok(MappingNotify {
+            request,
+            first_keycode,
+            count,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Generic {
+    /// The major opcode of the extension.
+    pub extension: u8,
+    pub evtype: u16,
+    /// The whole event, starting with its code, for the decoders of the
+    /// generated `protocol` events.
+    pub data: Vec<u8>,
+}
+
+impl Generic {
+    /// Reads the rest of a GenericEvent, whose code has already been read.
+    pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
+        let mut data = vec![0; 32];
+        data[0] = proto::GENERIC_EVENT;
+        r.read_exact(&mut data[1..])?;
+
+        let length = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+        data.resize(32 + usize::try_from(length)? * 4, 0);
+        r.read_exact(&mut data[32..])?;
+
+        Ok(Generic {
+            extension: data[1],
+            evtype: u16::from_be_bytes([data[8], data[9]]),
+            data,
+        })
+    }
+}
+
+fn none_if_zero<T: From<u32>>(v: u32) -> Option<T> {
+    match v {
+        0 => None,
+        v => Some(T::from(v)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rectangle(x: i16, y: i16, width: u16, height: u16) -> proto::Rectangle {
+        proto::Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn expose_regions_keep_only_uncovered_rectangles() {
+        let mut region = ExposeRegion::new(proto::Window(7));
+        assert_eq!(region.extents(), None);
+
+        region.add(rectangle(0, 0, 10, 10));
+        region.add(rectangle(20, 0, 10, 10));
+
+        // Covered by the first rectangle.
+        region.add(rectangle(2, 2, 5, 5));
+        assert_eq!(region.rectangles.len(), 2);
+
+        // Covers the first rectangle, which is dropped.
+        region.add(rectangle(0, 0, 15, 15));
+        assert_eq!(
+            region.rectangles,
+            [rectangle(20, 0, 10, 10), rectangle(0, 0, 15, 15)]
+        );
+
+        assert_eq!(region.extents(), Some(rectangle(0, 0, 30, 15)));
+    }
+}
@@ -0,0 +1,1172 @@
+use crate::{atom, display, error, event, proto, request};
+
+pub const NET_SUPPORTED: &str = "_NET_SUPPORTED";
+pub const NET_CLIENT_LIST: &str = "_NET_CLIENT_LIST";
+pub const NET_CLIENT_LIST_STACKING: &str = "_NET_CLIENT_LIST_STACKING";
+pub const NET_NUMBER_OF_DESKTOPS: &str = "_NET_NUMBER_OF_DESKTOPS";
+pub const NET_DESKTOP_NAMES: &str = "_NET_DESKTOP_NAMES";
+pub const NET_CURRENT_DESKTOP: &str = "_NET_CURRENT_DESKTOP";
+pub const NET_ACTIVE_WINDOW: &str = "_NET_ACTIVE_WINDOW";
+pub const NET_WORKAREA: &str = "_NET_WORKAREA";
+pub const NET_SUPPORTING_WM_CHECK: &str = "_NET_SUPPORTING_WM_CHECK";
+pub const NET_WM_NAME: &str = "_NET_WM_NAME";
+pub const NET_WM_STATE: &str = "_NET_WM_STATE";
+pub const NET_WM_WINDOW_TYPE: &str = "_NET_WM_WINDOW_TYPE";
+pub const NET_WM_DESKTOP: &str = "_NET_WM_DESKTOP";
+pub const NET_WM_STRUT: &str = "_NET_WM_STRUT";
+pub const NET_WM_STRUT_PARTIAL: &str = "_NET_WM_STRUT_PARTIAL";
+pub const NET_WM_PID: &str = "_NET_WM_PID";
+pub const NET_FRAME_EXTENTS: &str = "_NET_FRAME_EXTENTS";
+pub const NET_WM_WINDOW_OPACITY: &str = "_NET_WM_WINDOW_OPACITY";
+pub const UTF8_STRING: &str = "UTF8_STRING";
+
+/// The `_NET_WM_DESKTOP` of windows shown on every desktop.
+pub const ALL_DESKTOPS: u32 = 0xffffffff;
+
+/// Generates an enum of atoms named `<prefix><NAME>`, with conversions to
+/// and from their names.
+macro_rules! atom_names {
+    ($name:ident, $prefix:literal, { $($variant:ident => $atom:literal),* $(,)? }) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($variant),*
+        }
+
+        impl $name {
+            pub const ALL: &'static [$name] = &[$($name::$variant),*];
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $($name::$variant => concat!($prefix, $atom)),*
+                }
+            }
+
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $(concat!($prefix, $atom) => Some($name::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+atom_names!(WmState, "_NET_WM_STATE_", {
+    Modal => "MODAL",
+    Sticky => "STICKY",
+    MaximizedVert => "MAXIMIZED_VERT",
+    MaximizedHorz => "MAXIMIZED_HORZ",
+    Shaded => "SHADED",
+    SkipTaskbar => "SKIP_TASKBAR",
+    SkipPager => "SKIP_PAGER",
+    Hidden => "HIDDEN",
+    Fullscreen => "FULLSCREEN",
+    Above => "ABOVE",
+    Below => "BELOW",
+    DemandsAttention => "DEMANDS_ATTENTION",
+    Focused => "FOCUSED",
+});
+
+atom_names!(WindowType, "_NET_WM_WINDOW_TYPE_", {
+    Desktop => "DESKTOP",
+    Dock => "DOCK",
+    Toolbar => "TOOLBAR",
+    Menu => "MENU",
+    Utility => "UTILITY",
+    Splash => "SPLASH",
+    Dialog => "DIALOG",
+    DropdownMenu => "DROPDOWN_MENU",
+    PopupMenu => "POPUP_MENU",
+    Tooltip => "TOOLTIP",
+    Notification => "NOTIFICATION",
+    Combo => "COMBO",
+    Dnd => "DND",
+    Normal => "NORMAL",
+});
+
+/// What a `_NET_WM_STATE` client message does with the states it names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateAction {
+    Remove,
+    Add,
+    Toggle,
+}
+
+impl From<StateAction> for u32 {
+    fn from(v: StateAction) -> u32 {
+        match v {
+            StateAction::Remove => 0,
+            StateAction::Add => 1,
+            StateAction::Toggle => 2,
+        }
+    }
+}
+
+impl TryFrom<u32> for StateAction {
+    type Error = anyhow::Error;
+
+    fn try_from(v: u32) -> anyhow::Result<Self> {
+        match v {
+            0 => Ok(StateAction::Remove),
+            1 => Ok(StateAction::Add),
+            2 => Ok(StateAction::Toggle),
+
+            _ => anyhow::bail!("bad StateAction"),
+        }
+    }
+}
+
+impl StateAction {
+    /// Applies the action to a window's states.
+    pub fn apply(self, states: &mut Vec<WmState>, state: WmState) {
+        let position = states.iter().position(|&s| s == state);
+
+        match (self, position) {
+            (StateAction::Add | StateAction::Toggle, None) => states.push(state),
+            (StateAction::Remove | StateAction::Toggle, Some(i)) => {
+                states.remove(i);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Space along the edges of a window or the screen, like
+/// `_NET_FRAME_EXTENTS` or `_NET_WM_STRUT`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Extents {
+    pub left: u32,
+    pub right: u32,
+    pub top: u32,
+    pub bottom: u32,
+}
+
+impl Extents {
+    fn read(value: &[u32]) -> Option<Self> {
+        match *value {
+            [left, right, top, bottom, ..] => Some(Extents {
+                left,
+                right,
+                top,
+                bottom,
+            }),
+            _ => None,
+        }
+    }
+
+    fn write(&self) -> [u32; 4] {
+        [self.left, self.right, self.top, self.bottom]
+    }
+}
+
+/// `_NET_WM_STRUT_PARTIAL`: space reserved at the edges of the root window,
+/// along with the range of each edge it covers. Ranges are inclusive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Strut {
+    pub extents: Extents,
+    pub left: (u32, u32),
+    pub right: (u32, u32),
+    pub top: (u32, u32),
+    pub bottom: (u32, u32),
+}
+
+impl Strut {
+    /// A strut covering the whole length of every edge, like the older
+    /// `_NET_WM_STRUT`.
+    pub fn full(extents: Extents) -> Self {
+        let full = (0, u32::MAX);
+
+        Strut {
+            extents,
+            left: full,
+            right: full,
+            top: full,
+            bottom: full,
+        }
+    }
+
+    fn read(value: &[u32]) -> Option<Self> {
+        match *value {
+            [_, _, _, _, a, b, c, d, e, f, g, h, ..] => Some(Strut {
+                extents: Extents::read(value)?,
+                left: (a, b),
+                right: (c, d),
+                top: (e, f),
+                bottom: (g, h),
+            }),
+            _ => None,
+        }
+    }
+
+    fn write(&self) -> [u32; 12] {
+        let [left, right, top, bottom] = self.extents.write();
+
+        [
+            left,
+            right,
+            top,
+            bottom,
+            self.left.0,
+            self.left.1,
+            self.right.0,
+            self.right.1,
+            self.top.0,
+            self.top.1,
+            self.bottom.0,
+            self.bottom.1,
+        ]
+    }
+}
+
+/// Who sent a request to the window manager.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// Clients written against older versions of the specification.
+    Unknown,
+    Application,
+    /// Pagers, taskbars and other tools acting for the user.
+    Pager,
+}
+
+impl From<Source> for u32 {
+    fn from(v: Source) -> u32 {
+        match v {
+            Source::Unknown => 0,
+            Source::Application => 1,
+            Source::Pager => 2,
+        }
+    }
+}
+
+impl From<u32> for Source {
+    fn from(v: u32) -> Self {
+        match v {
+            1 => Source::Application,
+            2 => Source::Pager,
+            _ => Source::Unknown,
+        }
+    }
+}
+
+/// A request to the window manager, sent as a ClientMessage to the root
+/// window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RootMessage {
+    NumberOfDesktops(u32),
+    CurrentDesktop {
+        desktop: u32,
+        time: proto::Timestamp,
+    },
+    ActiveWindow {
+        window: proto::Window,
+        source: Source,
+        time: proto::Timestamp,
+        /// The client's currently active window, if it has one.
+        current: Option<proto::Window>,
+    },
+    /// Change one or two of a window's `_NET_WM_STATE`s. States this
+    /// library doesn't know are `None`.
+    WmState {
+        window: proto::Window,
+        action: StateAction,
+        first: Option<WmState>,
+        second: Option<WmState>,
+        source: Source,
+    },
+    WmDesktop {
+        window: proto::Window,
+        desktop: u32,
+        source: Source,
+    },
+}
+
+impl display::Display {
+    fn root_window(&self) -> anyhow::Result<proto::Window> {
+        self.root().ok_or_else(|| anyhow::anyhow!("no screen"))
+    }
+
+    /// Gets a property of format 32, or `None` if it doesn't exist or has
+    /// another type.
+    pub(crate) async fn get_u32s(
+        &mut self,
+        window: proto::Window,
+        name: &str,
+        property_type: atom::Atom,
+    ) -> anyhow::Result<Option<Vec<u32>>> {
+        let property = self.intern_atom(name).await?;
+
+        self.get_property(window, property, Some(property_type.into()))
+            .await?
+            .map(|value| value.u32s())
+            .transpose()
+    }
+
+    pub(crate) async fn set_u32s(
+        &mut self,
+        window: proto::Window,
+        name: &str,
+        property_type: atom::Atom,
+        data: &[u32],
+    ) -> anyhow::Result<()> {
+        let property = self.intern_atom(name).await?;
+
+        self.send(&request::ChangeProperty::u32s(
+            window,
+            property,
+            property_type.into(),
+            data,
+        ))
+        .await
+    }
+
+    /// Sends a ClientMessage to the root window, where the window manager
+    /// picks it up.
+    pub(crate) async fn send_root_message(
+        &mut self,
+        window: proto::Window,
+        name: &str,
+        data: [u32; 5],
+    ) -> anyhow::Result<()> {
+        let root = self.root_window()?;
+        let message_type = self.intern_atom(name).await?;
+        let message = event::ClientMessage::new(window, message_type, data);
+
+        self.send(
+            request::SendEvent::new(proto::Destination::Window(root), message.encode()).event_mask(
+                proto::EventMask::SUBSTRUCTURE_NOTIFY | proto::EventMask::SUBSTRUCTURE_REDIRECT,
+            ),
+        )
+        .await
+    }
+
+    pub async fn net_supported(&mut self) -> anyhow::Result<Vec<proto::Atom>> {
+        let root = self.root_window()?;
+        let atoms = self.get_u32s(root, NET_SUPPORTED, atom::Atom::Atom).await?;
+
+        Ok(atoms
+            .unwrap_or_default()
+            .into_iter()
+            .map(proto::Atom)
+            .collect())
+    }
+
+    pub async fn set_net_supported(&mut self, atoms: &[proto::Atom]) -> anyhow::Result<()> {
+        let root = self.root_window()?;
+        let atoms = atoms.iter().map(|atom| atom.0).collect::<Vec<_>>();
+
+        self.set_u32s(root, NET_SUPPORTED, atom::Atom::Atom, &atoms)
+            .await
+    }
+
+    /// The managed windows, oldest first.
+    pub async fn net_client_list(&mut self) -> anyhow::Result<Vec<proto::Window>> {
+        self.get_windows(NET_CLIENT_LIST).await
+    }
+
+    pub async fn set_net_client_list(&mut self, windows: &[proto::Window]) -> anyhow::Result<()> {
+        self.set_windows(NET_CLIENT_LIST, windows).await
+    }
+
+    /// The managed windows, bottom to top.
+    pub async fn net_client_list_stacking(&mut self) -> anyhow::Result<Vec<proto::Window>> {
+        self.get_windows(NET_CLIENT_LIST_STACKING).await
+    }
+
+    pub async fn set_net_client_list_stacking(
+        &mut self,
+        windows: &[proto::Window],
+    ) -> anyhow::Result<()> {
+        self.set_windows(NET_CLIENT_LIST_STACKING, windows).await
+    }
+
+    async fn get_windows(&mut self, name: &str) -> anyhow::Result<Vec<proto::Window>> {
+        let root = self.root_window()?;
+        let windows = self.get_u32s(root, name, atom::Atom::Window).await?;
+
+        Ok(windows
+            .unwrap_or_default()
+            .into_iter()
+            .map(proto::Window)
+            .collect())
+    }
+
+    async fn set_windows(&mut self, name: &str, windows: &[proto::Window]) -> anyhow::Result<()> {
+        let root = self.root_window()?;
+        let windows = windows.iter().map(|window| window.0).collect::<Vec<_>>();
+
+        self.set_u32s(root, name, atom::Atom::Window, &windows)
+            .await
+    }
+
+    async fn get_root_u32(
+        &mut self,
+        name: &str,
+        property_type: atom::Atom,
+    ) -> anyhow::Result<Option<u32>> {
+        let root = self.root_window()?;
+        let value = self.get_u32s(root, name, property_type).await?;
+
+        Ok(value.and_then(|value| value.first().copied()))
+    }
+
+    pub async fn net_number_of_desktops(&mut self) -> anyhow::Result<Option<u32>> {
+        self.get_root_u32(NET_NUMBER_OF_DESKTOPS, atom::Atom::Cardinal)
+            .await
+    }
+
+    pub async fn set_net_number_of_desktops(&mut self, number: u32) -> anyhow::Result<()> {
+        let root = self.root_window()?;
+        self.set_u32s(
+            root,
+            NET_NUMBER_OF_DESKTOPS,
+            atom::Atom::Cardinal,
+            &[number],
+        )
+        .await
+    }
+
+    /// Asks the window manager to change the number of desktops.
+    pub async fn request_number_of_desktops(&mut self, number: u32) -> anyhow::Result<()> {
+        let root = self.root_window()?;
+        self.send_root_message(root, NET_NUMBER_OF_DESKTOPS, [number, 0, 0, 0, 0])
+            .await
+    }
+
+    pub async fn net_desktop_names(&mut self) -> anyhow::Result<Vec<String>> {
+        let root = self.root_window()?;
+        let property = self.intern_atom(NET_DESKTOP_NAMES).await?;
+        let utf8_string = self.intern_atom(UTF8_STRING).await?;
+
+        let value = match self.get_property(root, property, Some(utf8_string)).await? {
+            Some(value) => value,
+            None => return Ok(Vec::new()),
+        };
+
+        Ok(split_strings(value.u8s()?))
+    }
+
+    pub async fn set_net_desktop_names(&mut self, names: &[&str]) -> anyhow::Result<()> {
+        let root = self.root_window()?;
+        let property = self.intern_atom(NET_DESKTOP_NAMES).await?;
+        let utf8_string = self.intern_atom(UTF8_STRING).await?;
+
+        self.send(&request::ChangeProperty::new(
+            root,
+            property,
+            utf8_string,
+            &join_strings(names),
+        ))
+        .await
+    }
+
+    pub async fn net_current_desktop(&mut self) -> anyhow::Result<Option<u32>> {
+        self.get_root_u32(NET_CURRENT_DESKTOP, atom::Atom::Cardinal)
+            .await
+    }
+
+    pub async fn set_net_current_desktop(&mut self, desktop: u32) -> anyhow::Result<()> {
+        let root = self.root_window()?;
+        self.set_u32s(root, NET_CURRENT_DESKTOP, atom::Atom::Cardinal, &[desktop])
+            .await
+    }
+
+    /// Asks the window manager to switch to another desktop.
+    pub async fn request_current_desktop(
+        &mut self,
+        desktop: u32,
+        time: proto::Timestamp,
+    ) -> anyhow::Result<()> {
+        let root = self.root_window()?;
+        self.send_root_message(root, NET_CURRENT_DESKTOP, [desktop, time.0, 0, 0, 0])
+            .await
+    }
+
+    pub async fn net_active_window(&mut self) -> anyhow::Result<Option<proto::Window>> {
+        let window = self
+            .get_root_u32(NET_ACTIVE_WINDOW, atom::Atom::Window)
+            .await?;
+
+        Ok(window.filter(|&w| w != 0).map(proto::Window))
+    }
+
+    /// Sets the active window, or `None` if no window is active.
+    pub async fn set_net_active_window(
+        &mut self,
+        window: Option<proto::Window>,
+    ) -> anyhow::Result<()> {
+        let root = self.root_window()?;
+        let window = window.map_or(0, |window| window.0);
+
+        self.set_u32s(root, NET_ACTIVE_WINDOW, atom::Atom::Window, &[window])
+            .await
+    }
+
+    /// Asks the window manager to activate a window.
+    pub async fn request_active_window(
+        &mut self,
+        window: proto::Window,
+        source: Source,
+        time: proto::Timestamp,
+        current: Option<proto::Window>,
+    ) -> anyhow::Result<()> {
+        let current = current.map_or(0, |window| window.0);
+
+        self.send_root_message(
+            window,
+            NET_ACTIVE_WINDOW,
+            [source.into(), time.0, current, 0, 0],
+        )
+        .await
+    }
+
+    /// The area of each desktop not covered by panels and docks. Values
+    /// too big for a rectangle, which no screen has, are clamped rather
+    /// than failing the whole property.
+    pub async fn net_workarea(&mut self) -> anyhow::Result<Vec<proto::Rectangle>> {
+        let root = self.root_window()?;
+        let value = self
+            .get_u32s(root, NET_WORKAREA, atom::Atom::Cardinal)
+            .await?
+            .unwrap_or_default();
+
+        let position = |v: u32| i16::try_from(v).unwrap_or(i16::MAX);
+        let size = |v: u32| u16::try_from(v).unwrap_or(u16::MAX);
+
+        Ok(value
+            .chunks_exact(4)
+            .map(|v| proto::Rectangle {
+                x: position(v[0]),
+                y: position(v[1]),
+                width: size(v[2]),
+                height: size(v[3]),
+            })
+            .collect())
+    }
+
+    pub async fn set_net_workarea(&mut self, areas: &[proto::Rectangle]) -> anyhow::Result<()> {
+        let root = self.root_window()?;
+        let value = areas
+            .iter()
+            .flat_map(|area| {
+                [
+                    area.x.max(0) as u32,
+                    area.y.max(0) as u32,
+                    area.width.into(),
+                    area.height.into(),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        self.set_u32s(root, NET_WORKAREA, atom::Atom::Cardinal, &value)
+            .await
+    }
+
+    /// The window a compliant window manager creates to show that it's
+    /// running. `None` if the property is missing, or is stale because it
+    /// doesn't point to a window pointing back at itself.
+    pub async fn net_supporting_wm_check(&mut self) -> anyhow::Result<Option<proto::Window>> {
+        let root = self.root_window()?;
+        let check = match self
+            .get_u32s(root, NET_SUPPORTING_WM_CHECK, atom::Atom::Window)
+            .await?
+            .and_then(|value| value.first().copied())
+        {
+            Some(check) => proto::Window(check),
+            None => return Ok(None),
+        };
+
+        let child = match self
+            .get_u32s(check, NET_SUPPORTING_WM_CHECK, atom::Atom::Window)
+            .await
+        {
+            Ok(value) => value.and_then(|value| value.first().copied()),
+            Err(err) => match err.downcast_ref::<error::Error>() {
+                Some(error::Error::Window(_)) => None,
+                _ => return Err(err),
+            },
+        };
+
+        Ok(Some(check).filter(|_| child == Some(check.0)))
+    }
+
+    /// Sets `_NET_SUPPORTING_WM_CHECK` on the root window and on `check`,
+    /// and names `check` after the window manager.
+    pub async fn set_net_supporting_wm_check(
+        &mut self,
+        check: proto::Window,
+        wm_name: &str,
+    ) -> anyhow::Result<()> {
+        let root = self.root_window()?;
+
+        for window in [root, check] {
+            self.set_u32s(
+                window,
+                NET_SUPPORTING_WM_CHECK,
+                atom::Atom::Window,
+                &[check.0],
+            )
+            .await?;
+        }
+
+        let property = self.intern_atom(NET_WM_NAME).await?;
+        let utf8_string = self.intern_atom(UTF8_STRING).await?;
+
+        self.send(&request::ChangeProperty::utf8(
+            check,
+            property,
+            utf8_string,
+            wm_name,
+        ))
+        .await
+    }
+
+    /// Decodes a request to the window manager. Returns `None` for other
+    /// client messages.
+    pub async fn root_message(
+        &mut self,
+        message: &event::ClientMessage,
+    ) -> anyhow::Result<Option<RootMessage>> {
+        if message.format != 32 {
+            return Ok(None);
+        }
+
+        let name = self.atom_name(message.message_type).await?;
+        let data = message.longs();
+
+        Ok(match name.as_str() {
+            NET_NUMBER_OF_DESKTOPS => Some(RootMessage::NumberOfDesktops(data[0])),
+
+            NET_CURRENT_DESKTOP => Some(RootMessage::CurrentDesktop {
+                desktop: data[0],
+                time: proto::Timestamp(data[1]),
+            }),
+
+            NET_ACTIVE_WINDOW => Some(RootMessage::ActiveWindow {
+                window: message.window,
+                source: data[0].into(),
+                time: proto::Timestamp(data[1]),
+                current: Some(proto::Window(data[2])).filter(|w| w.0 != 0),
+            }),
+
+            NET_WM_STATE => Some(RootMessage::WmState {
+                window: message.window,
+                action: match data[0].try_into() {
+                    Ok(action) => action,
+                    Err(_) => return Ok(None),
+                },
+                first: self.wm_state_from_atom(data[1]).await?,
+                second: self.wm_state_from_atom(data[2]).await?,
+                source: data[3].into(),
+            }),
+
+            NET_WM_DESKTOP => Some(RootMessage::WmDesktop {
+                window: message.window,
+                desktop: data[0],
+                source: data[1].into(),
+            }),
+
+            _ => None,
+        })
+    }
+
+    async fn wm_state_from_atom(&mut self, atom: u32) -> anyhow::Result<Option<WmState>> {
+        if atom == 0 {
+            return Ok(None);
+        }
+
+        Ok(WmState::from_name(
+            &self.atom_name(proto::Atom(atom)).await?,
+        ))
+    }
+
+    /// Gets an ATOM[] property naming atoms of a known set, leaving out
+    /// any others.
+    async fn get_named_atoms<T>(
+        &mut self,
+        window: proto::Window,
+        name: &str,
+        from_name: fn(&str) -> Option<T>,
+    ) -> anyhow::Result<Vec<T>> {
+        let atoms = self
+            .get_u32s(window, name, atom::Atom::Atom)
+            .await?
+            .unwrap_or_default();
+
+        let mut values = Vec::new();
+        for atom in atoms {
+            if let Some(value) = from_name(&self.atom_name(proto::Atom(atom)).await?) {
+                values.push(value);
+            }
+        }
+
+        Ok(values)
+    }
+
+    async fn set_named_atoms(
+        &mut self,
+        window: proto::Window,
+        name: &str,
+        names: &[&str],
+    ) -> anyhow::Result<()> {
+        let atoms = self.intern_atoms(names).await?;
+        let atoms = atoms.iter().map(|atom| atom.0).collect::<Vec<_>>();
+
+        self.set_u32s(window, name, atom::Atom::Atom, &atoms).await
+    }
+
+    pub async fn net_wm_state(&mut self, window: proto::Window) -> anyhow::Result<Vec<WmState>> {
+        self.get_named_atoms(window, NET_WM_STATE, WmState::from_name)
+            .await
+    }
+
+    /// Sets a window's states. Only the window manager does this; clients
+    /// ask for changes with `request_wm_state`.
+    pub async fn set_net_wm_state(
+        &mut self,
+        window: proto::Window,
+        states: &[WmState],
+    ) -> anyhow::Result<()> {
+        let names = states.iter().map(|state| state.name()).collect::<Vec<_>>();
+        self.set_named_atoms(window, NET_WM_STATE, &names).await
+    }
+
+    /// Asks the window manager to change one or two states of a mapped
+    /// window, like both maximized states at once.
+    pub async fn request_wm_state(
+        &mut self,
+        window: proto::Window,
+        action: StateAction,
+        first: WmState,
+        second: Option<WmState>,
+        source: Source,
+    ) -> anyhow::Result<()> {
+        let first = self.intern_atom(first.name()).await?;
+        let second = match second {
+            Some(state) => self.intern_atom(state.name()).await?.0,
+            None => 0,
+        };
+
+        self.send_root_message(
+            window,
+            NET_WM_STATE,
+            [action.into(), first.0, second, source.into(), 0],
+        )
+        .await
+    }
+
+    /// A window's types, most preferred first.
+    pub async fn net_wm_window_type(
+        &mut self,
+        window: proto::Window,
+    ) -> anyhow::Result<Vec<WindowType>> {
+        self.get_named_atoms(window, NET_WM_WINDOW_TYPE, WindowType::from_name)
+            .await
+    }
+
+    pub async fn set_net_wm_window_type(
+        &mut self,
+        window: proto::Window,
+        types: &[WindowType],
+    ) -> anyhow::Result<()> {
+        let names = types.iter().map(|t| t.name()).collect::<Vec<_>>();
+        self.set_named_atoms(window, NET_WM_WINDOW_TYPE, &names)
+            .await
+    }
+
+    /// The desktop a window is on, or `ALL_DESKTOPS`.
+    pub async fn net_wm_desktop(&mut self, window: proto::Window) -> anyhow::Result<Option<u32>> {
+        let value = self
+            .get_u32s(window, NET_WM_DESKTOP, atom::Atom::Cardinal)
+            .await?;
+
+        Ok(value.and_then(|value| value.first().copied()))
+    }
+
+    pub async fn set_net_wm_desktop(
+        &mut self,
+        window: proto::Window,
+        desktop: u32,
+    ) -> anyhow::Result<()> {
+        self.set_u32s(window, NET_WM_DESKTOP, atom::Atom::Cardinal, &[desktop])
+            .await
+    }
+
+    /// Asks the window manager to move a mapped window to another desktop.
+    pub async fn request_wm_desktop(
+        &mut self,
+        window: proto::Window,
+        desktop: u32,
+        source: Source,
+    ) -> anyhow::Result<()> {
+        self.send_root_message(window, NET_WM_DESKTOP, [desktop, source.into(), 0, 0, 0])
+            .await
+    }
+
+    /// The space a window reserves at the edges of the screen, from
+    /// `_NET_WM_STRUT_PARTIAL` or else `_NET_WM_STRUT`.
+    pub async fn net_wm_strut(&mut self, window: proto::Window) -> anyhow::Result<Option<Strut>> {
+        let partial = self
+            .get_u32s(window, NET_WM_STRUT_PARTIAL, atom::Atom::Cardinal)
+            .await?;
+
+        if let Some(strut) = partial.as_deref().and_then(Strut::read) {
+            return Ok(Some(strut));
+        }
+
+        let strut = self
+            .get_u32s(window, NET_WM_STRUT, atom::Atom::Cardinal)
+            .await?;
+
+        Ok(strut.as_deref().and_then(Extents::read).map(Strut::full))
+    }
+
+    /// Sets both `_NET_WM_STRUT_PARTIAL` and, for older window managers,
+    /// `_NET_WM_STRUT`.
+    pub async fn set_net_wm_strut(
+        &mut self,
+        window: proto::Window,
+        strut: &Strut,
+    ) -> anyhow::Result<()> {
+        self.set_u32s(
+            window,
+            NET_WM_STRUT_PARTIAL,
+            atom::Atom::Cardinal,
+            &strut.write(),
+        )
+        .await?;
+
+        self.set_u32s(
+            window,
+            NET_WM_STRUT,
+            atom::Atom::Cardinal,
+            &strut.extents.write(),
+        )
+        .await
+    }
+
+    /// The process ID of the client, which may be on another machine.
+    pub async fn net_wm_pid(&mut self, window: proto::Window) -> anyhow::Result<Option<u32>> {
+        let value = self
+            .get_u32s(window, NET_WM_PID, atom::Atom::Cardinal)
+            .await?;
+
+        Ok(value.and_then(|value| value.first().copied()))
+    }
+
+    pub async fn set_net_wm_pid(&mut self, window: proto::Window, pid: u32) -> anyhow::Result<()> {
+        self.set_u32s(window, NET_WM_PID, atom::Atom::Cardinal, &[pid])
+            .await
+    }
+
+    /// The size of the frame the window manager put around a window.
+    pub async fn net_frame_extents(
+        &mut self,
+        window: proto::Window,
+    ) -> anyhow::Result<Option<Extents>> {
+        let value = self
+            .get_u32s(window, NET_FRAME_EXTENTS, atom::Atom::Cardinal)
+            .await?;
+
+        Ok(value.as_deref().and_then(Extents::read))
+    }
+
+    pub async fn set_net_frame_extents(
+        &mut self,
+        window: proto::Window,
+        extents: &Extents,
+    ) -> anyhow::Result<()> {
+        self.set_u32s(
+            window,
+            NET_FRAME_EXTENTS,
+            atom::Atom::Cardinal,
+            &extents.write(),
+        )
+        .await
+    }
+
+    /// A window's opacity for compositing managers, from 0 for transparent
+    /// to `u32::MAX` for opaque.
+    pub async fn net_wm_window_opacity(
+        &mut self,
+        window: proto::Window,
+    ) -> anyhow::Result<Option<u32>> {
+        let value = self
+            .get_u32s(window, NET_WM_WINDOW_OPACITY, atom::Atom::Cardinal)
+            .await?;
+
+        Ok(value.and_then(|value| value.first().copied()))
+    }
+
+    pub async fn set_net_wm_window_opacity(
+        &mut self,
+        window: proto::Window,
+        opacity: u32,
+    ) -> anyhow::Result<()> {
+        self.set_u32s(
+            window,
+            NET_WM_WINDOW_OPACITY,
+            atom::Atom::Cardinal,
+            &[opacity],
+        )
+        .await
+    }
+
+    /// Computes the work area of the first screen from the struts of every
+    /// window in `_NET_CLIENT_LIST`. Windows that are gone are skipped.
+    pub async fn compute_workarea(&mut self) -> anyhow::Result<proto::Rectangle> {
+        let (width, height) = match self.screen() {
+            Some(screen) => (screen.width_in_pixels, screen.height_in_pixels),
+            None => anyhow::bail!("no screen"),
+        };
+
+        let mut struts = Vec::new();
+        for window in self.net_client_list().await? {
+            match self.net_wm_strut(window).await {
+                Ok(strut) => struts.extend(strut),
+                Err(err) => match err.downcast_ref::<error::Error>() {
+                    Some(error::Error::Window(_)) => {}
+                    _ => return Err(err),
+                },
+            }
+        }
+
+        let screen = proto::Rectangle {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        };
+
+        Ok(workarea(width, height, screen, &struts))
+    }
+}
+
+/// Splits a list of null-terminated UTF-8 strings.
+pub fn split_strings(value: &[u8]) -> Vec<String> {
+    let value = value.strip_suffix(&[0]).unwrap_or(value);
+    if value.is_empty() {
+        return Vec::new();
+    }
+
+    value
+        .split(|&b| b == 0)
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .collect()
+}
+
+pub fn join_strings(names: &[&str]) -> Vec<u8> {
+    names
+        .iter()
+        .flat_map(|name| name.bytes().chain([0]))
+        .collect()
+}
+
+/// The part of `monitor` not covered by any strut. Struts are measured from
+/// the edges of the root window, which is `root_width` by `root_height`. A
+/// strut counts if its range overlaps the monitor, and each edge keeps the
+/// largest reservation, whatever order the struts come in.
+pub fn workarea(
+    root_width: u16,
+    root_height: u16,
+    monitor: proto::Rectangle,
+    struts: &[Strut],
+) -> proto::Rectangle {
+    let (root_width, root_height) = (i64::from(root_width), i64::from(root_height));
+
+    let (x, y) = (i64::from(monitor.x), i64::from(monitor.y));
+    let (width, height) = (i64::from(monitor.width), i64::from(monitor.height));
+
+    let (mut left, mut top) = (x, y);
+    let (mut right, mut bottom) = (x + width, y + height);
+
+    // Whether an inclusive range along an edge overlaps [start, end).
+    let overlaps = |(first, last): (u32, u32), start: i64, end: i64| {
+        i64::from(first) < end && i64::from(last) >= start
+    };
+
+    for strut in struts {
+        let extents = strut.extents;
+
+        if extents.left > 0 && overlaps(strut.left, y, y + height) {
+            left = left.max(i64::from(extents.left));
+        }
+        if extents.right > 0 && overlaps(strut.right, y, y + height) {
+            right = right.min(root_width - i64::from(extents.right));
+        }
+        if extents.top > 0 && overlaps(strut.top, x, x + width) {
+            top = top.max(i64::from(extents.top));
+        }
+        if extents.bottom > 0 && overlaps(strut.bottom, x, x + width) {
+            bottom = bottom.min(root_height - i64::from(extents.bottom));
+        }
+    }
+
+    proto::Rectangle {
+        x: left as i16,
+        y: top as i16,
+        width: (right - left).max(0) as u16,
+        height: (bottom - top).max(0) as u16,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake;
+    use crate::request::Request;
+
+    fn rectangle(x: i16, y: i16, width: u16, height: u16) -> proto::Rectangle {
+        proto::Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Decodes the message a request to the window manager sent to the
+    /// root window.
+    async fn root_message(
+        display: &mut display::Display,
+        request: fake::Request,
+    ) -> anyhow::Result<Option<RootMessage>> {
+        assert_eq!(request.u32(4), fake::ROOT.0);
+
+        match request.event()? {
+            event::Event::ClientMessage(message) => display.root_message(&message).await,
+            event => anyhow::bail!("unexpected event {:?}", event),
+        }
+    }
+
+    #[tokio::test]
+    async fn root_messages_round_trip() -> anyhow::Result<()> {
+        let (mut display, mut server) = fake::connect()?;
+        let (window, current) = (proto::Window(0x40_0001), proto::Window(0x50_0001));
+
+        let ((), request) = tokio::try_join!(
+            display.request_number_of_desktops(4),
+            server.expect(request::SendEvent::OPCODE)
+        )?;
+        assert_eq!(
+            root_message(&mut display, request).await?,
+            Some(RootMessage::NumberOfDesktops(4))
+        );
+
+        let ((), request) = tokio::try_join!(
+            display.request_current_desktop(2, proto::Timestamp(7)),
+            server.expect(request::SendEvent::OPCODE)
+        )?;
+        assert_eq!(
+            root_message(&mut display, request).await?,
+            Some(RootMessage::CurrentDesktop {
+                desktop: 2,
+                time: proto::Timestamp(7),
+            })
+        );
+
+        for current in [Some(current), None] {
+            let ((), request) = tokio::try_join!(
+                display.request_active_window(window, Source::Pager, proto::Timestamp(9), current),
+                server.expect(request::SendEvent::OPCODE)
+            )?;
+            assert_eq!(
+                root_message(&mut display, request).await?,
+                Some(RootMessage::ActiveWindow {
+                    window,
+                    source: Source::Pager,
+                    time: proto::Timestamp(9),
+                    current,
+                })
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn workarea_values_too_big_are_clamped() -> anyhow::Result<()> {
+        let (mut display, mut server) = fake::connect()?;
+
+        let value = [0, 0, 1920, 1080, 70_000, 40_000, 100_000, 5]
+            .iter()
+            .flat_map(|v: &u32| v.to_be_bytes())
+            .collect::<Vec<_>>();
+
+        let (areas, ()) = tokio::try_join!(display.net_workarea(), async {
+            server.expect(request::GetProperty::OPCODE).await?;
+            server
+                .reply_property(32, atom::Atom::Cardinal.into(), 0, &value)
+                .await
+        })?;
+
+        assert_eq!(
+            areas,
+            [
+                rectangle(0, 0, 1920, 1080),
+                rectangle(i16::MAX, i16::MAX, u16::MAX, 5),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn struts_are_checked_against_the_whole_monitor() {
+        // A top panel covering the first 100 pixels of the top edge, and a
+        // left panel reaching up to the very top of the left edge.
+        let top = Strut {
+            extents: Extents {
+                top: 30,
+                ..Extents::default()
+            },
+            top: (0, 99),
+            ..Strut::default()
+        };
+        let left = Strut {
+            extents: Extents {
+                left: 40,
+                ..Extents::default()
+            },
+            left: (0, 20),
+            ..Strut::default()
+        };
+
+        let monitor = rectangle(0, 0, 1920, 1080);
+        let expected = rectangle(40, 30, 1880, 1050);
+
+        assert_eq!(workarea(1920, 1080, monitor, &[top, left]), expected);
+        assert_eq!(workarea(1920, 1080, monitor, &[left, top]), expected);
+    }
+
+    #[test]
+    fn each_edge_keeps_the_largest_reservation() {
+        let bottom = |height| {
+            Strut::full(Extents {
+                bottom: height,
+                ..Extents::default()
+            })
+        };
+
+        let monitor = rectangle(0, 0, 1920, 1080);
+        let area = workarea(1920, 1080, monitor, &[bottom(50), bottom(20)]);
+
+        assert_eq!(area, rectangle(0, 0, 1920, 1030));
+    }
+
+    #[test]
+    fn struts_on_another_monitor_are_ignored() {
+        let left = Strut {
+            extents: Extents {
+                left: 40,
+                ..Extents::default()
+            },
+            left: (0, 1079),
+            ..Strut::default()
+        };
+
+        let monitor = rectangle(0, 1080, 1920, 1080);
+        let area = workarea(1920, 2160, monitor, &[left]);
+
+        assert_eq!(area, monitor);
+    }
+}
@@ -0,0 +1,341 @@
+//! A fake X server for tests. A `Display` is connected to it through a
+//! socket pair; tests read the requests it sends and write the replies and
+//! events by hand. InternAtom is answered by the server itself, so that
+//! tests only see the requests they are about.
+
+use crate::{display, event, proto, setup};
+use std::collections::HashMap;
+use std::io;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+use tokio::time;
+
+pub const ROOT: proto::Window = proto::Window(1);
+
+/// How long the server waits for a request, and the display for the rest
+/// of a message, before the test fails.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Atoms handed out for InternAtom start here, past the predefined ones.
+const FIRST_ATOM: u32 = 100;
+
+const OPCODE_INTERN_ATOM: u8 = 16;
+
+/// A request read by the server.
+#[derive(Debug)]
+pub struct Request {
+    pub opcode: u8,
+    /// The whole request, header included.
+    pub data: Vec<u8>,
+}
+
+impl Request {
+    pub fn u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.data[offset], self.data[offset + 1]])
+    }
+
+    pub fn u32(&self, offset: usize) -> u32 {
+        let bytes = &self.data[offset..offset + 4];
+        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    /// The event sent by a SendEvent request.
+    pub fn event(&self) -> anyhow::Result<event::Event> {
+        decode(&self.data[12..44])
+    }
+}
+
+pub struct Server {
+    conn: UnixStream,
+    /// The sequence number of the last request read.
+    sequence: u16,
+    atoms: HashMap<String, proto::Atom>,
+}
+
+/// Connects a display to a new server with a single screen, whose root is
+/// `ROOT`.
+pub fn connect() -> anyhow::Result<(display::Display, Server)> {
+    let (client, conn) = UnixStream::pair()?;
+    let display = display::Display::new(client, setup(), TIMEOUT);
+
+    let server = Server {
+        conn,
+        sequence: 0,
+        atoms: HashMap::new(),
+    };
+
+    Ok((display, server))
+}
+
+impl Server {
+    /// The atom the server hands out for `name`.
+    pub fn atom(&mut self, name: &str) -> proto::Atom {
+        let next = proto::Atom(FIRST_ATOM + self.atoms.len() as u32);
+        *self.atoms.entry(String::from(name)).or_insert(next)
+    }
+
+    /// Reads the next request other than InternAtom.
+    pub async fn request(&mut self) -> anyhow::Result<Request> {
+        loop {
+            let request = self.read_request().await?;
+            if request.opcode != OPCODE_INTERN_ATOM {
+                return Ok(request);
+            }
+
+            self.intern_atom(&request).await?;
+        }
+    }
+
+    /// Answers InternAtom for `names`, in order, for calls that make no
+    /// other request.
+    pub async fn intern(&mut self, names: &[&str]) -> anyhow::Result<()> {
+        for name in names {
+            let request = self.read_request().await?;
+            anyhow::ensure!(
+                request.opcode == OPCODE_INTERN_ATOM,
+                "expected InternAtom {}, got {:?}",
+                name,
+                request
+            );
+
+            let interned = self.intern_atom(&request).await?;
+            anyhow::ensure!(interned == *name, "expected {}, got {}", name, interned);
+        }
+
+        Ok(())
+    }
+
+    /// Answers an InternAtom request. Returns the name.
+    async fn intern_atom(&mut self, request: &Request) -> anyhow::Result<String> {
+        let length = usize::from(request.u16(4));
+        let name = String::from_utf8(request.data[8..8 + length].to_vec())?;
+
+        let atom = self.atom(&name);
+        self.reply(0, &atom.0.to_be_bytes()).await?;
+
+        Ok(name)
+    }
+
+    /// Reads the next request other than InternAtom, which has to have
+    /// `opcode`.
+    pub async fn expect(&mut self, opcode: u8) -> anyhow::Result<Request> {
+        let request = self.request().await?;
+        anyhow::ensure!(
+            request.opcode == opcode,
+            "expected request {}, got {:?}",
+            opcode,
+            request
+        );
+
+        Ok(request)
+    }
+
+    async fn read_request(&mut self) -> anyhow::Result<Request> {
+        let mut header = [0; 4];
+        time::timeout(TIMEOUT, self.conn.read_exact(&mut header)).await??;
+
+        let length = usize::from(u16::from_be_bytes([header[2], header[3]])) * 4;
+        let mut data = header.to_vec();
+        data.resize(length, 0);
+        time::timeout(TIMEOUT, self.conn.read_exact(&mut data[4..])).await??;
+
+        self.sequence = self.sequence.wrapping_add(1);
+
+        Ok(Request {
+            opcode: header[0],
+            data,
+        })
+    }
+
+    /// Answers the last request. `body` is everything after the length
+    /// field, padded to the 24 bytes every reply has.
+    pub async fn reply(&mut self, data: u8, body: &[u8]) -> anyhow::Result<()> {
+        self.reply_to(self.sequence, data, body).await
+    }
+
+    /// Answers the request with sequence number `sequence`.
+    pub async fn reply_to(&mut self, sequence: u16, data: u8, body: &[u8]) -> anyhow::Result<()> {
+        let mut body = body.to_vec();
+        body.resize(body.len().max(24).next_multiple_of(4), 0);
+
+        let mut reply = vec![proto::RESPONSE_REPLY, data];
+        reply.extend(sequence.to_be_bytes());
+        reply.extend((((body.len() - 24) / 4) as u32).to_be_bytes());
+        reply.extend(body);
+
+        self.send(&reply).await
+    }
+
+    /// Answers the last request with a GetProperty reply.
+    pub async fn reply_property(
+        &mut self,
+        format: u8,
+        property_type: proto::Atom,
+        bytes_after: u32,
+        value: &[u8],
+    ) -> anyhow::Result<()> {
+        let value_len = match format {
+            0 => 0,
+            format => value.len() / usize::from(format / 8),
+        };
+
+        let mut body = Vec::new();
+        body.extend(property_type.0.to_be_bytes());
+        body.extend(bytes_after.to_be_bytes());
+        body.extend((value_len as u32).to_be_bytes());
+        body.extend([0; 12]);
+        body.extend(value);
+
+        self.reply(format, &body).await
+    }
+
+    /// Answers the last request with a GetWindowAttributes reply in which
+    /// only the events selected by this client are set.
+    pub async fn reply_window_attributes(
+        &mut self,
+        your_event_mask: proto::EventMask,
+    ) -> anyhow::Result<()> {
+        let mut body = [0; 36];
+        body[28..32].copy_from_slice(&your_event_mask.bits().to_be_bytes());
+
+        self.reply(0, &body).await
+    }
+
+    /// Answers the last request with an error.
+    pub async fn error(&mut self, code: u8, value: u32) -> anyhow::Result<()> {
+        let mut error = [0; 32];
+        error[1] = code;
+        error[2..4].copy_from_slice(&self.sequence.to_be_bytes());
+        error[4..8].copy_from_slice(&value.to_be_bytes());
+
+        self.send(&error).await
+    }
+
+    /// Writes an event, or anything else, as it is.
+    pub async fn send(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        Ok(self.conn.write_all(data).await?)
+    }
+}
+
+/// The body of a GetGeometry reply for a window `width` pixels wide.
+pub fn geometry(width: u16) -> Vec<u8> {
+    let mut body = vec![0; 24];
+    body[8..10].copy_from_slice(&width.to_be_bytes());
+    body
+}
+
+/// Reads an event the way the display does.
+pub fn decode(event: &[u8]) -> anyhow::Result<event::Event> {
+    event::Event::read(&mut io::Cursor::new(event))
+}
+
+fn fields(code: u8, fields: &[u32]) -> [u8; 32] {
+    let mut event = [0; 32];
+    event[0] = code;
+
+    for (i, field) in fields.iter().enumerate() {
+        event[4 + i * 4..8 + i * 4].copy_from_slice(&field.to_be_bytes());
+    }
+
+    event
+}
+
+pub fn motion_notify(window: proto::Window, x: i16, y: i16) -> [u8; 32] {
+    let mut event = fields(6, &[0, ROOT.0, window.0, 0]);
+    event[24..26].copy_from_slice(&x.to_be_bytes());
+    event[26..28].copy_from_slice(&y.to_be_bytes());
+    event
+}
+
+pub fn expose(window: proto::Window, rectangle: proto::Rectangle, count: u16) -> [u8; 32] {
+    let mut event = fields(12, &[window.0]);
+    event[8..10].copy_from_slice(&rectangle.x.to_be_bytes());
+    event[10..12].copy_from_slice(&rectangle.y.to_be_bytes());
+    event[12..14].copy_from_slice(&rectangle.width.to_be_bytes());
+    event[14..16].copy_from_slice(&rectangle.height.to_be_bytes());
+    event[16..18].copy_from_slice(&count.to_be_bytes());
+    event
+}
+
+pub fn configure_notify(window: proto::Window, width: u16, height: u16) -> [u8; 32] {
+    let mut event = fields(22, &[window.0, window.0, 0]);
+    event[20..22].copy_from_slice(&width.to_be_bytes());
+    event[22..24].copy_from_slice(&height.to_be_bytes());
+    event
+}
+
+pub fn property_notify(
+    window: proto::Window,
+    atom: proto::Atom,
+    time: proto::Timestamp,
+    state: u8,
+) -> [u8; 32] {
+    let mut event = fields(28, &[window.0, atom.0, time.0]);
+    event[16] = state;
+    event
+}
+
+pub fn selection_request(
+    time: proto::Timestamp,
+    owner: proto::Window,
+    requestor: proto::Window,
+    selection: proto::Atom,
+    target: proto::Atom,
+    property: proto::Atom,
+) -> [u8; 32] {
+    fields(
+        30,
+        &[
+            time.0,
+            owner.0,
+            requestor.0,
+            selection.0,
+            target.0,
+            property.0,
+        ],
+    )
+}
+
+fn setup() -> setup::Setup {
+    setup::Setup {
+        protocol_major_version: 11,
+        protocol_minor_version: 0,
+        length: 0,
+        release_number: 0,
+        resource_id_base: 0x0020_0000,
+        resource_id_mask: 0x001f_ffff,
+        motion_buffer_size: 0,
+        vendor_length: 0,
+        maximum_request_length: u16::MAX,
+        screen_count: 1,
+        pixmap_format_count: 0,
+        image_byte_order: 1,
+        bitmap_format_bit_order: 1,
+        bitmap_format_scanline_unit: 32,
+        bitmap_format_scanline_pad: 32,
+        min_keycode: 8,
+        max_keycode: 255,
+        vendor: String::new(),
+        pixmap_formats: Vec::new(),
+        screens: vec![setup::Screen {
+            root_window: ROOT,
+            colormap: proto::Colormap(0x20),
+            white_pixel: 0xffffff,
+            black_pixel: 0,
+            current_input_masks: proto::EventMask::empty(),
+            width_in_pixels: 1920,
+            height_in_pixels: 1080,
+            width_in_millimeters: 508,
+            height_in_millimeters: 286,
+            min_installed_maps: 1,
+            max_installed_maps: 1,
+            root_visual: proto::Visualid(0x21),
+            backing_stores: setup::BackingStores::Never,
+            save_unders: 0,
+            root_depth: 24,
+            allowed_depth_count: 0,
+            depths: Vec::new(),
+        }],
+    }
+}
@@ -0,0 +1,775 @@
+use crate::{atom, display, proto, reply, request};
+
+bitflags::bitflags! {
+    /// Which fields of WM_HINTS are set.
+    #[derive(Default)]
+    struct HintsFlags: u32 {
+        const INPUT = 0x0001;
+        const STATE = 0x0002;
+        const ICON_PIXMAP = 0x0004;
+        const ICON_WINDOW = 0x0008;
+        const ICON_POSITION = 0x0010;
+        const ICON_MASK = 0x0020;
+        const WINDOW_GROUP = 0x0040;
+        const URGENCY = 0x0100;
+    }
+}
+
+bitflags::bitflags! {
+    /// Which fields of WM_SIZE_HINTS are set.
+    #[derive(Default)]
+    struct SizeHintsFlags: u32 {
+        const US_POSITION = 0x0001;
+        const US_SIZE = 0x0002;
+        const P_POSITION = 0x0004;
+        const P_SIZE = 0x0008;
+        const P_MIN_SIZE = 0x0010;
+        const P_MAX_SIZE = 0x0020;
+        const P_RESIZE_INC = 0x0040;
+        const P_ASPECT = 0x0080;
+        const P_BASE_SIZE = 0x0100;
+        const P_WIN_GRAVITY = 0x0200;
+    }
+}
+
+/// The number of 32-bit fields in WM_HINTS.
+const HINTS_LENGTH: usize = 9;
+
+/// The number of 32-bit fields in WM_SIZE_HINTS.
+const SIZE_HINTS_LENGTH: usize = 18;
+
+/// The type of a text property.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Latin-1.
+    String,
+    CompoundText,
+    Utf8String,
+}
+
+impl Encoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::String => "STRING",
+            Encoding::CompoundText => "COMPOUND_TEXT",
+            Encoding::Utf8String => "UTF8_STRING",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "STRING" => Some(Encoding::String),
+            "COMPOUND_TEXT" => Some(Encoding::CompoundText),
+            "UTF8_STRING" => Some(Encoding::Utf8String),
+            _ => None,
+        }
+    }
+}
+
+pub fn decode_text(encoding: Encoding, value: &[u8]) -> String {
+    match encoding {
+        Encoding::String => value.iter().map(|&b| char::from(b)).collect(),
+        Encoding::CompoundText => decode_compound_text(value),
+        Encoding::Utf8String => String::from_utf8_lossy(value).into_owned(),
+    }
+}
+
+/// Encodes text as STRING if it's all Latin-1, and as COMPOUND_TEXT
+/// otherwise, as ICCCM asks for.
+pub fn encode_text(text: &str) -> (Encoding, Vec<u8>) {
+    let latin1 = text
+        .chars()
+        .map(|c| u8::try_from(u32::from(c)).ok())
+        .collect::<Option<Vec<_>>>();
+
+    match latin1 {
+        Some(value) => (Encoding::String, value),
+        None => (Encoding::CompoundText, encode_compound_text(text)),
+    }
+}
+
+/// The character sets compound text can switch between. Only ASCII, the
+/// right half of Latin-1 and UTF-8 segments are decoded; text in any other
+/// character set comes out as U+FFFD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Charset {
+    Ascii,
+    Latin1,
+    Other,
+}
+
+const ESC: u8 = 0x1b;
+const CSI: u8 = 0x9b;
+
+fn decode_compound_text(value: &[u8]) -> String {
+    let mut text = String::new();
+    let mut gl = Charset::Ascii;
+    let mut gr = Charset::Latin1;
+    let mut i = 0;
+
+    while i < value.len() {
+        match value[i] {
+            ESC => {
+                let start = i + 1;
+                let mut end = start;
+                while end < value.len() && (0x20..=0x2f).contains(&value[end]) {
+                    end += 1;
+                }
+
+                let intermediate = &value[start..end];
+                let last = match value.get(end) {
+                    Some(&last) => last,
+                    None => break,
+                };
+                i = end + 1;
+
+                match (intermediate, last) {
+                    (b"(", b'B') | (b"(", b'J') => gl = Charset::Ascii,
+                    (b"(", _) | (b"$(", _) => gl = Charset::Other,
+                    (b"-", b'A') => gr = Charset::Latin1,
+                    (b"-", _) | (b")", _) | (b"$)", _) => gr = Charset::Other,
+
+                    // A UTF-8 segment, ended by ESC % @.
+                    (b"%", b'G') => {
+                        let end = value[i..]
+                            .windows(3)
+                            .position(|w| w == [ESC, b'%', b'@'])
+                            .map_or(value.len(), |n| i + n);
+
+                        text.push_str(&String::from_utf8_lossy(&value[i..end]));
+                        i = (end + 3).min(value.len());
+                    }
+
+                    _ => {}
+                }
+            }
+
+            // Direction changes; skipped up to their final byte.
+            CSI => {
+                i += 1;
+                while i < value.len() && !(0x40..=0x7e).contains(&value[i]) {
+                    i += 1;
+                }
+                i += 1;
+            }
+
+            b @ (b'\t' | b'\n') => {
+                text.push(char::from(b));
+                i += 1;
+            }
+
+            b @ 0x20..=0x7f => {
+                text.push(match gl {
+                    Charset::Other if b != b' ' => char::REPLACEMENT_CHARACTER,
+                    _ => char::from(b),
+                });
+                i += 1;
+            }
+
+            b @ 0xa0..=0xff => {
+                text.push(match gr {
+                    Charset::Latin1 => char::from(b),
+                    _ => char::REPLACEMENT_CHARACTER,
+                });
+                i += 1;
+            }
+
+            // Other control characters aren't allowed.
+            _ => i += 1,
+        }
+    }
+
+    text
+}
+
+fn encode_compound_text(text: &str) -> Vec<u8> {
+    let mut value = vec![ESC, b'%', b'G'];
+    value.extend_from_slice(text.as_bytes());
+    value.extend_from_slice(&[ESC, b'%', b'@']);
+    value
+}
+
+/// WM_CLASS, two consecutive null-terminated strings.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WmClass {
+    pub instance: String,
+    pub class: String,
+}
+
+impl WmClass {
+    pub fn read(value: &reply::GetProperty) -> anyhow::Result<Self> {
+        let mut parts = value
+            .u8s()?
+            .split(|&b| b == 0)
+            .map(|part| decode_text(Encoding::String, part));
+
+        Ok(WmClass {
+            instance: parts.next().unwrap_or_default(),
+            class: parts.next().unwrap_or_default(),
+        })
+    }
+
+    pub fn change(&self, window: proto::Window) -> request::ChangeProperty {
+        let mut value = Vec::new();
+        for part in [&self.instance, &self.class] {
+            value.extend(part.chars().map(|c| u8::try_from(c).unwrap_or(b'?')));
+            value.push(0);
+        }
+
+        request::ChangeProperty::new(
+            window,
+            atom::Atom::WmClass.into(),
+            atom::Atom::String.into(),
+            &value,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitialState {
+    Withdrawn,
+    Normal,
+    Iconic,
+}
+
+impl TryFrom<u32> for InitialState {
+    type Error = anyhow::Error;
+
+    fn try_from(v: u32) -> anyhow::Result<Self> {
+        match v {
+            0 => Ok(InitialState::Withdrawn),
+            1 => Ok(InitialState::Normal),
+            3 => Ok(InitialState::Iconic),
+
+            _ => anyhow::bail!("bad InitialState"),
+        }
+    }
+}
+
+impl From<InitialState> for u32 {
+    fn from(v: InitialState) -> u32 {
+        match v {
+            InitialState::Withdrawn => 0,
+            InitialState::Normal => 1,
+            InitialState::Iconic => 3,
+        }
+    }
+}
+
+/// WM_HINTS. Fields the client didn't set are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WmHints {
+    /// Whether the client relies on the window manager to get keyboard
+    /// input.
+    pub input: Option<bool>,
+    pub initial_state: Option<InitialState>,
+    pub icon_pixmap: Option<proto::Pixmap>,
+    pub icon_window: Option<proto::Window>,
+    pub icon_position: Option<(i32, i32)>,
+    pub icon_mask: Option<proto::Pixmap>,
+    pub window_group: Option<proto::Window>,
+    pub urgent: bool,
+}
+
+impl WmHints {
+    pub fn read(value: &reply::GetProperty) -> anyhow::Result<Self> {
+        // Clients written against X11R3 leave out the window group. Fields
+        // missing from a truncated property count as unset, whatever the
+        // flags say.
+        let mut fields = value.u32s()?;
+        let present = fields.len();
+        fields.resize(HINTS_LENGTH, 0);
+
+        let flags = HintsFlags::from_bits_truncate(fields[0]);
+        let set = |flag, last: usize| flags.contains(flag) && last < present;
+
+        Ok(WmHints {
+            input: Some(fields[1] != 0).filter(|_| set(HintsFlags::INPUT, 1)),
+            initial_state: match set(HintsFlags::STATE, 2) {
+                true => InitialState::try_from(fields[2]).ok(),
+                false => None,
+            },
+            icon_pixmap: Some(proto::Pixmap(fields[3])).filter(|_| set(HintsFlags::ICON_PIXMAP, 3)),
+            icon_window: Some(proto::Window(fields[4])).filter(|_| set(HintsFlags::ICON_WINDOW, 4)),
+            icon_position: Some((fields[5] as i32, fields[6] as i32))
+                .filter(|_| set(HintsFlags::ICON_POSITION, 6)),
+            icon_mask: Some(proto::Pixmap(fields[7])).filter(|_| set(HintsFlags::ICON_MASK, 7)),
+            window_group: Some(proto::Window(fields[8]))
+                .filter(|_| set(HintsFlags::WINDOW_GROUP, 8)),
+            urgent: set(HintsFlags::URGENCY, 0),
+        })
+    }
+
+    pub fn change(&self, window: proto::Window) -> request::ChangeProperty {
+        let mut flags = HintsFlags::empty();
+        let mut fields = [0; HINTS_LENGTH];
+
+        if let Some(input) = self.input {
+            flags |= HintsFlags::INPUT;
+            fields[1] = input.into();
+        }
+        if let Some(state) = self.initial_state {
+            flags |= HintsFlags::STATE;
+            fields[2] = state.into();
+        }
+        if let Some(pixmap) = self.icon_pixmap {
+            flags |= HintsFlags::ICON_PIXMAP;
+            fields[3] = pixmap.0;
+        }
+        if let Some(icon) = self.icon_window {
+            flags |= HintsFlags::ICON_WINDOW;
+            fields[4] = icon.0;
+        }
+        if let Some((x, y)) = self.icon_position {
+            flags |= HintsFlags::ICON_POSITION;
+            fields[5] = x as u32;
+            fields[6] = y as u32;
+        }
+        if let Some(mask) = self.icon_mask {
+            flags |= HintsFlags::ICON_MASK;
+            fields[7] = mask.0;
+        }
+        if let Some(group) = self.window_group {
+            flags |= HintsFlags::WINDOW_GROUP;
+            fields[8] = group.0;
+        }
+        if self.urgent {
+            flags |= HintsFlags::URGENCY;
+        }
+
+        fields[0] = flags.bits();
+
+        request::ChangeProperty::u32s(
+            window,
+            atom::Atom::WmHints.into(),
+            atom::Atom::WmHints.into(),
+            &fields,
+        )
+    }
+}
+
+/// Minimum and maximum aspect ratios, as (numerator, denominator).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Aspect {
+    pub min: (u32, u32),
+    pub max: (u32, u32),
+}
+
+/// WM_SIZE_HINTS, as found in WM_NORMAL_HINTS. Fields the client didn't
+/// set are `None`. The obsolete position and size fields are only kept as
+/// their flags.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WmSizeHints {
+    pub user_position: bool,
+    pub user_size: bool,
+    pub program_position: bool,
+    pub program_size: bool,
+    pub min_size: Option<(u32, u32)>,
+    pub max_size: Option<(u32, u32)>,
+    pub resize_inc: Option<(u32, u32)>,
+    pub aspect: Option<Aspect>,
+    pub base_size: Option<(u32, u32)>,
+    pub win_gravity: Option<proto::WinGravity>,
+}
+
+impl WmSizeHints {
+    pub fn read(value: &reply::GetProperty) -> anyhow::Result<Self> {
+        // Clients written before X11R4 leave out the base size and gravity.
+        // Fields missing from a truncated property count as unset.
+        let mut fields = value.u32s()?;
+        let present = fields.len();
+        fields.resize(SIZE_HINTS_LENGTH, 0);
+
+        let flags = SizeHintsFlags::from_bits_truncate(fields[0]);
+        let set = |flag, last: usize| flags.contains(flag) && last < present;
+        let pair = |flag, i: usize| Some((fields[i], fields[i + 1])).filter(|_| set(flag, i + 1));
+
+        Ok(WmSizeHints {
+            user_position: flags.contains(SizeHintsFlags::US_POSITION),
+            user_size: flags.contains(SizeHintsFlags::US_SIZE),
+            program_position: flags.contains(SizeHintsFlags::P_POSITION),
+            program_size: flags.contains(SizeHintsFlags::P_SIZE),
+            min_size: pair(SizeHintsFlags::P_MIN_SIZE, 5),
+            max_size: pair(SizeHintsFlags::P_MAX_SIZE, 7),
+            resize_inc: pair(SizeHintsFlags::P_RESIZE_INC, 9),
+            aspect: match set(SizeHintsFlags::P_ASPECT, 14) {
+                true => Some(Aspect {
+                    min: (fields[11], fields[12]),
+                    max: (fields[13], fields[14]),
+                }),
+                false => None,
+            },
+            base_size: pair(SizeHintsFlags::P_BASE_SIZE, 15),
+            win_gravity: match set(SizeHintsFlags::P_WIN_GRAVITY, 17) {
+                true => u8::try_from(fields[17])
+                    .ok()
+                    .and_then(|v| proto::WinGravity::try_from(v).ok()),
+                false => None,
+            },
+        })
+    }
+
+    pub fn change(&self, window: proto::Window) -> request::ChangeProperty {
+        let mut flags = SizeHintsFlags::empty();
+        let mut fields = [0; SIZE_HINTS_LENGTH];
+
+        flags.set(SizeHintsFlags::US_POSITION, self.user_position);
+        flags.set(SizeHintsFlags::US_SIZE, self.user_size);
+        flags.set(SizeHintsFlags::P_POSITION, self.program_position);
+        flags.set(SizeHintsFlags::P_SIZE, self.program_size);
+
+        let pairs = [
+            (SizeHintsFlags::P_MIN_SIZE, 5, self.min_size),
+            (SizeHintsFlags::P_MAX_SIZE, 7, self.max_size),
+            (SizeHintsFlags::P_RESIZE_INC, 9, self.resize_inc),
+            (SizeHintsFlags::P_BASE_SIZE, 15, self.base_size),
+        ];
+
+        for (flag, i, pair) in pairs {
+            if let Some((a, b)) = pair {
+                flags |= flag;
+                fields[i] = a;
+                fields[i + 1] = b;
+            }
+        }
+
+        if let Some(aspect) = self.aspect {
+            flags |= SizeHintsFlags::P_ASPECT;
+            fields[11] = aspect.min.0;
+            fields[12] = aspect.min.1;
+            fields[13] = aspect.max.0;
+            fields[14] = aspect.max.1;
+        }
+        if let Some(gravity) = self.win_gravity {
+            flags |= SizeHintsFlags::P_WIN_GRAVITY;
+            fields[17] = u8::from(gravity).into();
+        }
+
+        fields[0] = flags.bits();
+
+        request::ChangeProperty::u32s(
+            window,
+            atom::Atom::WmNormalHints.into(),
+            atom::Atom::WmSizeHints.into(),
+            &fields,
+        )
+    }
+}
+
+impl display::Display {
+    /// Gets a text property of type STRING, COMPOUND_TEXT or UTF8_STRING.
+    pub async fn get_text_property(
+        &mut self,
+        window: proto::Window,
+        property: proto::Atom,
+    ) -> anyhow::Result<Option<String>> {
+        let value = match self.get_property(window, property, None).await? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        let property_type = value.type_;
+        let name = self.atom_name(property_type).await?;
+        let encoding = Encoding::from_name(&name)
+            .ok_or_else(|| anyhow::anyhow!("property has type {}, not text", name))?;
+
+        Ok(Some(decode_text(encoding, value.u8s()?)))
+    }
+
+    /// Sets a text property, as STRING if possible and as COMPOUND_TEXT
+    /// otherwise.
+    pub async fn set_text_property(
+        &mut self,
+        window: proto::Window,
+        property: proto::Atom,
+        text: &str,
+    ) -> anyhow::Result<()> {
+        let (encoding, value) = encode_text(text);
+        let property_type = self.intern_atom(encoding.name()).await?;
+
+        self.send(&request::ChangeProperty::new(
+            window,
+            property,
+            property_type,
+            &value,
+        ))
+        .await
+    }
+
+    pub async fn wm_name(&mut self, window: proto::Window) -> anyhow::Result<Option<String>> {
+        self.get_text_property(window, atom::Atom::WmName.into())
+            .await
+    }
+
+    pub async fn set_wm_name(&mut self, window: proto::Window, name: &str) -> anyhow::Result<()> {
+        self.set_text_property(window, atom::Atom::WmName.into(), name)
+            .await
+    }
+
+    pub async fn wm_icon_name(&mut self, window: proto::Window) -> anyhow::Result<Option<String>> {
+        self.get_text_property(window, atom::Atom::WmIconName.into())
+            .await
+    }
+
+    pub async fn set_wm_icon_name(
+        &mut self,
+        window: proto::Window,
+        name: &str,
+    ) -> anyhow::Result<()> {
+        self.set_text_property(window, atom::Atom::WmIconName.into(), name)
+            .await
+    }
+
+    pub async fn wm_client_machine(
+        &mut self,
+        window: proto::Window,
+    ) -> anyhow::Result<Option<String>> {
+        self.get_text_property(window, atom::Atom::WmClientMachine.into())
+            .await
+    }
+
+    pub async fn set_wm_client_machine(
+        &mut self,
+        window: proto::Window,
+        name: &str,
+    ) -> anyhow::Result<()> {
+        self.set_text_property(window, atom::Atom::WmClientMachine.into(), name)
+            .await
+    }
+
+    pub async fn wm_class(&mut self, window: proto::Window) -> anyhow::Result<Option<WmClass>> {
+        self.get_property(
+            window,
+            atom::Atom::WmClass.into(),
+            Some(atom::Atom::String.into()),
+        )
+        .await?
+        .map(|value| WmClass::read(&value))
+        .transpose()
+    }
+
+    pub async fn set_wm_class(
+        &mut self,
+        window: proto::Window,
+        class: &WmClass,
+    ) -> anyhow::Result<()> {
+        self.send(&class.change(window)).await
+    }
+
+    pub async fn wm_hints(&mut self, window: proto::Window) -> anyhow::Result<Option<WmHints>> {
+        self.get_property(
+            window,
+            atom::Atom::WmHints.into(),
+            Some(atom::Atom::WmHints.into()),
+        )
+        .await?
+        .map(|value| WmHints::read(&value))
+        .transpose()
+    }
+
+    pub async fn set_wm_hints(
+        &mut self,
+        window: proto::Window,
+        hints: &WmHints,
+    ) -> anyhow::Result<()> {
+        self.send(&hints.change(window)).await
+    }
+
+    pub async fn wm_normal_hints(
+        &mut self,
+        window: proto::Window,
+    ) -> anyhow::Result<Option<WmSizeHints>> {
+        self.get_property(
+            window,
+            atom::Atom::WmNormalHints.into(),
+            Some(atom::Atom::WmSizeHints.into()),
+        )
+        .await?
+        .map(|value| WmSizeHints::read(&value))
+        .transpose()
+    }
+
+    pub async fn set_wm_normal_hints(
+        &mut self,
+        window: proto::Window,
+        hints: &WmSizeHints,
+    ) -> anyhow::Result<()> {
+        self.send(&hints.change(window)).await
+    }
+
+    pub async fn wm_transient_for(
+        &mut self,
+        window: proto::Window,
+    ) -> anyhow::Result<Option<proto::Window>> {
+        let value = self
+            .get_property(
+                window,
+                atom::Atom::WmTransientFor.into(),
+                Some(atom::Atom::Window.into()),
+            )
+            .await?;
+
+        match value {
+            Some(value) => Ok(value.windows()?.first().copied().filter(|w| w.0 != 0)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn set_wm_transient_for(
+        &mut self,
+        window: proto::Window,
+        owner: proto::Window,
+    ) -> anyhow::Result<()> {
+        self.send(&request::ChangeProperty::u32s(
+            window,
+            atom::Atom::WmTransientFor.into(),
+            atom::Atom::Window.into(),
+            &[owner.0],
+        ))
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::Request;
+
+    /// The property a ChangeProperty request would leave behind.
+    fn stored(request: request::ChangeProperty) -> anyhow::Result<reply::GetProperty> {
+        request.encode()?;
+
+        Ok(reply::GetProperty {
+            format: request.format,
+            type_: request.type_,
+            bytes_after: 0,
+            value_len: request.data_len,
+            value: request.data,
+        })
+    }
+
+    fn u32s(fields: &[u32]) -> reply::GetProperty {
+        reply::GetProperty {
+            format: 32,
+            type_: atom::Atom::WmHints.into(),
+            bytes_after: 0,
+            value_len: fields.len() as u32,
+            value: fields.iter().flat_map(|v| v.to_be_bytes()).collect(),
+        }
+    }
+
+    #[test]
+    fn wm_hints_round_trip() -> anyhow::Result<()> {
+        let hints = WmHints {
+            input: Some(false),
+            initial_state: Some(InitialState::Iconic),
+            icon_pixmap: Some(proto::Pixmap(0x400001)),
+            icon_window: None,
+            icon_position: Some((-20, 40)),
+            icon_mask: Some(proto::Pixmap(0x400002)),
+            window_group: Some(proto::Window(0x400003)),
+            urgent: true,
+        };
+
+        let window = proto::Window(1);
+        assert_eq!(WmHints::read(&stored(hints.change(window))?)?, hints);
+
+        let partial = WmHints {
+            input: Some(true),
+            ..WmHints::default()
+        };
+        assert_eq!(WmHints::read(&stored(partial.change(window))?)?, partial);
+
+        Ok(())
+    }
+
+    #[test]
+    fn wm_hints_truncated() -> anyhow::Result<()> {
+        // An X11R3 client: no window group, but the flag set anyway.
+        let flags = HintsFlags::INPUT | HintsFlags::WINDOW_GROUP;
+        let mut fields = [0; HINTS_LENGTH - 1];
+        fields[0] = flags.bits();
+        fields[1] = 1;
+
+        let hints = WmHints::read(&u32s(&fields))?;
+        assert_eq!(hints.input, Some(true));
+        assert_eq!(hints.window_group, None);
+
+        assert_eq!(WmHints::read(&u32s(&[]))?, WmHints::default());
+
+        Ok(())
+    }
+
+    #[test]
+    fn wm_size_hints_round_trip() -> anyhow::Result<()> {
+        let hints = WmSizeHints {
+            user_position: true,
+            program_size: true,
+            min_size: Some((100, 50)),
+            resize_inc: Some((8, 16)),
+            aspect: Some(Aspect {
+                min: (4, 3),
+                max: (16, 9),
+            }),
+            base_size: Some((4, 2)),
+            win_gravity: Some(proto::WinGravity::Static),
+            ..WmSizeHints::default()
+        };
+
+        let window = proto::Window(1);
+        assert_eq!(WmSizeHints::read(&stored(hints.change(window))?)?, hints);
+
+        let partial = WmSizeHints {
+            max_size: Some((800, 600)),
+            ..WmSizeHints::default()
+        };
+        assert_eq!(
+            WmSizeHints::read(&stored(partial.change(window))?)?,
+            partial
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn wm_size_hints_truncated() -> anyhow::Result<()> {
+        // A pre-X11R4 client: 15 fields, so no base size or gravity.
+        let flags = SizeHintsFlags::P_MIN_SIZE
+            | SizeHintsFlags::P_BASE_SIZE
+            | SizeHintsFlags::P_WIN_GRAVITY;
+        let mut fields = [0; 15];
+        fields[0] = flags.bits();
+        fields[5] = 100;
+        fields[6] = 50;
+
+        let hints = WmSizeHints::read(&u32s(&fields))?;
+        assert_eq!(hints.min_size, Some((100, 50)));
+        assert_eq!(hints.base_size, None);
+        assert_eq!(hints.win_gravity, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn compound_text_round_trip() {
+        for text in ["Grüße aus Köln", "日本語のテキスト", "Ελληνικά and ASCII"]
+        {
+            let (encoding, value) = encode_text(text);
+            assert_eq!(decode_text(encoding, &value), text);
+        }
+
+        assert_eq!(encode_text("Grüße").0, Encoding::String);
+        assert_eq!(encode_text("日本語").0, Encoding::CompoundText);
+    }
+
+    #[test]
+    fn compound_text_other_charsets() {
+        // Latin-1 in GR, then two characters of JIS X 0208 in GL.
+        let value = b"caf\xe9 \x1b$(B\x30\x21\x1b(B ok";
+
+        assert_eq!(
+            decode_text(Encoding::CompoundText, value),
+            "café \u{fffd}\u{fffd} ok"
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use crate::{atom, display, proto, request, setup};
+
+pub const NET_WM_ICON: &str = "_NET_WM_ICON";
+
+/// Image byte order and bitmap bit order `LSBFirst`.
+const LSB_FIRST: u8 = 0;
+
+/// An icon as non-premultiplied 8-bit RGBA, row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// Decodes `_NET_WM_ICON`: any number of images, each a width and height
+/// followed by width * height ARGB pixels. A truncated image at the end is
+/// left out, as are images without any pixels.
+pub fn decode_net_wm_icon(value: &[u32]) -> Vec<Image> {
+    let mut images = Vec::new();
+    let mut rest = value;
+
+    while let [width, height, pixels @ ..] = rest {
+        let size = match usize::try_from(u64::from(*width) * u64::from(*height)) {
+            Ok(size) if size <= pixels.len() => size,
+            _ => break,
+        };
+
+        rest = &pixels[size..];
+        if size == 0 {
+            continue;
+        }
+
+        let rgba = pixels[..size]
+            .iter()
+            .flat_map(|argb| {
+                let [a, r, g, b] = argb.to_be_bytes();
+                [r, g, b, a]
+            })
+            .collect();
+
+        images.push(Image {
+            width: *width,
+            height: *height,
+            rgba,
+        });
+    }
+
+    images
+}
+
+/// Picks the icon to show at `size` pixels: the smallest one at least that
+/// big, so it only has to be scaled down, or else the biggest one.
+pub fn best_size(images: &[Image], size: u32) -> Option<&Image> {
+    let side = |image: &Image| image.width.max(image.height);
+
+    images
+        .iter()
+        .filter(|image| side(image) >= size)
+        .min_by_key(|image| side(image))
+        .or_else(|| images.iter().max_by_key(|image| side(image)))
+}
+
+impl display::Display {
+    pub async fn net_wm_icon(&mut self, window: proto::Window) -> anyhow::Result<Vec<Image>> {
+        let value = self
+            .get_u32s(window, NET_WM_ICON, atom::Atom::Cardinal)
+            .await?;
+
+        Ok(value.map_or_else(Vec::new, |value| decode_net_wm_icon(&value)))
+    }
+
+    /// The icon of a window closest to `size`, from `_NET_WM_ICON` or else
+    /// from the icon pixmap and mask in WM_HINTS.
+    pub async fn window_icon(
+        &mut self,
+        window: proto::Window,
+        size: u32,
+    ) -> anyhow::Result<Option<Image>> {
+        let images = self.net_wm_icon(window).await?;
+        if let Some(image) = best_size(&images, size) {
+            return Ok(Some(image.clone()));
+        }
+
+        let hints = match self.wm_hints(window).await? {
+            Some(hints) => hints,
+            None => return Ok(None),
+        };
+
+        match hints.icon_pixmap {
+            Some(pixmap) => Ok(Some(self.pixmap_image(pixmap, hints.icon_mask).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads a pixmap with GetImage and converts it to RGBA. Bitmaps are
+    /// black on white; deeper pixmaps need a TrueColor or DirectColor
+    /// visual of their depth. The mask, a bitmap, sets which pixels are
+    /// opaque.
+    pub async fn pixmap_image(
+        &mut self,
+        pixmap: proto::Pixmap,
+        mask: Option<proto::Pixmap>,
+    ) -> anyhow::Result<Image> {
+        let geometry = self.get_geometry(pixmap).await?;
+        let (width, height) = (geometry.width, geometry.height);
+
+        let data = self
+            .send(&request::GetImage::new(pixmap.into(), 0, 0, width, height))
+            .await?
+            .data;
+        let pixels = decode_pixels(&self.setup, geometry.depth, width, height, &data)?;
+
+        let mask = match mask {
+            Some(mask) => Some((mask, self.get_geometry(mask).await?)),
+            None => None,
+        };
+
+        // A mask that doesn't fit the pixmap is ignored.
+        let alpha = match mask {
+            Some((mask, geometry))
+                if geometry.depth == 1 && geometry.width == width && geometry.height == height =>
+            {
+                let data = self
+                    .send(&request::GetImage::new(mask.into(), 0, 0, width, height))
+                    .await?
+                    .data;
+                Some(decode_pixels(&self.setup, 1, width, height, &data)?)
+            }
+            _ => None,
+        };
+
+        let mut rgba = Vec::with_capacity(pixels.len() * 4);
+        for (i, &[r, g, b]) in pixels.iter().enumerate() {
+            // Set bits of the mask come out black.
+            let a = match &alpha {
+                Some(alpha) if alpha[i] == [0; 3] => 255,
+                Some(_) => 0,
+                None => 255,
+            };
+
+            rgba.extend([r, g, b, a]);
+        }
+
+        Ok(Image {
+            width: width.into(),
+            height: height.into(),
+            rgba,
+        })
+    }
+}
+
+/// Converts a ZPixmap image to RGB. Set bits of a bitmap come out black.
+fn decode_pixels(
+    setup: &setup::Setup,
+    depth: u8,
+    width: u16,
+    height: u16,
+    data: &[u8],
+) -> anyhow::Result<Vec<[u8; 3]>> {
+    let format = setup
+        .pixmap_formats
+        .iter()
+        .find(|format| format.depth == depth)
+        .ok_or_else(|| anyhow::anyhow!("no pixmap format for depth {}", depth))?;
+
+    if width == 0 || height == 0 {
+        return Ok(Vec::new());
+    }
+
+    let bits_per_pixel = usize::from(format.bits_per_pixel);
+    let pad = usize::from(format.scanline_pad);
+    let stride = (usize::from(width) * bits_per_pixel).div_ceil(pad) * pad / 8;
+    anyhow::ensure!(
+        data.len() >= stride * usize::from(height),
+        "image is too short"
+    );
+
+    let rows = data.chunks_exact(stride).take(height.into());
+    let mut pixels = Vec::with_capacity(usize::from(width) * usize::from(height));
+
+    if depth == 1 {
+        anyhow::ensure!(
+            setup.bitmap_format_scanline_unit == 8
+                || setup.image_byte_order == setup.bitmap_format_bit_order,
+            "unsupported bitmap format"
+        );
+
+        for row in rows {
+            for x in 0..usize::from(width) {
+                let bit = match setup.bitmap_format_bit_order {
+                    LSB_FIRST => row[x / 8] >> (x % 8),
+                    _ => row[x / 8] >> (7 - x % 8),
+                };
+
+                pixels.push(match bit & 1 {
+                    0 => [0xff; 3],
+                    _ => [0; 3],
+                });
+            }
+        }
+
+        return Ok(pixels);
+    }
+
+    let visual = setup
+        .screens
+        .iter()
+        .flat_map(|screen| &screen.depths)
+        .filter(|d| d.depth == depth)
+        .flat_map(|d| &d.visual_types)
+        .find(|visual| {
+            matches!(
+                visual.class,
+                setup::VisualTypeClass::TrueColor | setup::VisualTypeClass::DirectColor
+            )
+        })
+        .ok_or_else(|| anyhow::anyhow!("no TrueColor visual for depth {}", depth))?;
+
+    let bytes_per_pixel = bits_per_pixel / 8;
+    anyhow::ensure!(
+        matches!(bytes_per_pixel, 2..=4),
+        "unsupported bits per pixel: {}",
+        bits_per_pixel
+    );
+
+    for row in rows {
+        for pixel in row.chunks_exact(bytes_per_pixel).take(width.into()) {
+            let pixel = match setup.image_byte_order {
+                LSB_FIRST => pixel.iter().rev().fold(0, |v, &b| v << 8 | u32::from(b)),
+                _ => pixel.iter().fold(0, |v, &b| v << 8 | u32::from(b)),
+            };
+
+            pixels.push([
+                channel(pixel, visual.red_mask),
+                channel(pixel, visual.green_mask),
+                channel(pixel, visual.blue_mask),
+            ]);
+        }
+    }
+
+    Ok(pixels)
+}
+
+/// Scales the bits of `pixel` under `mask` to eight bits.
+fn channel(pixel: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+
+    let value = u64::from((pixel & mask) >> mask.trailing_zeros());
+    let max = u64::from(mask >> mask.trailing_zeros());
+
+    (value * 255 / max) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(side: u32) -> Image {
+        Image {
+            width: side,
+            height: side,
+            rgba: vec![0; side as usize * side as usize * 4],
+        }
+    }
+
+    #[test]
+    fn pixels_are_converted_to_rgba() {
+        let images = decode_net_wm_icon(&[2, 1, 0xff112233, 0x80445566]);
+
+        assert_eq!(
+            images,
+            [Image {
+                width: 2,
+                height: 1,
+                rgba: vec![0x11, 0x22, 0x33, 0xff, 0x44, 0x55, 0x66, 0x80],
+            }]
+        );
+    }
+
+    #[test]
+    fn truncated_image_is_left_out() {
+        let mut value = vec![1, 1, 0xffffffff];
+        value.extend([16, 16]);
+        value.extend([0; 100]);
+
+        let images = decode_net_wm_icon(&value);
+        assert_eq!(images.len(), 1);
+        assert_eq!((images[0].width, images[0].height), (1, 1));
+
+        // A width without a height.
+        assert_eq!(decode_net_wm_icon(&[1, 1, 0, 8]).len(), 1);
+    }
+
+    #[test]
+    fn empty_images_are_skipped() {
+        let images = decode_net_wm_icon(&[0, 0, 0, 5, 7, 0, 1, 1, 0xffffffff]);
+
+        assert_eq!(images.len(), 1);
+        assert_eq!((images[0].width, images[0].height), (1, 1));
+    }
+
+    #[test]
+    fn best_size_prefers_scaling_down() {
+        let images = [image(64), image(16), image(128), image(32)];
+        let side = |size| best_size(&images, size).map(|image| image.width);
+
+        assert_eq!(side(16), Some(16));
+        assert_eq!(side(20), Some(32));
+        assert_eq!(side(48), Some(64));
+        assert_eq!(side(65), Some(128));
+        assert_eq!(side(256), Some(128));
+        assert_eq!(best_size(&[], 32), None);
+    }
+
+    #[test]
+    fn best_size_goes_by_the_longer_side() {
+        let wide = Image {
+            width: 48,
+            height: 16,
+            rgba: vec![0; 48 * 16 * 4],
+        };
+        let images = [image(32), wide];
+
+        assert_eq!(best_size(&images, 40).map(|image| image.width), Some(48));
+    }
+}
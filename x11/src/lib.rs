@@ -1,8 +1,21 @@
 pub mod atom;
 pub mod bytes;
+pub mod clipboard;
+pub mod dispatch;
 pub mod display;
 pub mod error;
 pub mod event;
+pub mod ewmh;
+#[cfg(test)]
+mod fake;
+pub mod icccm;
+pub mod icon;
+pub mod property;
 pub mod proto;
+pub mod protocol;
+pub mod protocols;
+pub mod reply;
 pub mod request;
 pub mod setup;
+pub mod stream;
+pub mod tree;
@@ -0,0 +1,155 @@
+use crate::{event, proto, reply};
+use std::collections::{HashMap, HashSet};
+
+const STATE_DELETED: u8 = 1;
+
+/// Property values fetched through `Display::property`. A value is dropped
+/// when a PropertyNotify says it changed and fetched again the next time
+/// it's asked for, so reading a property that hasn't changed never costs a
+/// round trip.
+#[derive(Debug, Default)]
+pub struct Cache {
+    /// `None` is a property known not to exist.
+    values: HashMap<(proto::Window, proto::Atom), Option<reply::GetProperty>>,
+    /// Windows PropertyChange has been selected on.
+    watched: HashSet<proto::Window>,
+}
+
+impl Cache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(
+        &self,
+        window: proto::Window,
+        atom: proto::Atom,
+    ) -> Option<&Option<reply::GetProperty>> {
+        self.values.get(&(window, atom))
+    }
+
+    pub fn insert(
+        &mut self,
+        window: proto::Window,
+        atom: proto::Atom,
+        value: Option<reply::GetProperty>,
+    ) {
+        self.values.insert((window, atom), value);
+    }
+
+    pub fn is_watched(&self, window: proto::Window) -> bool {
+        self.watched.contains(&window)
+    }
+
+    pub fn watch(&mut self, window: proto::Window) {
+        self.watched.insert(window);
+    }
+
+    /// Drops everything cached for a window.
+    pub fn forget(&mut self, window: proto::Window) {
+        self.watched.remove(&window);
+        self.values.retain(|(w, _), _| *w != window);
+    }
+
+    /// Drops every cached value, keeping the watched windows. Used when
+    /// events may have been missed.
+    pub fn invalidate(&mut self) {
+        self.values.clear();
+    }
+
+    /// Updates the cache from an event read from the connection.
+    pub fn handle(&mut self, event: &event::Event) {
+        match event {
+            event::Event::PropertyNotify(notify) => {
+                let key = (notify.window, notify.atom);
+
+                // A deletion is only worth remembering for properties that
+                // were cached, or on windows whose changes are seen anyway;
+                // anything else would grow the cache with every deletion on
+                // any window.
+                if notify.state == STATE_DELETED
+                    && (self.values.contains_key(&key) || self.is_watched(notify.window))
+                {
+                    self.values.insert(key, None);
+                } else {
+                    self.values.remove(&key);
+                }
+            }
+
+            event::Event::DestroyNotify(destroy) => self.forget(destroy.window),
+
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn property_notify(window: u32, atom: u32, state: u8) -> event::Event {
+        event::Event::PropertyNotify(event::PropertyNotify {
+            window: proto::Window(window),
+            atom: proto::Atom(atom),
+            time: proto::Timestamp(0),
+            state,
+        })
+    }
+
+    fn value() -> Option<reply::GetProperty> {
+        Some(reply::GetProperty {
+            format: 8,
+            type_: proto::Atom(31),
+            bytes_after: 0,
+            value_len: 5,
+            value: b"value".to_vec(),
+        })
+    }
+
+    #[test]
+    fn deletions_of_unknown_properties_are_ignored() {
+        let mut cache = Cache::new();
+        cache.handle(&property_notify(1, 39, STATE_DELETED));
+
+        assert!(cache.get(proto::Window(1), proto::Atom(39)).is_none());
+    }
+
+    #[test]
+    fn deletions_are_remembered_for_cached_and_watched() {
+        let mut cache = Cache::new();
+        cache.insert(proto::Window(1), proto::Atom(39), value());
+        cache.watch(proto::Window(2));
+
+        cache.handle(&property_notify(1, 39, STATE_DELETED));
+        cache.handle(&property_notify(2, 39, STATE_DELETED));
+
+        assert!(matches!(
+            cache.get(proto::Window(1), proto::Atom(39)),
+            Some(None)
+        ));
+        assert!(matches!(
+            cache.get(proto::Window(2), proto::Atom(39)),
+            Some(None)
+        ));
+    }
+
+    #[test]
+    fn destroy_notify_forgets_the_window() {
+        let mut cache = Cache::new();
+        cache.insert(proto::Window(1), proto::Atom(39), value());
+        cache.insert(proto::Window(2), proto::Atom(39), value());
+        cache.watch(proto::Window(1));
+
+        cache.handle(&event::Event::DestroyNotify(event::DestroyNotify {
+            event: proto::Window(1),
+            window: proto::Window(1),
+        }));
+
+        assert!(cache.get(proto::Window(1), proto::Atom(39)).is_none());
+        assert!(!cache.is_watched(proto::Window(1)));
+        assert!(matches!(
+            cache.get(proto::Window(2), proto::Atom(39)),
+            Some(Some(_))
+        ));
+    }
+}
@@ -1,115 +1,162 @@
+use std::fmt;
 use std::io;
 
-pub type Window = u32;
+macro_rules! resource {
+    ($($name:ident),* $(,)?) => {
+        $(
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+            pub struct $name(pub u32);
+
+            impl From<u32> for $name {
+                fn from(v: u32) -> Self {
+                    $name(v)
+                }
+            }
+
+            impl From<$name> for u32 {
+                fn from(v: $name) -> u32 {
+                    v.0
+                }
+            }
+
+            impl fmt::Display for $name {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    write!(f, "{:#x}", self.0)
+                }
+            }
+        )*
+    };
+}
+
+resource!(Window, Pixmap, Drawable, Atom, Colormap, Cursor, Font, Gcontext, Visualid, Timestamp);
+
+impl From<Window> for Drawable {
+    fn from(window: Window) -> Self {
+        Drawable(window.0)
+    }
+}
+
+impl From<Pixmap> for Drawable {
+    fn from(pixmap: Pixmap) -> Self {
+        Drawable(pixmap.0)
+    }
+}
+
+impl Timestamp {
+    pub const CURRENT_TIME: Timestamp = Timestamp(0);
+}
 
 pub const RESPONSE_ERROR: u8 = 0;
 pub const RESPONSE_REPLY: u8 = 1;
+/// The code of GenericEvent, which is followed by `length` more words.
+pub const GENERIC_EVENT: u8 = 35;
 
-enum EventMask {
-    KeyPress,
-    KeyRelease,
-    ButtonPress,
-    ButtonRelease,
-    EnterWindow,
-    LeaveWindow,
-    PointerMotion,
-    PointerMotionHint,
-    Button1Motion,
-    Button2Motion,
-    Button3Motion,
-    Button4Motion,
-    Button5Motion,
-    ButtonMotion,
-    KeymapState,
-    Xposure,
-    VisibilityChange,
-    StructureNotify,
-    ResizeRedirect,
-    SubstructureNotify,
-    SubstructureRedirect,
-    FocusChange,
-    PropertyChange,
-    ColormapChange,
-    OwnerGrabButton,
-}
-
-impl EventMask {
-    fn write<W: io::Write>(&self, w: &mut W) -> anyhow::Result<()> {
-        use byteorder::{BigEndian, WriteBytesExt};
+bitflags::bitflags! {
+    #[derive(Default)]
+    pub struct EventMask: u32 {
+        const KEY_PRESS = 0x00000001;
+        const KEY_RELEASE = 0x00000002;
+        const BUTTON_PRESS = 0x00000004;
+        const BUTTON_RELEASE = 0x00000008;
+        const ENTER_WINDOW = 0x00000010;
+        const LEAVE_WINDOW = 0x00000020;
+        const POINTER_MOTION = 0x00000040;
+        const POINTER_MOTION_HINT = 0x00000080;
+        const BUTTON1_MOTION = 0x00000100;
+        const BUTTON2_MOTION = 0x00000200;
+        const BUTTON3_MOTION = 0x00000400;
+        const BUTTON4_MOTION = 0x00000800;
+        const BUTTON5_MOTION = 0x00001000;
+        const BUTTON_MOTION = 0x00002000;
+        const KEYMAP_STATE = 0x00004000;
+        const EXPOSURE = 0x00008000;
+        const VISIBILITY_CHANGE = 0x00010000;
+        const STRUCTURE_NOTIFY = 0x00020000;
+        const RESIZE_REDIRECT = 0x00040000;
+        const SUBSTRUCTURE_NOTIFY = 0x00080000;
+        const SUBSTRUCTURE_REDIRECT = 0x00100000;
+        const FOCUS_CHANGE = 0x00200000;
+        const PROPERTY_CHANGE = 0x00400000;
+        const COLORMAP_CHANGE = 0x00800000;
+        const OWNER_GRAB_BUTTON = 0x01000000;
+    }
+}
 
-        let value = match self {
-            EventMask::KeyPress => 0x00000001,
-            EventMask::KeyRelease => 0x00000002,
-            EventMask::ButtonPress => 0x00000004,
-            EventMask::ButtonRelease => 0x00000008,
-            EventMask::EnterWindow => 0x00000010,
-            EventMask::LeaveWindow => 0x00000020,
-            EventMask::PointerMotion => 0x00000040,
-            EventMask::PointerMotionHint => 0x00000080,
-            EventMask::Button1Motion => 0x00000100,
-            EventMask::Button2Motion => 0x00000200,
-            EventMask::Button3Motion => 0x00000400,
-            EventMask::Button4Motion => 0x00000800,
-            EventMask::Button5Motion => 0x00001000,
-            EventMask::ButtonMotion => 0x00002000,
-            EventMask::KeymapState => 0x00004000,
-            EventMask::Xposure => 0x00008000,
-            EventMask::VisibilityChange => 0x00010000,
-            EventMask::StructureNotify => 0x00020000,
-            EventMask::ResizeRedirect => 0x00040000,
-            EventMask::SubstructureNotify => 0x00080000,
-            EventMask::SubstructureRedirect => 0x00100000,
-            EventMask::FocusChange => 0x00200000,
-            EventMask::PropertyChange => 0x00400000,
-            EventMask::ColormapChange => 0x00800000,
-            EventMask::OwnerGrabButton => 0x01000000,
-        };
+bitflags::bitflags! {
+    /// The events that can be kept from propagating to ancestors.
+    #[derive(Default)]
+    pub struct DeviceEventMask: u32 {
+        const KEY_PRESS = 0x00000001;
+        const KEY_RELEASE = 0x00000002;
+        const BUTTON_PRESS = 0x00000004;
+        const BUTTON_RELEASE = 0x00000008;
+        const POINTER_MOTION = 0x00000040;
+        const BUTTON1_MOTION = 0x00000100;
+        const BUTTON2_MOTION = 0x00000200;
+        const BUTTON3_MOTION = 0x00000400;
+        const BUTTON4_MOTION = 0x00000800;
+        const BUTTON5_MOTION = 0x00001000;
+        const BUTTON_MOTION = 0x00002000;
+    }
+}
 
-        w.write_u32::<BigEndian>(value).map_err(|err| err.into())
-    }
-}
-
-pub const EVENT_MASK_KEY_PRESS: u32 = 0x00000001;
-pub const EVENT_MASK_KEY_RELEASE: u32 = 0x00000002;
-pub const EVENT_MASK_BUTTON_PRESS: u32 = 0x00000004;
-pub const EVENT_MASK_BUTTON_RELEASE: u32 = 0x00000008;
-pub const EVENT_MASK_ENTER_WINDOW: u32 = 0x00000010;
-pub const EVENT_MASK_LEAVE_WINDOW: u32 = 0x00000020;
-pub const EVENT_MASK_POINTER_MOTION: u32 = 0x00000040;
-pub const EVENT_MASK_POINTER_MOTION_HINT: u32 = 0x00000080;
-pub const EVENT_MASK_BUTTON1_MOTION: u32 = 0x00000100;
-pub const EVENT_MASK_BUTTON2_MOTION: u32 = 0x00000200;
-pub const EVENT_MASK_BUTTON3_MOTION: u32 = 0x00000400;
-pub const EVENT_MASK_BUTTON4_MOTION: u32 = 0x00000800;
-pub const EVENT_MASK_BUTTON5_MOTION: u32 = 0x00001000;
-pub const EVENT_MASK_BUTTON_MOTION: u32 = 0x00002000;
-pub const EVENT_MASK_KEYMAP_STATE: u32 = 0x00004000;
-pub const EVENT_MASK_EXPOSURE: u32 = 0x00008000;
-pub const EVENT_MASK_VISIBILITY_CHANGE: u32 = 0x00010000;
-pub const EVENT_MASK_STRUCTURE_NOTIFY: u32 = 0x00020000;
-pub const EVENT_MASK_RESIZE_REDIRECT: u32 = 0x00040000;
-pub const EVENT_MASK_SUBSTRUCTURE_NOTIFY: u32 = 0x00080000;
-pub const EVENT_MASK_SUBSTRUCTURE_REDIRECT: u32 = 0x00100000;
-pub const EVENT_MASK_FOCUS_CHANGE: u32 = 0x00200000;
-pub const EVENT_MASK_PROPERTY_CHANGE: u32 = 0x00400000;
-pub const EVENT_MASK_COLORMAP_CHANGE: u32 = 0x00800000;
-pub const EVENT_MASK_OWNER_GRAB_BUTTON: u32 = 0x01000000;
-
-pub const VALUE_MASK_BACKGROUND_PIXMAP: u32 = 0x00000001;
-pub const VALUE_MASK_BACKGROUND_PIXEL: u32 = 0x00000002;
-pub const VALUE_MASK_BORDER_PIXMAP: u32 = 0x00000004;
-pub const VALUE_MASK_BORDER_PIXEL: u32 = 0x00000008;
-pub const VALUE_MASK_BIT_GRAVITY: u32 = 0x00000010;
-pub const VALUE_MASK_WIN_GRAVITY: u32 = 0x00000020;
-pub const VALUE_MASK_BACKING_STORE: u32 = 0x00000040;
-pub const VALUE_MASK_BACKING_PLANES: u32 = 0x00000080;
-pub const VALUE_MASK_BACKING_PIXEL: u32 = 0x00000100;
-pub const VALUE_MASK_OVERRIDE_REDIRECT: u32 = 0x00000200;
-pub const VALUE_MASK_SAVE_UNDER: u32 = 0x00000400;
-pub const VALUE_MASK_EVENT_MASK: u32 = 0x00000800;
-pub const VALUE_MASK_DO_NOT_PROPAGATE_MASK: u32 = 0x00001000;
-pub const VALUE_MASK_COLORMAP: u32 = 0x00002000;
-pub const VALUE_MASK_CURSOR: u32 = 0x00004000;
+bitflags::bitflags! {
+    /// The window attributes present in a value list.
+    #[derive(Default)]
+    pub struct ValueMask: u32 {
+        const BACKGROUND_PIXMAP = 0x00000001;
+        const BACKGROUND_PIXEL = 0x00000002;
+        const BORDER_PIXMAP = 0x00000004;
+        const BORDER_PIXEL = 0x00000008;
+        const BIT_GRAVITY = 0x00000010;
+        const WIN_GRAVITY = 0x00000020;
+        const BACKING_STORE = 0x00000040;
+        const BACKING_PLANES = 0x00000080;
+        const BACKING_PIXEL = 0x00000100;
+        const OVERRIDE_REDIRECT = 0x00000200;
+        const SAVE_UNDER = 0x00000400;
+        const EVENT_MASK = 0x00000800;
+        const DO_NOT_PROPAGATE_MASK = 0x00001000;
+        const COLORMAP = 0x00002000;
+        const CURSOR = 0x00004000;
+    }
+}
+
+bitflags::bitflags! {
+    /// The fields present in a ConfigureWindow request or ConfigureRequest
+    /// event.
+    #[derive(Default)]
+    pub struct ConfigureMask: u16 {
+        const X = 0x0001;
+        const Y = 0x0002;
+        const WIDTH = 0x0004;
+        const HEIGHT = 0x0008;
+        const BORDER_WIDTH = 0x0010;
+        const SIBLING = 0x0020;
+        const STACK_MODE = 0x0040;
+    }
+}
+
+bitflags::bitflags! {
+    /// Modifier keys and pointer buttons held down, as reported in the state
+    /// of input events.
+    #[derive(Default)]
+    pub struct KeyButMask: u16 {
+        const SHIFT = 0x0001;
+        const LOCK = 0x0002;
+        const CONTROL = 0x0004;
+        const MOD1 = 0x0008;
+        const MOD2 = 0x0010;
+        const MOD3 = 0x0020;
+        const MOD4 = 0x0040;
+        const MOD5 = 0x0080;
+        const BUTTON1 = 0x0100;
+        const BUTTON2 = 0x0200;
+        const BUTTON3 = 0x0400;
+        const BUTTON4 = 0x0800;
+        const BUTTON5 = 0x1000;
+    }
+}
 
 pub const OPCODE_CREATE_WINDOW: u8 = 1;
 pub const OPCODE_CHANGE_WINDOW_ATTRIBUTES: u8 = 2;
@@ -117,45 +164,121 @@ pub const OPCODE_GET_WINDOW_ATTRIBUTES: u8 = 3;
 pub const OPCODE_MAP_WINDOW: u8 = 8;
 pub const OPCODE_QUERY_TREE: u8 = 15;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rectangle {
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Rectangle {
+    pub fn contains(&self, other: &Rectangle) -> bool {
+        let (x, y) = (i32::from(self.x), i32::from(self.y));
+        let (ox, oy) = (i32::from(other.x), i32::from(other.y));
+
+        ox >= x
+            && oy >= y
+            && ox + i32::from(other.width) <= x + i32::from(self.width)
+            && oy + i32::from(other.height) <= y + i32::from(self.height)
+    }
+
+    pub fn union(&self, other: &Rectangle) -> Rectangle {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (i32::from(self.x) + i32::from(self.width))
+            .max(i32::from(other.x) + i32::from(other.width));
+        let bottom = (i32::from(self.y) + i32::from(self.height))
+            .max(i32::from(other.y) + i32::from(other.height));
+
+        Rectangle {
+            x,
+            y,
+            width: (right - i32::from(x)) as u16,
+            height: (bottom - i32::from(y)) as u16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Class {
     CopyFromParent,
     InputOutput,
     InputOnly,
 }
 
-impl Class {
-    pub fn write<W: io::Write>(&self, w: &mut W) -> anyhow::Result<()> {
-        use byteorder::{BigEndian, WriteBytesExt};
+impl TryFrom<u16> for Class {
+    type Error = anyhow::Error;
 
-        match self {
-            Class::CopyFromParent => w.write_u16::<BigEndian>(0),
-            Class::InputOutput => w.write_u16::<BigEndian>(1),
-            Class::InputOnly => w.write_u16::<BigEndian>(2),
+    fn try_from(v: u16) -> anyhow::Result<Self> {
+        match v {
+            0 => Ok(Class::CopyFromParent),
+            1 => Ok(Class::InputOutput),
+            2 => Ok(Class::InputOnly),
+
+            _ => anyhow::bail!("bad Class"),
+        }
+    }
+}
+
+impl From<Class> for u16 {
+    fn from(v: Class) -> u16 {
+        match v {
+            Class::CopyFromParent => 0,
+            Class::InputOutput => 1,
+            Class::InputOnly => 2,
         }
-        .map_err(|err| err.into())
     }
 }
 
 #[derive(Debug)]
 pub enum Visual {
-    Id(u32),
+    Id(Visualid),
     CopyFromParent,
 }
 
-impl Visual {
-    pub fn write<W: io::Write>(&self, w: &mut W) -> anyhow::Result<()> {
-        use byteorder::{BigEndian, WriteBytesExt};
+impl From<Visual> for Visualid {
+    fn from(v: Visual) -> Visualid {
+        match v {
+            Visual::Id(id) => id,
+            Visual::CopyFromParent => Visualid(0),
+        }
+    }
+}
 
-        match self {
-            Visual::Id(id) => w.write_u32::<BigEndian>(*id),
-            Visual::CopyFromParent => w.write_u32::<BigEndian>(0),
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveSetMode {
+    Insert,
+    Delete,
+}
+
+impl From<SaveSetMode> for u8 {
+    fn from(v: SaveSetMode) -> u8 {
+        match v {
+            SaveSetMode::Insert => 0,
+            SaveSetMode::Delete => 1,
         }
-        .map_err(|err| err.into())
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropertyMode {
+    Replace,
+    Prepend,
+    Append,
+}
+
+impl From<PropertyMode> for u8 {
+    fn from(v: PropertyMode) -> u8 {
+        match v {
+            PropertyMode::Replace => 0,
+            PropertyMode::Prepend => 1,
+            PropertyMode::Append => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StackMode {
     Above,
     Below,
@@ -164,20 +287,130 @@ pub enum StackMode {
     Opposite,
 }
 
-#[derive(Debug)]
+impl TryFrom<u8> for StackMode {
+    type Error = anyhow::Error;
+
+    fn try_from(v: u8) -> anyhow::Result<Self> {
+        match v {
+            0 => Ok(StackMode::Above),
+            1 => Ok(StackMode::Below),
+            2 => Ok(StackMode::TopIf),
+            3 => Ok(StackMode::BottomIf),
+            4 => Ok(StackMode::Opposite),
+
+            _ => anyhow::bail!("bad StackMode"),
+        }
+    }
+}
+
+impl From<StackMode> for u8 {
+    fn from(v: StackMode) -> u8 {
+        match v {
+            StackMode::Above => 0,
+            StackMode::Below => 1,
+            StackMode::TopIf => 2,
+            StackMode::BottomIf => 3,
+            StackMode::Opposite => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CirculateDirection {
+    RaiseLowest,
+    LowerHighest,
+}
+
+impl From<CirculateDirection> for u8 {
+    fn from(v: CirculateDirection) -> u8 {
+        match v {
+            CirculateDirection::RaiseLowest => 0,
+            CirculateDirection::LowerHighest => 1,
+        }
+    }
+}
+
+/// The destination of SendEvent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Destination {
+    PointerWindow,
+    InputFocus,
+    Window(Window),
+}
+
+impl From<Destination> for u32 {
+    fn from(v: Destination) -> u32 {
+        match v {
+            Destination::PointerWindow => 0,
+            Destination::InputFocus => 1,
+            Destination::Window(window) => window.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Focus {
+    None,
+    PointerRoot,
+    Window(Window),
+}
+
+impl From<Focus> for u32 {
+    fn from(v: Focus) -> u32 {
+        match v {
+            Focus::None => 0,
+            Focus::PointerRoot => 1,
+            Focus::Window(window) => window.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevertTo {
+    None,
+    PointerRoot,
+    Parent,
+}
+
+impl From<RevertTo> for u8 {
+    fn from(v: RevertTo) -> u8 {
+        match v {
+            RevertTo::None => 0,
+            RevertTo::PointerRoot => 1,
+            RevertTo::Parent => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    XYPixmap,
+    ZPixmap,
+}
+
+impl From<ImageFormat> for u8 {
+    fn from(v: ImageFormat) -> u8 {
+        match v {
+            ImageFormat::XYPixmap => 1,
+            ImageFormat::ZPixmap => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BackgroundPixmap {
-    Pixmap(u32),
+    Pixmap(Pixmap),
     None,
     ParentRelative,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BorderPixmap {
-    Pixmap(u32),
+    Pixmap(Pixmap),
     CopyFromParent,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BitGravity {
     Forget = 0,
     NorthWest,
@@ -214,9 +447,9 @@ impl TryFrom<u8> for BitGravity {
     }
 }
 
-impl Into<u8> for BitGravity {
-    fn into(self) -> u8 {
-        match self {
+impl From<BitGravity> for u8 {
+    fn from(v: BitGravity) -> u8 {
+        match v {
             BitGravity::Forget => 0,
             BitGravity::NorthWest => 1,
             BitGravity::North => 2,
@@ -232,7 +465,7 @@ impl Into<u8> for BitGravity {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WinGravity {
     Unmap,
     Static,
@@ -269,9 +502,9 @@ impl TryFrom<u8> for WinGravity {
     }
 }
 
-impl Into<u8> for WinGravity {
-    fn into(self) -> u8 {
-        match self {
+impl From<WinGravity> for u8 {
+    fn from(v: WinGravity) -> u8 {
+        match v {
             WinGravity::Unmap => 0,
             WinGravity::NorthWest => 1,
             WinGravity::North => 2,
@@ -287,20 +520,65 @@ impl Into<u8> for WinGravity {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BackingStore {
     NotUseful,
     WhenMapped,
     Always,
 }
 
-#[derive(Debug)]
-pub enum Colormap {
-    Colormap(u32),
+impl TryFrom<u8> for BackingStore {
+    type Error = anyhow::Error;
+
+    fn try_from(v: u8) -> anyhow::Result<Self> {
+        match v {
+            0 => Ok(BackingStore::NotUseful),
+            1 => Ok(BackingStore::WhenMapped),
+            2 => Ok(BackingStore::Always),
+
+            _ => anyhow::bail!("bad BackingStore"),
+        }
+    }
+}
+
+impl From<BackingStore> for u8 {
+    fn from(v: BackingStore) -> u8 {
+        match v {
+            BackingStore::NotUseful => 0,
+            BackingStore::WhenMapped => 1,
+            BackingStore::Always => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapState {
+    Unmapped,
+    Unviewable,
+    Viewable,
+}
+
+impl TryFrom<u8> for MapState {
+    type Error = anyhow::Error;
+
+    fn try_from(v: u8) -> anyhow::Result<Self> {
+        match v {
+            0 => Ok(MapState::Unmapped),
+            1 => Ok(MapState::Unviewable),
+            2 => Ok(MapState::Viewable),
+
+            _ => anyhow::bail!("bad MapState"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowColormap {
+    Colormap(Colormap),
     CopyFromParent,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WindowAttribute {
     BackgroundPixmap(BackgroundPixmap),
     BackgroundPixel(u32),
@@ -312,9 +590,198 @@ pub enum WindowAttribute {
     BackingPlanes(u32),
     BackingPixel(u32),
     SaveUnder(bool),
-    // EventMask  SETofEVENT,
-    // DoNotPropagateMask   SETofDEVICEEVENT,
+    EventMask(EventMask),
+    DoNotPropagateMask(DeviceEventMask),
     OverrideRedirect(bool),
-    Colormap(Colormap),
-    Cursor(Option<u32>),
+    Colormap(WindowColormap),
+    Cursor(Option<Cursor>),
+}
+
+impl WindowAttribute {
+    pub fn mask(&self) -> ValueMask {
+        match self {
+            WindowAttribute::BackgroundPixmap(_) => ValueMask::BACKGROUND_PIXMAP,
+            WindowAttribute::BackgroundPixel(_) => ValueMask::BACKGROUND_PIXEL,
+            WindowAttribute::BorderPixmap(_) => ValueMask::BORDER_PIXMAP,
+            WindowAttribute::BorderPixel(_) => ValueMask::BORDER_PIXEL,
+            WindowAttribute::BitGravity(_) => ValueMask::BIT_GRAVITY,
+            WindowAttribute::WinGravity(_) => ValueMask::WIN_GRAVITY,
+            WindowAttribute::BackingStore(_) => ValueMask::BACKING_STORE,
+            WindowAttribute::BackingPlanes(_) => ValueMask::BACKING_PLANES,
+            WindowAttribute::BackingPixel(_) => ValueMask::BACKING_PIXEL,
+            WindowAttribute::SaveUnder(_) => ValueMask::SAVE_UNDER,
+            WindowAttribute::EventMask(_) => ValueMask::EVENT_MASK,
+            WindowAttribute::DoNotPropagateMask(_) => ValueMask::DO_NOT_PROPAGATE_MASK,
+            WindowAttribute::OverrideRedirect(_) => ValueMask::OVERRIDE_REDIRECT,
+            WindowAttribute::Colormap(_) => ValueMask::COLORMAP,
+            WindowAttribute::Cursor(_) => ValueMask::CURSOR,
+        }
+    }
+
+    pub fn value(&self) -> u32 {
+        match *self {
+            WindowAttribute::BackgroundPixmap(BackgroundPixmap::Pixmap(pixmap)) => pixmap.0,
+            WindowAttribute::BackgroundPixmap(BackgroundPixmap::None) => 0,
+            WindowAttribute::BackgroundPixmap(BackgroundPixmap::ParentRelative) => 1,
+            WindowAttribute::BackgroundPixel(pixel) => pixel,
+            WindowAttribute::BorderPixmap(BorderPixmap::Pixmap(pixmap)) => pixmap.0,
+            WindowAttribute::BorderPixmap(BorderPixmap::CopyFromParent) => 0,
+            WindowAttribute::BorderPixel(pixel) => pixel,
+            WindowAttribute::BitGravity(gravity) => u8::from(gravity).into(),
+            WindowAttribute::WinGravity(gravity) => u8::from(gravity).into(),
+            WindowAttribute::BackingStore(backing_store) => u8::from(backing_store).into(),
+            WindowAttribute::BackingPlanes(planes) => planes,
+            WindowAttribute::BackingPixel(pixel) => pixel,
+            WindowAttribute::SaveUnder(save_under) => save_under.into(),
+            WindowAttribute::EventMask(mask) => mask.bits(),
+            WindowAttribute::DoNotPropagateMask(mask) => mask.bits(),
+            WindowAttribute::OverrideRedirect(override_redirect) => override_redirect.into(),
+            WindowAttribute::Colormap(WindowColormap::Colormap(colormap)) => colormap.0,
+            WindowAttribute::Colormap(WindowColormap::CopyFromParent) => 0,
+            WindowAttribute::Cursor(Some(cursor)) => cursor.0,
+            WindowAttribute::Cursor(None) => 0,
+        }
+    }
+}
+
+/// A window attribute value list, shared by CreateWindow and
+/// ChangeWindowAttributes. Setting an attribute twice keeps the last value,
+/// and values are always written in value mask order as the server
+/// requires.
+#[derive(Debug, Default, Clone)]
+pub struct WindowAttributes {
+    attributes: Vec<WindowAttribute>,
+}
+
+impl WindowAttributes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, attribute: WindowAttribute) -> &mut Self {
+        let mask = attribute.mask();
+
+        match self
+            .attributes
+            .binary_search_by_key(&mask.bits(), |a| a.mask().bits())
+        {
+            Ok(i) => self.attributes[i] = attribute,
+            Err(i) => self.attributes.insert(i, attribute),
+        }
+
+        self
+    }
+
+    pub fn mask(&self) -> ValueMask {
+        self.attributes
+            .iter()
+            .fold(ValueMask::empty(), |mask, a| mask | a.mask())
+    }
+
+    pub fn len(&self) -> usize {
+        self.attributes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.attributes.is_empty()
+    }
+
+    /// The attributes in value mask order.
+    pub fn iter(&self) -> impl Iterator<Item = &WindowAttribute> {
+        self.attributes.iter()
+    }
+
+    /// Writes the value mask followed by the values.
+    pub fn write<W: io::Write>(&self, w: &mut W) -> anyhow::Result<()> {
+        use byteorder::{BigEndian, WriteBytesExt};
+
+        w.write_u32::<BigEndian>(self.mask().bits())?;
+
+        for attribute in &self.attributes {
+            w.write_u32::<BigEndian>(attribute.value())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rectangles_contain_and_unite() {
+        let outer = Rectangle {
+            x: -10,
+            y: -10,
+            width: 100,
+            height: 50,
+        };
+        let inner = Rectangle {
+            x: 0,
+            y: 0,
+            width: 90,
+            height: 40,
+        };
+        let apart = Rectangle {
+            x: 200,
+            y: 100,
+            width: 10,
+            height: 10,
+        };
+
+        assert!(outer.contains(&inner));
+        assert!(outer.contains(&outer));
+        assert!(!inner.contains(&outer));
+        assert!(!outer.contains(&apart));
+
+        // Sticking out by a pixel on the right.
+        let wider = Rectangle { width: 91, ..inner };
+        assert!(!outer.contains(&wider));
+
+        assert_eq!(outer.union(&inner), outer);
+        assert_eq!(
+            outer.union(&apart),
+            Rectangle {
+                x: -10,
+                y: -10,
+                width: 220,
+                height: 120,
+            }
+        );
+    }
+
+    #[test]
+    fn window_attributes_are_written_in_mask_order() -> anyhow::Result<()> {
+        let mut attributes = WindowAttributes::new();
+        attributes
+            .set(WindowAttribute::Cursor(Some(Cursor(0x600001))))
+            .set(WindowAttribute::EventMask(EventMask::PROPERTY_CHANGE))
+            .set(WindowAttribute::BackgroundPixel(0x112233))
+            .set(WindowAttribute::OverrideRedirect(true))
+            .set(WindowAttribute::BackgroundPixel(0x445566));
+
+        assert_eq!(attributes.len(), 4);
+
+        let mut buf = Vec::new();
+        attributes.write(&mut buf)?;
+
+        let words = buf
+            .chunks_exact(4)
+            .map(|v| u32::from_be_bytes([v[0], v[1], v[2], v[3]]))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            words,
+            [
+                0x4a02,
+                0x445566,
+                1,
+                EventMask::PROPERTY_CHANGE.bits(),
+                0x600001
+            ]
+        );
+
+        Ok(())
+    }
 }
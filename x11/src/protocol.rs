@@ -0,0 +1,258 @@
+//! Requests, replies, events, errors, enums and masks generated from the
+//! xcb-proto descriptions in `xml/`, one module per extension and `xproto`
+//! for the core protocol.
+//!
+//! Core requests implement `request::Request` and are sent with
+//! `Display::send`; extension requests implement `request::ExtensionRequest`
+//! and are sent with `Display::send_extension`. Events and errors are
+//! decoded from the raw bytes of `event::Event::Unknown`,
+//! `event::Event::Generic` and `error::Error::Unknown` with their `decode`.
+//!
+//! Items the generator can't handle are listed in `SKIPPED`. These are:
+//!
+//! - requests passing file descriptors, in DRI3, MIT-SHM and RandR leases;
+//! - `<switch>` with `<case>`, with an alignment, or with lists in its
+//!   bitcases, which leaves out the XInput device controls, feedback and
+//!   device properties, the XI2 device classes of XIQueryDevice and
+//!   DeviceChanged, and the XKB map and names requests;
+//! - event structs, used by XInput's SendExtensionEvent;
+//! - the GLX error without a number, MIT-SHM's copy of the core Value
+//!   error, and anything built on the items above.
+
+/// Reads the big-endian fields of a message.
+pub struct Reader<'a> {
+    buf: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, offset: 0 }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.offset
+    }
+
+    pub fn bytes(&mut self, n: usize) -> anyhow::Result<&'a [u8]> {
+        anyhow::ensure!(n <= self.remaining(), "message is too short");
+
+        let bytes = &self.buf[self.offset..self.offset + n];
+        self.offset += n;
+        Ok(bytes)
+    }
+
+    pub fn skip(&mut self, n: usize) -> anyhow::Result<()> {
+        self.bytes(n).map(|_| ())
+    }
+
+    /// Skips to the next multiple of `n` bytes from the start.
+    pub fn align(&mut self, n: usize) -> anyhow::Result<()> {
+        self.skip(self.offset.next_multiple_of(n) - self.offset)
+    }
+
+    fn array<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    pub fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn i8(&mut self) -> anyhow::Result<i8> {
+        Ok(self.u8()? as i8)
+    }
+
+    pub fn bool(&mut self) -> anyhow::Result<bool> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    pub fn i16(&mut self) -> anyhow::Result<i16> {
+        Ok(i16::from_be_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    pub fn i32(&mut self) -> anyhow::Result<i32> {
+        Ok(i32::from_be_bytes(self.array()?))
+    }
+
+    pub fn u64(&mut self) -> anyhow::Result<u64> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+
+    pub fn i64(&mut self) -> anyhow::Result<i64> {
+        Ok(i64::from_be_bytes(self.array()?))
+    }
+
+    pub fn f32(&mut self) -> anyhow::Result<f32> {
+        Ok(f32::from_be_bytes(self.array()?))
+    }
+
+    pub fn f64(&mut self) -> anyhow::Result<f64> {
+        Ok(f64::from_be_bytes(self.array()?))
+    }
+}
+
+/// Pads `w` with zeros to a multiple of `n` bytes.
+pub fn align(w: &mut Vec<u8>, n: usize) {
+    w.resize(w.len().next_multiple_of(n), 0);
+}
+
+include!(concat!(env!("OUT_DIR"), "/protocol.rs"));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::{ExtensionRequest, Request};
+    use crate::{proto, reply::Reply};
+
+    #[test]
+    fn query_extension_is_padded() -> anyhow::Result<()> {
+        let request = xproto::QueryExtension {
+            name: b"RANDR".to_vec(),
+        };
+
+        assert_eq!(
+            request.encode()?,
+            [98, 0, 0, 4, 0, 5, 0, 0, b'R', b'A', b'N', b'D', b'R', 0, 0, 0]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn value_list_follows_its_mask() -> anyhow::Result<()> {
+        let request = xproto::ConfigureWindow {
+            window: proto::Window(0x200001),
+            value_list: xproto::ConfigureWindowValueList {
+                width: Some(640),
+                x: Some(-1),
+                stack_mode: Some(xproto::StackMode::ABOVE),
+                ..Default::default()
+            },
+        };
+
+        let mut expected = vec![12, 0, 0, 6, 0, 0x20, 0, 1, 0, 0x45, 0, 0];
+        expected.extend([0xff; 4]);
+        expected.extend(640u32.to_be_bytes());
+        expected.extend(0u32.to_be_bytes());
+        assert_eq!(request.encode()?, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn extension_request_takes_the_major_opcode() -> anyhow::Result<()> {
+        assert_eq!(bigreq::Enable {}.encode(133)?, [133, 0, 0, 1]);
+        Ok(())
+    }
+
+    #[test]
+    fn reply_is_read_after_its_code() -> anyhow::Result<()> {
+        let mut reply = vec![0, 0, 1, 0, 0, 0, 0, 1, 130, 64, 87];
+        reply.resize(31, 0);
+
+        let reply = xproto::QueryExtensionReply::read(&mut reply.as_slice())?;
+        assert_eq!(
+            reply,
+            xproto::QueryExtensionReply {
+                present: true,
+                major_opcode: 130,
+                first_event: 64,
+                first_error: 87,
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn generic_event_is_decoded() -> anyhow::Result<()> {
+        let mut event = vec![35, 131, 0, 1, 0, 0, 0, 9, 0, 15];
+        event.extend(2u16.to_be_bytes()); // deviceid
+        event.extend(1000u32.to_be_bytes()); // time
+        event.extend(1u32.to_be_bytes()); // detail
+        event.extend(3u16.to_be_bytes()); // sourceid
+        event.extend(1u16.to_be_bytes()); // valuators_len
+        event.extend([0; 8]); // flags and pad
+        event.extend(0b101u32.to_be_bytes()); // valuator_mask
+        for v in [1i32, -2, 10, -20] {
+            event.extend(v.to_be_bytes());
+            event.extend(0u32.to_be_bytes());
+        }
+        assert_eq!(event.len(), 32 + 9 * 4);
+
+        let event = xinput::RawButtonPressEvent::decode(&event)?;
+        assert_eq!(event.deviceid, 2);
+        assert_eq!(event.detail, 1);
+        assert_eq!(event.valuator_mask, [0b101]);
+        assert_eq!(
+            event
+                .axisvalues_raw
+                .iter()
+                .map(|v| v.integral)
+                .collect::<Vec<_>>(),
+            [10, -20]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn skipped_items_are_documented() {
+        let documented = [
+            "<fd>",
+            "file descriptors",
+            "switch with",
+            "switch on an expression",
+            "bitcase with",
+            "<paramref> expressions",
+            "event structs",
+            "number -1",
+            "copy of an unknown error",
+            "it's read as event::Generic",
+            "unknown type",
+        ];
+
+        for (module, name, reason) in SKIPPED {
+            assert!(
+                documented.iter().any(|d| reason.starts_with(d)),
+                "{}::{} isn't generated: {}",
+                module,
+                name,
+                reason
+            );
+        }
+    }
+
+    #[test]
+    fn event_round_trip() -> anyhow::Result<()> {
+        let event = xproto::ConfigureNotifyEvent {
+            event: proto::Window(1),
+            window: proto::Window(2),
+            above_sibling: proto::Window(0),
+            x: -10,
+            y: 20,
+            width: 300,
+            height: 200,
+            border_width: 1,
+            override_redirect: true,
+        };
+
+        let bytes = event.encode(xproto::ConfigureNotifyEvent::NUMBER)?;
+        assert_eq!(bytes.len(), 32);
+        assert_eq!(bytes[0], 22);
+        assert_eq!(xproto::ConfigureNotifyEvent::decode(&bytes)?, event);
+
+        Ok(())
+    }
+}
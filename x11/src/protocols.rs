@@ -0,0 +1,336 @@
+use crate::{display, event, proto, request};
+
+pub const WM_PROTOCOLS: &str = "WM_PROTOCOLS";
+pub const WM_DELETE_WINDOW: &str = "WM_DELETE_WINDOW";
+pub const WM_TAKE_FOCUS: &str = "WM_TAKE_FOCUS";
+pub const NET_WM_PING: &str = "_NET_WM_PING";
+
+/// A WM_PROTOCOLS message received by a client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    /// The window manager asks the client to close the window.
+    DeleteWindow(proto::Timestamp),
+    /// The window manager asks the client to set the focus, using this
+    /// timestamp.
+    TakeFocus(proto::Timestamp),
+    /// The window manager checks that the client is responding. It has
+    /// already been answered by `Display::handle_protocol`.
+    Ping(proto::Timestamp),
+    Other(proto::Atom, proto::Timestamp),
+}
+
+/// How `Display::close_window` closed a window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Close {
+    /// The client was sent WM_DELETE_WINDOW and is expected to close the
+    /// window itself.
+    Requested,
+    /// The client doesn't take part in WM_DELETE_WINDOW, so its connection
+    /// was closed with KillClient.
+    Killed,
+}
+
+impl display::Display {
+    /// The protocols a client takes part in, from its WM_PROTOCOLS.
+    pub async fn wm_protocols(
+        &mut self,
+        window: proto::Window,
+    ) -> anyhow::Result<Vec<proto::Atom>> {
+        let property = self.intern_atom(WM_PROTOCOLS).await?;
+
+        match self.get_property(window, property, None).await? {
+            Some(value) => value.atoms(),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub async fn set_wm_protocols(
+        &mut self,
+        window: proto::Window,
+        protocols: &[proto::Atom],
+    ) -> anyhow::Result<()> {
+        let property = self.intern_atom(WM_PROTOCOLS).await?;
+
+        self.send(&request::ChangeProperty::atoms(window, property, protocols))
+            .await
+    }
+
+    /// Whether a client lists the protocol called `name` in WM_PROTOCOLS.
+    pub async fn supports_protocol(
+        &mut self,
+        window: proto::Window,
+        name: &str,
+    ) -> anyhow::Result<bool> {
+        let protocol = self.intern_atom(name).await?;
+        Ok(self.wm_protocols(window).await?.contains(&protocol))
+    }
+
+    /// Sends a WM_PROTOCOLS message to the client that owns `window`.
+    pub async fn send_protocol(
+        &mut self,
+        window: proto::Window,
+        protocol: proto::Atom,
+        time: proto::Timestamp,
+        extra: [u32; 3],
+    ) -> anyhow::Result<()> {
+        let message_type = self.intern_atom(WM_PROTOCOLS).await?;
+        let message = event::ClientMessage::new(
+            window,
+            message_type,
+            [protocol.0, time.0, extra[0], extra[1], extra[2]],
+        );
+
+        self.send(&request::SendEvent::new(
+            proto::Destination::Window(window),
+            message.encode(),
+        ))
+        .await
+    }
+
+    /// Closes a window the way ICCCM asks a window manager to: with
+    /// WM_DELETE_WINDOW if the client takes part in it, and by killing the
+    /// client otherwise.
+    pub async fn close_window(&mut self, window: proto::Window) -> anyhow::Result<Close> {
+        if self.supports_protocol(window, WM_DELETE_WINDOW).await? {
+            let protocol = self.intern_atom(WM_DELETE_WINDOW).await?;
+            self.send_protocol(window, protocol, proto::Timestamp::CURRENT_TIME, [0; 3])
+                .await?;
+
+            return Ok(Close::Requested);
+        }
+
+        self.kill_client(window).await?;
+
+        Ok(Close::Killed)
+    }
+
+    /// Closes the connection of the client that created `window`.
+    pub async fn kill_client(&mut self, window: proto::Window) -> anyhow::Result<()> {
+        self.send(&request::KillClient::new(window.0)).await
+    }
+
+    pub async fn set_input_focus(
+        &mut self,
+        focus: proto::Focus,
+        revert_to: proto::RevertTo,
+        time: proto::Timestamp,
+    ) -> anyhow::Result<()> {
+        self.send(&request::SetInputFocus::new(focus, revert_to, time))
+            .await
+    }
+
+    /// Gives a window the focus according to its input model. Clients whose
+    /// WM_HINTS input field isn't false get the focus set directly; clients
+    /// taking part in WM_TAKE_FOCUS are asked to take it. `time` has to be
+    /// the timestamp of the event that caused the focus change, not
+    /// CurrentTime, so that clients can order focus changes.
+    pub async fn focus_window(
+        &mut self,
+        window: proto::Window,
+        time: proto::Timestamp,
+    ) -> anyhow::Result<()> {
+        let input = self
+            .wm_hints(window)
+            .await?
+            .and_then(|hints| hints.input)
+            .unwrap_or(true);
+
+        if input {
+            self.set_input_focus(proto::Focus::Window(window), proto::RevertTo::Parent, time)
+                .await?;
+        }
+
+        if self.supports_protocol(window, WM_TAKE_FOCUS).await? {
+            let protocol = self.intern_atom(WM_TAKE_FOCUS).await?;
+            self.send_protocol(window, protocol, time, [0; 3]).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends `_NET_WM_PING` to a client. Its answer is a ClientMessage to
+    /// the root window recognized by `is_pong`.
+    pub async fn ping(
+        &mut self,
+        window: proto::Window,
+        time: proto::Timestamp,
+    ) -> anyhow::Result<()> {
+        let protocol = self.intern_atom(NET_WM_PING).await?;
+        self.send_protocol(window, protocol, time, [window.0, 0, 0])
+            .await
+    }
+
+    /// Whether a ClientMessage is a client's answer to `ping` for `window`.
+    pub async fn is_pong(
+        &mut self,
+        message: &event::ClientMessage,
+        window: proto::Window,
+    ) -> anyhow::Result<bool> {
+        let message_type = self.intern_atom(WM_PROTOCOLS).await?;
+        let protocol = self.intern_atom(NET_WM_PING).await?;
+        let data = message.longs();
+
+        Ok(Some(message.window) == self.root()
+            && message.message_type == message_type
+            && data[0] == protocol.0
+            && data[2] == window.0)
+    }
+
+    /// Decodes a WM_PROTOCOLS message received by a client. `_NET_WM_PING`
+    /// is answered right away by sending the message back to the root
+    /// window. Returns `None` for other client messages.
+    pub async fn handle_protocol(
+        &mut self,
+        message: &event::ClientMessage,
+    ) -> anyhow::Result<Option<Protocol>> {
+        let message_type = self.intern_atom(WM_PROTOCOLS).await?;
+        if message.message_type != message_type || message.format != 32 {
+            return Ok(None);
+        }
+
+        let atoms = self
+            .intern_atoms(&[WM_DELETE_WINDOW, WM_TAKE_FOCUS, NET_WM_PING])
+            .await?;
+        let (delete_window, take_focus, ping) = (atoms[0], atoms[1], atoms[2]);
+
+        let data = message.longs();
+        let protocol = proto::Atom(data[0]);
+        let time = proto::Timestamp(data[1]);
+
+        Ok(Some(match protocol {
+            p if p == delete_window => Protocol::DeleteWindow(time),
+            p if p == take_focus => Protocol::TakeFocus(time),
+
+            p if p == ping => {
+                let root = self.root().ok_or_else(|| anyhow::anyhow!("no screen"))?;
+
+                let mut pong = message.clone();
+                pong.window = root;
+
+                self.send(
+                    request::SendEvent::new(proto::Destination::Window(root), pong.encode())
+                        .event_mask(
+                            proto::EventMask::SUBSTRUCTURE_NOTIFY
+                                | proto::EventMask::SUBSTRUCTURE_REDIRECT,
+                        ),
+                )
+                .await?;
+
+                Protocol::Ping(time)
+            }
+
+            _ => Protocol::Other(protocol, time),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::Request;
+    use crate::{atom, fake};
+
+    const WINDOW: proto::Window = proto::Window(0x40_0001);
+
+    #[tokio::test]
+    async fn pings_are_answered_through_the_root() -> anyhow::Result<()> {
+        let (mut display, mut server) = fake::connect()?;
+        let wm_protocols = server.atom(WM_PROTOCOLS);
+        let ping = server.atom(NET_WM_PING);
+
+        let message =
+            event::ClientMessage::new(WINDOW, wm_protocols, [ping.0, 123, WINDOW.0, 0, 0]);
+        let (protocol, pong) = tokio::try_join!(
+            display.handle_protocol(&message),
+            server.expect(request::SendEvent::OPCODE)
+        )?;
+        assert_eq!(protocol, Some(Protocol::Ping(proto::Timestamp(123))));
+
+        let mask = proto::EventMask::SUBSTRUCTURE_NOTIFY | proto::EventMask::SUBSTRUCTURE_REDIRECT;
+        assert_eq!(pong.u32(4), fake::ROOT.0);
+        assert_eq!(pong.u32(8), mask.bits());
+
+        match pong.event()? {
+            event::Event::ClientMessage(pong) => {
+                assert_eq!(pong.window, fake::ROOT);
+                assert_eq!(pong.message_type, wm_protocols);
+                assert_eq!(pong.longs(), [ping.0, 123, WINDOW.0, 0, 0]);
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        // Which is what the window manager takes for an answer.
+        let pong = event::ClientMessage {
+            window: fake::ROOT,
+            ..message.clone()
+        };
+        assert!(display.is_pong(&pong, WINDOW).await?);
+        assert!(!display.is_pong(&pong, proto::Window(0x50_0001)).await?);
+        assert!(!display.is_pong(&message, WINDOW).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn other_messages_are_only_decoded() -> anyhow::Result<()> {
+        let (mut display, mut server) = fake::connect()?;
+        let wm_protocols = server.atom(WM_PROTOCOLS);
+        let delete_window = server.atom(WM_DELETE_WINDOW);
+
+        let message =
+            event::ClientMessage::new(WINDOW, wm_protocols, [delete_window.0, 5, 0, 0, 0]);
+        let (protocol, ()) = tokio::try_join!(
+            display.handle_protocol(&message),
+            server.intern(&[WM_PROTOCOLS, WM_DELETE_WINDOW, WM_TAKE_FOCUS, NET_WM_PING])
+        )?;
+        assert_eq!(protocol, Some(Protocol::DeleteWindow(proto::Timestamp(5))));
+
+        let message =
+            event::ClientMessage::new(WINDOW, proto::Atom(1), [delete_window.0, 5, 0, 0, 0]);
+        assert_eq!(display.handle_protocol(&message).await?, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn clients_without_delete_window_are_killed() -> anyhow::Result<()> {
+        let (mut display, mut server) = fake::connect()?;
+        let take_focus = server.atom(WM_TAKE_FOCUS);
+
+        let (close, kill) = tokio::try_join!(display.close_window(WINDOW), async {
+            server.expect(request::GetProperty::OPCODE).await?;
+            server
+                .reply_property(32, atom::Atom::Atom.into(), 0, &take_focus.0.to_be_bytes())
+                .await?;
+            server.expect(request::KillClient::OPCODE).await
+        })?;
+        assert_eq!(close, Close::Killed);
+        assert_eq!(kill.u32(4), WINDOW.0);
+
+        // A client that lists WM_DELETE_WINDOW is asked instead.
+        let delete_window = server.atom(WM_DELETE_WINDOW);
+        let listed = [take_focus.0, delete_window.0]
+            .iter()
+            .flat_map(|atom| atom.to_be_bytes())
+            .collect::<Vec<_>>();
+
+        let (close, message) = tokio::try_join!(display.close_window(WINDOW), async {
+            server.expect(request::GetProperty::OPCODE).await?;
+            server
+                .reply_property(32, atom::Atom::Atom.into(), 0, &listed)
+                .await?;
+            server.expect(request::SendEvent::OPCODE).await
+        })?;
+        assert_eq!(close, Close::Requested);
+
+        match message.event()? {
+            event::Event::ClientMessage(message) => {
+                assert_eq!(message.window, WINDOW);
+                assert_eq!(message.longs()[0], delete_window.0);
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        Ok(())
+    }
+}
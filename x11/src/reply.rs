@@ -1,13 +1,134 @@
+use crate::{atom, proto};
+use std::io;
+
+pub trait Reply: Sized {
+    /// Whether the server sends a reply at all.
+    const EXPECTED: bool = true;
+
+    /// Reads a reply, starting after the reply code.
+    fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self>;
+}
+
+impl Reply for () {
+    const EXPECTED: bool = false;
+
+    fn read<T: io::Read>(_r: &mut T) -> anyhow::Result<Self> {
+        Ok(())
+    }
+}
+
+/// A reply that isn't decoded any further.
 #[derive(Debug)]
-pub struct QueryTree {
-    sequence_number: u16,
-    root: u32,
-    parent: Option<u32>,
-    children: Vec<u32>,
+pub struct Raw {
+    pub data: u8,
+    pub sequence_number: u16,
+    pub body: Vec<u8>,
 }
 
-impl QueryTree {
+impl Reply for Raw {
     fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
-        todo()
+        use byteorder::{BigEndian, ReadBytesExt};
+
+        let data = r.read_u8()?;
+        let sequence_number = r.read_u16::<BigEndian>()?;
+        let length = r.read_u32::<BigEndian>()?;
+
+        let mut body = vec![0; 24 + usize::try_from(length)? * 4];
+        r.read_exact(&mut body)?;
+
+        Ok(Raw {
+            data,
+            sequence_number,
+            body,
+        })
+    }
+}
+
+// The core replies are generated from xcb-proto in `protocol::xproto`, and
+// named here after their requests.
+pub use crate::protocol::xproto::{
+    GetAtomNameReply as GetAtomName, GetGeometryReply as GetGeometry, GetImageReply as GetImage,
+    GetPropertyReply as GetProperty, GetSelectionOwnerReply as GetSelectionOwner,
+    GetWindowAttributesReply as GetWindowAttributes, InternAtomReply as InternAtom,
+    ListPropertiesReply as ListProperties, QueryTreeReply as QueryTree,
+    TranslateCoordinatesReply as TranslateCoordinates,
+};
+
+impl GetProperty {
+    pub fn exists(&self) -> bool {
+        self.property_type().is_some()
+    }
+
+    /// The type of the property, or `None` if it doesn't exist.
+    pub fn property_type(&self) -> Option<proto::Atom> {
+        Some(self.type_).filter(|t| t.0 != 0)
+    }
+
+    pub fn u8s(&self) -> anyhow::Result<&[u8]> {
+        self.check_format(8)?;
+        Ok(&self.value)
+    }
+
+    pub fn u16s(&self) -> anyhow::Result<Vec<u16>> {
+        self.check_format(16)?;
+
+        Ok(self
+            .value
+            .chunks_exact(2)
+            .map(|v| u16::from_be_bytes([v[0], v[1]]))
+            .collect())
+    }
+
+    pub fn u32s(&self) -> anyhow::Result<Vec<u32>> {
+        self.check_format(32)?;
+
+        Ok(self
+            .value
+            .chunks_exact(4)
+            .map(|v| u32::from_be_bytes([v[0], v[1], v[2], v[3]]))
+            .collect())
+    }
+
+    pub fn atoms(&self) -> anyhow::Result<Vec<proto::Atom>> {
+        self.check_type(atom::Atom::Atom.into())?;
+        Ok(self.u32s()?.into_iter().map(proto::Atom).collect())
+    }
+
+    pub fn windows(&self) -> anyhow::Result<Vec<proto::Window>> {
+        self.check_type(atom::Atom::Window.into())?;
+        Ok(self.u32s()?.into_iter().map(proto::Window).collect())
+    }
+
+    /// Decodes a text property. STRING is Latin-1; any other type of
+    /// format 8, such as UTF8_STRING, is taken to be UTF-8.
+    pub fn string(&self) -> anyhow::Result<String> {
+        let value = self.u8s()?;
+
+        match self.property_type() {
+            Some(t) if t == atom::Atom::String => {
+                Ok(value.iter().map(|&b| char::from(b)).collect())
+            }
+            _ => Ok(String::from(std::str::from_utf8(value)?)),
+        }
+    }
+
+    fn check_format(&self, format: u8) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.format == format,
+            "property has format {}, not {}",
+            self.format,
+            format
+        );
+        Ok(())
+    }
+
+    fn check_type(&self, property_type: proto::Atom) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.type_ == property_type,
+            "property has type {}, not {}",
+            self.type_,
+            property_type
+        );
+        Ok(())
     }
 }
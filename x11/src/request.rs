@@ -1,31 +1,136 @@
-use crate::display;
-use crate::proto;
+use crate::{atom, event, proto, protocol::xproto, reply};
 use byteorder::{BigEndian, WriteBytesExt};
 use std::io;
 
-#[derive(Debug)]
-pub struct CreateWindow {
-    depth: u8,
-    window: u32,
-    parent: u32,
-    x: u16,
-    y: u16,
-    width: u16,
-    height: u16,
-    border_width: u16,
-    class: proto::Class,
-    visual: proto::Visual,
-    value_mask: u32,
-    values: Vec<u32>,
+pub trait Request {
+    /// The decoded reply, or `()` for requests without one.
+    type Reply: reply::Reply;
+
+    const OPCODE: u8;
+
+    /// The request length in units of four bytes, including the header.
+    fn length(&self) -> usize;
+
+    /// The byte following the opcode in the request header.
+    fn data(&self) -> u8 {
+        0
+    }
+
+    /// Writes everything following the request header.
+    fn write_body<W: io::Write>(&self, w: &mut W) -> anyhow::Result<()>;
+
+    fn write<W: io::Write>(&self, w: &mut W) -> anyhow::Result<()> {
+        w.write_u8(Self::OPCODE)?;
+        w.write_u8(self.data())?;
+        w.write_u16::<BigEndian>(self.length().try_into()?)?; // request length
+
+        self.write_body(w)
+    }
+
+    fn encode(&self) -> anyhow::Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(self.length() * 4);
+        self.write(&mut buf)?;
+
+        anyhow::ensure!(
+            buf.len() == self.length() * 4,
+            "request length {} doesn't match encoded length {}",
+            self.length() * 4,
+            buf.len()
+        );
+
+        Ok(buf)
+    }
+}
+
+/// A request of an extension. Its major opcode is only known once the
+/// extension has been queried, and the minor opcode takes the place of
+/// `Request::data`.
+pub trait ExtensionRequest {
+    /// The decoded reply, or `()` for requests without one.
+    type Reply: reply::Reply;
+
+    /// The name of the extension as known to QueryExtension.
+    const EXTENSION: &'static str;
+
+    const MINOR_OPCODE: u8;
+
+    /// The request length in units of four bytes, including the header.
+    fn length(&self) -> usize;
+
+    /// Writes everything following the request header.
+    fn write_body<W: io::Write>(&self, w: &mut W) -> anyhow::Result<()>;
+
+    fn encode(&self, major_opcode: u8) -> anyhow::Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(self.length() * 4);
+        buf.write_u8(major_opcode)?;
+        buf.write_u8(Self::MINOR_OPCODE)?;
+        buf.write_u16::<BigEndian>(self.length().try_into()?)?; // request length
+        self.write_body(&mut buf)?;
+
+        anyhow::ensure!(
+            buf.len() == self.length() * 4,
+            "request length {} doesn't match encoded length {}",
+            self.length() * 4,
+            buf.len()
+        );
+
+        Ok(buf)
+    }
+}
+
+// The core requests are generated from xcb-proto in `protocol::xproto`.
+// What follows are constructors for them that take the typed values of
+// `proto` and fill in the usual defaults.
+pub use crate::protocol::xproto::{
+    ChangeProperty, ChangeSaveSet, ChangeWindowAttributes, CirculateWindow, ConfigureWindow,
+    ConvertSelection, CreateWindow, DeleteProperty, DestroySubwindows, DestroyWindow, GetAtomName,
+    GetGeometry, GetImage, GetProperty, GetSelectionOwner, GetWindowAttributes, InternAtom,
+    KillClient, ListProperties, MapSubwindows, MapWindow, QueryTree, ReparentWindow,
+    RotateProperties, SendEvent, SetInputFocus, SetSelectionOwner, TranslateCoordinates,
+    UnmapSubwindows, UnmapWindow,
+};
+
+/// Sets a window attribute in the value list of CreateWindow or
+/// ChangeWindowAttributes, which have the same fields.
+macro_rules! set_attribute {
+    ($list:expr, $attribute:expr) => {{
+        let attribute: proto::WindowAttribute = $attribute;
+        let v = attribute.value();
+
+        match attribute {
+            proto::WindowAttribute::BackgroundPixmap(_) => {
+                $list.background_pixmap = Some(proto::Pixmap(v))
+            }
+            proto::WindowAttribute::BackgroundPixel(_) => $list.background_pixel = Some(v),
+            proto::WindowAttribute::BorderPixmap(_) => $list.border_pixmap = Some(proto::Pixmap(v)),
+            proto::WindowAttribute::BorderPixel(_) => $list.border_pixel = Some(v),
+            proto::WindowAttribute::BitGravity(_) => $list.bit_gravity = Some(xproto::Gravity(v)),
+            proto::WindowAttribute::WinGravity(_) => $list.win_gravity = Some(xproto::Gravity(v)),
+            proto::WindowAttribute::BackingStore(_) => {
+                $list.backing_store = Some(xproto::BackingStore(v))
+            }
+            proto::WindowAttribute::BackingPlanes(_) => $list.backing_planes = Some(v),
+            proto::WindowAttribute::BackingPixel(_) => $list.backing_pixel = Some(v),
+            proto::WindowAttribute::SaveUnder(_) => $list.save_under = Some(v),
+            proto::WindowAttribute::EventMask(_) => $list.event_mask = Some(xproto::EventMask(v)),
+            proto::WindowAttribute::DoNotPropagateMask(_) => {
+                $list.do_not_propogate_mask = Some(xproto::EventMask(v))
+            }
+            proto::WindowAttribute::OverrideRedirect(_) => $list.override_redirect = Some(v),
+            proto::WindowAttribute::Colormap(_) => $list.colormap = Some(proto::Colormap(v)),
+            proto::WindowAttribute::Cursor(_) => $list.cursor = Some(proto::Cursor(v)),
+        }
+    }};
 }
 
 impl CreateWindow {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         depth: u8,
-        window: u32,
-        parent: u32,
-        x: u16,
-        y: u16,
+        window: proto::Window,
+        parent: proto::Window,
+        x: i16,
+        y: i16,
         width: u16,
         height: u16,
         border_width: u16,
@@ -34,160 +139,546 @@ impl CreateWindow {
     ) -> Self {
         Self {
             depth,
-            window,
+            wid: window,
             parent,
             x,
             y,
             width,
             height,
             border_width,
-            class,
-            visual,
-            value_mask: 0,
-            values: Vec::new(),
+            class: xproto::WindowClass(u16::from(class).into()),
+            visual: visual.into(),
+            value_list: Default::default(),
         }
     }
 
-    pub fn write<W: io::Write>(&self, w: &mut W) -> anyhow::Result<()> {
-        w.write_u8(proto::OPCODE_CREATE_WINDOW)?;
+    pub fn attribute(&mut self, attribute: proto::WindowAttribute) -> &mut Self {
+        set_attribute!(self.value_list, attribute);
+        self
+    }
 
-        w.write_u8(self.depth)?; // depth
+    pub fn attributes(&mut self, attributes: proto::WindowAttributes) -> &mut Self {
+        self.value_list = Default::default();
+        for &attribute in attributes.iter() {
+            self.attribute(attribute);
+        }
+        self
+    }
 
-        w.write_u16::<BigEndian>((8 + self.values.len()).try_into()?)?; // request length (8+n)
+    pub fn event_mask(&mut self, value: proto::EventMask) -> &mut Self {
+        self.attribute(proto::WindowAttribute::EventMask(value))
+    }
+}
 
-        w.write_u32::<BigEndian>(self.window)?;
-        w.write_u32::<BigEndian>(self.parent)?;
+impl ChangeWindowAttributes {
+    pub fn new(window: proto::Window) -> Self {
+        Self {
+            window,
+            value_list: Default::default(),
+        }
+    }
 
-        w.write_u16::<BigEndian>(self.x)?;
-        w.write_u16::<BigEndian>(self.y)?;
-        w.write_u16::<BigEndian>(self.width)?;
-        w.write_u16::<BigEndian>(self.height)?;
-        w.write_u16::<BigEndian>(self.border_width)?;
+    pub fn attribute(&mut self, attribute: proto::WindowAttribute) -> &mut Self {
+        set_attribute!(self.value_list, attribute);
+        self
+    }
 
-        self.class.write(w)?;
-        self.visual.write(w)?;
+    pub fn attributes(&mut self, attributes: proto::WindowAttributes) -> &mut Self {
+        self.value_list = Default::default();
+        for &attribute in attributes.iter() {
+            self.attribute(attribute);
+        }
+        self
+    }
 
-        w.write_u32::<BigEndian>(self.value_mask)?;
+    pub fn event_mask(&mut self, value: proto::EventMask) -> &mut Self {
+        self.attribute(proto::WindowAttribute::EventMask(value))
+    }
+}
 
-        // TODO: order?
-        for v in &self.values {
-            w.write_u32::<BigEndian>(*v)?; // n
+impl GetWindowAttributes {
+    pub fn new(window: proto::Window) -> Self {
+        Self { window }
+    }
+}
+
+impl DestroyWindow {
+    pub fn new(window: proto::Window) -> Self {
+        Self { window }
+    }
+}
+
+impl DestroySubwindows {
+    pub fn new(window: proto::Window) -> Self {
+        Self { window }
+    }
+}
+
+impl ChangeSaveSet {
+    pub fn new(mode: proto::SaveSetMode, window: proto::Window) -> Self {
+        Self {
+            mode: xproto::SetMode(u8::from(mode).into()),
+            window,
         }
+    }
+}
 
-        Ok(())
+impl ReparentWindow {
+    pub fn new(window: proto::Window, parent: proto::Window, x: i16, y: i16) -> Self {
+        Self {
+            window,
+            parent,
+            x,
+            y,
+        }
     }
+}
 
-    pub fn event_mask(&mut self, value: u32) -> &mut Self {
-        self.value_mask |= proto::VALUE_MASK_EVENT_MASK;
-        self.values.push(value);
-        self
+impl MapWindow {
+    pub fn new(window: proto::Window) -> Self {
+        Self { window }
     }
 }
 
-#[derive(Debug)]
-pub struct ChangeWindowAttributes {
-    window: u32,
-    value_mask: u32,
-    values: Vec<u32>,
+impl MapSubwindows {
+    pub fn new(window: proto::Window) -> Self {
+        Self { window }
+    }
 }
 
-impl ChangeWindowAttributes {
-    pub fn new(window: u32) -> Self {
+impl UnmapWindow {
+    pub fn new(window: proto::Window) -> Self {
+        Self { window }
+    }
+}
+
+impl UnmapSubwindows {
+    pub fn new(window: proto::Window) -> Self {
+        Self { window }
+    }
+}
+
+impl QueryTree {
+    pub fn new(window: proto::Window) -> Self {
+        Self { window }
+    }
+}
+
+impl ConfigureWindow {
+    pub fn new(window: proto::Window) -> Self {
         Self {
             window,
-            value_mask: 0,
-            values: Vec::new(),
+            ..Default::default()
         }
     }
 
-    pub fn write<W: io::Write>(&self, w: &mut W) -> anyhow::Result<()> {
-        w.write_u8(proto::OPCODE_CHANGE_WINDOW_ATTRIBUTES)?;
-        w.write_u8(0)?; // unused
-        w.write_u16::<BigEndian>((3 + self.values.len()).try_into()?)?; // request length (3+n)
+    /// Grants a ConfigureRequest, changing exactly the fields it asked for.
+    pub fn from_request(request: &event::ConfigureRequest) -> Self {
+        let has = |field| request.value_mask.contains(field);
 
-        w.write_u32::<BigEndian>(self.window)?;
+        let mut configure = Self::new(request.window);
+        if has(proto::ConfigureMask::X) {
+            configure.x(request.x);
+        }
+        if has(proto::ConfigureMask::Y) {
+            configure.y(request.y);
+        }
+        if has(proto::ConfigureMask::WIDTH) {
+            configure.width(request.width);
+        }
+        if has(proto::ConfigureMask::HEIGHT) {
+            configure.height(request.height);
+        }
+        if has(proto::ConfigureMask::BORDER_WIDTH) {
+            configure.border_width(request.border_width);
+        }
+        if let Some(sibling) = request
+            .sibling
+            .filter(|_| has(proto::ConfigureMask::SIBLING))
+        {
+            configure.sibling(sibling);
+        }
+        if has(proto::ConfigureMask::STACK_MODE) {
+            configure.stack_mode(request.stack_mode);
+        }
+        configure
+    }
+
+    pub fn x(&mut self, x: i16) -> &mut Self {
+        self.value_list.x = Some(x.into());
+        self
+    }
+
+    pub fn y(&mut self, y: i16) -> &mut Self {
+        self.value_list.y = Some(y.into());
+        self
+    }
 
-        w.write_u32::<BigEndian>(self.value_mask)?;
+    pub fn width(&mut self, width: u16) -> &mut Self {
+        self.value_list.width = Some(width.into());
+        self
+    }
+
+    pub fn height(&mut self, height: u16) -> &mut Self {
+        self.value_list.height = Some(height.into());
+        self
+    }
+
+    pub fn border_width(&mut self, border_width: u16) -> &mut Self {
+        self.value_list.border_width = Some(border_width.into());
+        self
+    }
+
+    pub fn sibling(&mut self, sibling: proto::Window) -> &mut Self {
+        self.value_list.sibling = Some(sibling);
+        self
+    }
+
+    pub fn stack_mode(&mut self, stack_mode: proto::StackMode) -> &mut Self {
+        self.value_list.stack_mode = Some(xproto::StackMode(u8::from(stack_mode).into()));
+        self
+    }
 
-        for v in &self.values {
-            w.write_u32::<BigEndian>(*v)?; // n
+    pub fn mask(&self) -> proto::ConfigureMask {
+        proto::ConfigureMask::from_bits_truncate(self.value_list.mask() as u16)
+    }
+}
+
+impl CirculateWindow {
+    pub fn new(direction: proto::CirculateDirection, window: proto::Window) -> Self {
+        Self {
+            direction: xproto::Circulate(u8::from(direction).into()),
+            window,
         }
+    }
+}
 
-        Ok(())
+impl GetGeometry {
+    pub fn new(drawable: proto::Drawable) -> Self {
+        Self { drawable }
     }
+}
 
-    pub fn event_mask(&mut self, value: u32) -> &mut Self {
-        self.value_mask |= proto::VALUE_MASK_EVENT_MASK;
-        self.values.push(value);
+impl InternAtom {
+    pub fn new(name: &str) -> Self {
+        Self {
+            only_if_exists: false,
+            name: name.as_bytes().to_vec(),
+        }
+    }
+
+    /// Don't create the atom if it doesn't exist yet.
+    pub fn only_if_exists(&mut self, only_if_exists: bool) -> &mut Self {
+        self.only_if_exists = only_if_exists;
         self
     }
 }
 
-#[derive(Debug)]
-pub struct GetWindowAttributes {
-    window: u32,
+impl GetAtomName {
+    pub fn new(atom: proto::Atom) -> Self {
+        Self { atom }
+    }
 }
 
-impl GetWindowAttributes {
-    pub fn new(window: u32) -> Self {
-        Self { window }
+impl ChangeProperty {
+    /// A property of format 8. `data` holds one byte per element.
+    pub fn new(
+        window: proto::Window,
+        property: proto::Atom,
+        property_type: proto::Atom,
+        data: &[u8],
+    ) -> Self {
+        Self {
+            mode: xproto::PropMode::REPLACE,
+            window,
+            property,
+            type_: property_type,
+            format: 8,
+            data_len: data.len() as u32,
+            data: data.to_vec(),
+        }
+    }
+
+    /// A property of format 16.
+    pub fn u16s(
+        window: proto::Window,
+        property: proto::Atom,
+        property_type: proto::Atom,
+        data: &[u16],
+    ) -> Self {
+        Self {
+            format: 16,
+            data_len: data.len() as u32,
+            data: data.iter().flat_map(|v| v.to_be_bytes()).collect(),
+            ..Self::new(window, property, property_type, &[])
+        }
+    }
+
+    /// A property of format 32.
+    pub fn u32s(
+        window: proto::Window,
+        property: proto::Atom,
+        property_type: proto::Atom,
+        data: &[u32],
+    ) -> Self {
+        Self {
+            format: 32,
+            data_len: data.len() as u32,
+            data: data.iter().flat_map(|v| v.to_be_bytes()).collect(),
+            ..Self::new(window, property, property_type, &[])
+        }
     }
 
-    pub fn write<W: io::Write>(&self, w: &mut W) -> anyhow::Result<()> {
-        w.write_u8(proto::OPCODE_GET_WINDOW_ATTRIBUTES)?;
-        w.write_u8(0)?; // unused
-        w.write_u16::<BigEndian>(2)?; // request length
+    /// A list of atoms, of type ATOM.
+    pub fn atoms(window: proto::Window, property: proto::Atom, atoms: &[proto::Atom]) -> Self {
+        let data = atoms.iter().map(|atom| atom.0).collect::<Vec<_>>();
+        Self::u32s(window, property, atom::Atom::Atom.into(), &data)
+    }
 
-        w.write_u32::<BigEndian>(self.window)?; // window
+    /// A UTF-8 string, of type UTF8_STRING, which has to be interned.
+    pub fn utf8(
+        window: proto::Window,
+        property: proto::Atom,
+        utf8_string: proto::Atom,
+        value: &str,
+    ) -> Self {
+        Self::new(window, property, utf8_string, value.as_bytes())
+    }
 
-        Ok(())
+    pub fn mode(&mut self, mode: proto::PropertyMode) -> &mut Self {
+        self.mode = xproto::PropMode(u8::from(mode).into());
+        self
     }
 }
 
-#[derive(Debug)]
-pub struct MapWindow {
-    window: u32,
+impl DeleteProperty {
+    pub fn new(window: proto::Window, property: proto::Atom) -> Self {
+        Self { window, property }
+    }
 }
 
-impl MapWindow {
-    pub fn new(window: u32) -> Self {
+impl GetProperty {
+    /// Gets the whole value of a property of any type.
+    pub fn new(window: proto::Window, property: proto::Atom) -> Self {
+        Self {
+            delete: false,
+            window,
+            property,
+            type_: proto::Atom(0), // AnyPropertyType
+            long_offset: 0,
+            long_length: u32::MAX,
+        }
+    }
+
+    pub fn delete(&mut self, delete: bool) -> &mut Self {
+        self.delete = delete;
+        self
+    }
+
+    /// Only gets the value if the property has this type. `None` accepts
+    /// any type.
+    pub fn property_type(&mut self, property_type: Option<proto::Atom>) -> &mut Self {
+        self.type_ = property_type.unwrap_or_default();
+        self
+    }
+
+    /// The part of the value to get, in units of four bytes.
+    pub fn range(&mut self, long_offset: u32, long_length: u32) -> &mut Self {
+        self.long_offset = long_offset;
+        self.long_length = long_length;
+        self
+    }
+}
+
+impl ListProperties {
+    pub fn new(window: proto::Window) -> Self {
         Self { window }
     }
+}
+
+impl SetSelectionOwner {
+    /// Makes `owner` the owner of `selection`, or gives it up if `None`.
+    pub fn new(
+        owner: Option<proto::Window>,
+        selection: proto::Atom,
+        time: proto::Timestamp,
+    ) -> Self {
+        Self {
+            owner: owner.unwrap_or_default(),
+            selection,
+            time,
+        }
+    }
+}
 
-    pub fn write<W: io::Write>(&self, w: &mut W) -> anyhow::Result<()> {
-        w.write_u8(proto::OPCODE_MAP_WINDOW)?;
-        w.write_u8(0)?; // unused
-        w.write_u16::<BigEndian>(2)?; // request length
+impl GetSelectionOwner {
+    pub fn new(selection: proto::Atom) -> Self {
+        Self { selection }
+    }
+}
 
-        w.write_u32::<BigEndian>(self.window)?;
+impl ConvertSelection {
+    /// Asks the owner of `selection` to store it, converted to `target`,
+    /// in `property` on `requestor`.
+    pub fn new(
+        requestor: proto::Window,
+        selection: proto::Atom,
+        target: proto::Atom,
+        property: Option<proto::Atom>,
+        time: proto::Timestamp,
+    ) -> Self {
+        Self {
+            requestor,
+            selection,
+            target,
+            property: property.unwrap_or_default(),
+            time,
+        }
+    }
+}
 
-        Ok(())
+impl SendEvent {
+    /// Sends an encoded event. With an empty event mask, the event goes to
+    /// the client that created the destination window.
+    pub fn new(destination: proto::Destination, event: [u8; 32]) -> Self {
+        Self {
+            propagate: false,
+            destination: proto::Window(destination.into()),
+            event_mask: xproto::EventMask(0),
+            event: event.to_vec(),
+        }
+    }
+
+    pub fn propagate(&mut self, propagate: bool) -> &mut Self {
+        self.propagate = propagate;
+        self
+    }
+
+    pub fn event_mask(&mut self, event_mask: proto::EventMask) -> &mut Self {
+        self.event_mask = xproto::EventMask(event_mask.bits());
+        self
     }
 }
 
-#[derive(Debug)]
-pub struct QueryTree {
-    window: u32,
+impl TranslateCoordinates {
+    pub fn new(
+        src_window: proto::Window,
+        dst_window: proto::Window,
+        src_x: i16,
+        src_y: i16,
+    ) -> Self {
+        Self {
+            src_window,
+            dst_window,
+            src_x,
+            src_y,
+        }
+    }
 }
 
-#[derive(Debug)]
-pub struct QueryTreeReply {
-    root: proto::Window,
-    parent: Option<proto::Window>,
-    children: Vec<proto::Window>,
+impl SetInputFocus {
+    pub fn new(focus: proto::Focus, revert_to: proto::RevertTo, time: proto::Timestamp) -> Self {
+        Self {
+            revert_to: xproto::InputFocus(u8::from(revert_to).into()),
+            focus: proto::Window(focus.into()),
+            time,
+        }
+    }
 }
 
-impl QueryTree {
-    pub fn new(window: u32) -> Self {
-        Self { window }
+impl GetImage {
+    /// Gets a rectangle of a drawable in ZPixmap format, with all planes.
+    pub fn new(drawable: proto::Drawable, x: i16, y: i16, width: u16, height: u16) -> Self {
+        Self {
+            format: xproto::ImageFormat::Z_PIXMAP,
+            drawable,
+            x,
+            y,
+            width,
+            height,
+            plane_mask: u32::MAX,
+        }
     }
 
-    pub fn write<W: io::Write>(&self, w: &mut W) -> anyhow::Result<()> {
-        w.write_u8(proto::OPCODE_QUERY_TREE)?;
-        w.write_u8(0)?; // unused
-        w.write_u16::<BigEndian>(2)?; // request length
+    pub fn format(&mut self, format: proto::ImageFormat) -> &mut Self {
+        self.format = xproto::ImageFormat(u8::from(format).into());
+        self
+    }
+
+    pub fn plane_mask(&mut self, plane_mask: u32) -> &mut Self {
+        self.plane_mask = plane_mask;
+        self
+    }
+}
+
+impl KillClient {
+    /// Closes the connection of the client that created `resource`. Zero
+    /// (AllTemporary) kills the resources of clients that have terminated
+    /// with a close-down mode of RetainTemporary.
+    pub fn new(resource: u32) -> Self {
+        Self { resource }
+    }
+}
+
+impl RotateProperties {
+    pub fn new(window: proto::Window, delta: i16, properties: &[proto::Atom]) -> Self {
+        Self {
+            window,
+            delta,
+            atoms: properties.to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(buf: &[u8]) -> Vec<u32> {
+        buf.chunks_exact(4)
+            .map(|v| u32::from_be_bytes([v[0], v[1], v[2], v[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn configure_window_values_follow_the_mask() -> anyhow::Result<()> {
+        let buf = ConfigureWindow::new(proto::Window(0x200001))
+            .stack_mode(proto::StackMode::Below)
+            .sibling(proto::Window(0x200002))
+            .width(640)
+            .x(-10)
+            .encode()?;
+
+        assert_eq!(&buf[..4], [ConfigureWindow::OPCODE, 0, 0, 7]);
+        assert_eq!(
+            words(&buf[4..]),
+            [0x200001, 0x0065 << 16, -10i32 as u32, 640, 0x200002, 1]
+        );
+
+        Ok(())
+    }
 
-        w.write_u32::<BigEndian>(self.window)?;
+    #[test]
+    fn configure_window_grants_only_the_requested_fields() -> anyhow::Result<()> {
+        let request = event::ConfigureRequest {
+            stack_mode: proto::StackMode::Above,
+            parent: proto::Window(1),
+            window: proto::Window(2),
+            sibling: None,
+            x: 100,
+            y: 200,
+            width: 300,
+            height: 400,
+            border_width: 5,
+            value_mask: proto::ConfigureMask::Y | proto::ConfigureMask::HEIGHT,
+        };
+
+        let configure = ConfigureWindow::from_request(&request);
+        assert_eq!(configure.mask(), request.value_mask);
+        assert_eq!(
+            words(&configure.encode()?[4..]),
+            [2, 0x000a << 16, 200, 400]
+        );
 
         Ok(())
     }
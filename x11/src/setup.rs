@@ -1,3 +1,4 @@
+use crate::proto;
 use anyhow::anyhow;
 use std::fmt;
 use std::io;
@@ -86,9 +87,9 @@ impl Setup {
                 }
 
                 let reason = {
-                    let length = usize::try_from(length)?;
+                    let length = usize::from(length);
 
-                    let mut data = vec![0; usize::try_from(length)?];
+                    let mut data = vec![0; length];
                     r.read_exact(&mut data)?;
 
                     String::from(str::from_utf8(&data)?)
@@ -149,7 +150,7 @@ impl Setup {
         }
 
         let vendor = {
-            let length = usize::try_from(vendor_length)?;
+            let length = usize::from(vendor_length);
 
             let mut data = vec![0; length];
             r.read_exact(&mut data)?;
@@ -245,18 +246,18 @@ impl TryFrom<u8> for BackingStores {
 
 #[derive(Debug)]
 pub struct Screen {
-    pub root_window: u32,
-    pub colormap: u32,
+    pub root_window: proto::Window,
+    pub colormap: proto::Colormap,
     pub white_pixel: u32,
     pub black_pixel: u32,
-    pub current_input_masks: u32,
+    pub current_input_masks: proto::EventMask,
     pub width_in_pixels: u16,
     pub height_in_pixels: u16,
     pub width_in_millimeters: u16,
     pub height_in_millimeters: u16,
     pub min_installed_maps: u16,
     pub max_installed_maps: u16,
-    pub root_visual: u32,
+    pub root_visual: proto::Visualid,
     pub backing_stores: BackingStores,
     pub save_unders: u8,
     pub root_depth: u8,
@@ -268,18 +269,18 @@ impl Screen {
     pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
         use byteorder::{BigEndian, ReadBytesExt};
 
-        let root_window = r.read_u32::<BigEndian>()?;
-        let colormap = r.read_u32::<BigEndian>()?;
+        let root_window = proto::Window(r.read_u32::<BigEndian>()?);
+        let colormap = proto::Colormap(r.read_u32::<BigEndian>()?);
         let white_pixel = r.read_u32::<BigEndian>()?;
         let black_pixel = r.read_u32::<BigEndian>()?;
-        let current_input_masks = r.read_u32::<BigEndian>()?;
+        let current_input_masks = proto::EventMask::from_bits_truncate(r.read_u32::<BigEndian>()?);
         let width_in_pixels = r.read_u16::<BigEndian>()?;
         let height_in_pixels = r.read_u16::<BigEndian>()?;
         let width_in_millimeters = r.read_u16::<BigEndian>()?;
         let height_in_millimeters = r.read_u16::<BigEndian>()?;
         let min_installed_maps = r.read_u16::<BigEndian>()?;
         let max_installed_maps = r.read_u16::<BigEndian>()?;
-        let root_visual = r.read_u32::<BigEndian>()?;
+        let root_visual = proto::Visualid(r.read_u32::<BigEndian>()?);
         let backing_stores = r.read_u8()?.try_into()?;
         let save_unders = r.read_u8()?;
         let root_depth = r.read_u8()?;
@@ -343,7 +344,7 @@ impl Depth {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum VisualTypeClass {
     StaticGray,
     GrayScale,
@@ -372,7 +373,7 @@ impl TryFrom<u8> for VisualTypeClass {
 
 #[derive(Debug)]
 pub struct VisualType {
-    pub visual_id: u32,
+    pub visual_id: proto::Visualid,
     pub class: VisualTypeClass,
     pub bits_per_rgb_value: u8,
     pub colormap_entries: u16,
@@ -385,7 +386,7 @@ impl VisualType {
     pub fn read<T: io::Read>(r: &mut T) -> anyhow::Result<Self> {
         use byteorder::{BigEndian, ReadBytesExt};
 
-        let visual_id = r.read_u32::<BigEndian>()?;
+        let visual_id = proto::Visualid(r.read_u32::<BigEndian>()?);
         let class = r.read_u8()?.try_into()?;
         let bits_per_rgb_value = r.read_u8()?;
         let colormap_entries = r.read_u16::<BigEndian>()?;
@@ -0,0 +1,74 @@
+use crate::{event, proto};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::Stream;
+
+/// Number of events buffered for each subscriber. A subscriber that falls
+/// further behind than this skips the oldest events.
+pub const CAPACITY: usize = 1024;
+
+type Filter = Box<dyn Fn(&event::Event) -> bool + Send + Sync>;
+
+/// A stream of the events read by a `Display`. Each subscriber gets its own
+/// copy of every event, independent of replies, errors and other
+/// subscribers.
+pub struct EventStream {
+    inner: BroadcastStream<event::Event>,
+    filters: Vec<Filter>,
+}
+
+impl EventStream {
+    pub fn new(receiver: broadcast::Receiver<event::Event>) -> Self {
+        Self {
+            inner: BroadcastStream::new(receiver),
+            filters: Vec::new(),
+        }
+    }
+
+    pub fn window(self, window: proto::Window) -> Self {
+        self.filter(move |event| event.window() == Some(window))
+    }
+
+    pub fn kind(self, kind: event::Kind) -> Self {
+        self.filter(move |event| event.kind() == kind)
+    }
+
+    pub fn kinds(self, kinds: &[event::Kind]) -> Self {
+        let kinds = kinds.to_vec();
+        self.filter(move |event| kinds.contains(&event.kind()))
+    }
+
+    pub fn filter<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&event::Event) -> bool + Send + Sync + 'static,
+    {
+        self.filters.push(Box::new(predicate));
+        self
+    }
+
+    fn matches(&self, event: &event::Event) -> bool {
+        self.filters.iter().all(|filter| filter(event))
+    }
+}
+
+impl Stream for EventStream {
+    type Item = event::Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match Pin::new(&mut self.inner).poll_next(cx) {
+                Poll::Ready(Some(Ok(event))) if self.matches(&event) => {
+                    return Poll::Ready(Some(event));
+                }
+
+                // Filtered out, or lagged behind and skipped.
+                Poll::Ready(Some(_)) => continue,
+
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
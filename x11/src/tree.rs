@@ -0,0 +1,271 @@
+use crate::{atom, display, error, proto, reply, request};
+use std::collections::HashMap;
+
+/// What to fetch for every window besides its children.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Options {
+    pub attributes: bool,
+    pub geometry: bool,
+    pub name: bool,
+}
+
+#[derive(Debug)]
+pub struct Tree {
+    pub window: proto::Window,
+    pub attributes: Option<reply::GetWindowAttributes>,
+    pub geometry: Option<reply::GetGeometry>,
+    pub name: Option<String>,
+    pub children: Vec<Tree>,
+}
+
+struct Cookies {
+    tree: display::Cookie<reply::QueryTree>,
+    attributes: Option<display::Cookie<reply::GetWindowAttributes>>,
+    geometry: Option<display::Cookie<reply::GetGeometry>>,
+    name: Option<display::Cookie<reply::GetProperty>>,
+}
+
+struct Node {
+    attributes: Option<reply::GetWindowAttributes>,
+    geometry: Option<reply::GetGeometry>,
+    name: Option<String>,
+    children: Vec<proto::Window>,
+}
+
+impl display::Display {
+    /// Walks the window tree below `window`. The requests for all windows on
+    /// one level of the tree are sent before any reply is waited for, so a
+    /// walk takes one round trip per level rather than per window. Windows
+    /// destroyed during the walk are left out.
+    pub async fn walk_tree(
+        &mut self,
+        window: proto::Window,
+        options: Options,
+    ) -> anyhow::Result<Option<Tree>> {
+        let mut nodes = HashMap::new();
+        let mut level = vec![window];
+
+        while !level.is_empty() {
+            let mut cookies = Vec::with_capacity(level.len());
+            for &window in &level {
+                cookies.push(self.send_tree_requests(window, options).await?);
+            }
+
+            // Every reply of the level is read before any error is
+            // returned, so none are left behind for `next_message`.
+            let mut replies = Vec::with_capacity(cookies.len());
+            for cookies in cookies {
+                replies.push(self.wait_tree_replies(cookies).await);
+            }
+
+            let mut next = Vec::new();
+
+            for (window, node) in level.into_iter().zip(replies) {
+                if let Some(node) = node? {
+                    next.extend(node.children.iter().copied());
+                    nodes.insert(window, node);
+                }
+            }
+
+            level = next;
+        }
+
+        Ok(build(window, &mut nodes))
+    }
+
+    async fn send_tree_requests(
+        &mut self,
+        window: proto::Window,
+        options: Options,
+    ) -> anyhow::Result<Cookies> {
+        let tree = self.send_request(&request::QueryTree::new(window)).await?;
+
+        let attributes = match options.attributes {
+            true => Some(
+                self.send_request(&request::GetWindowAttributes::new(window))
+                    .await?,
+            ),
+            false => None,
+        };
+
+        let geometry = match options.geometry {
+            true => Some(
+                self.send_request(&request::GetGeometry::new(window.into()))
+                    .await?,
+            ),
+            false => None,
+        };
+
+        let name = match options.name {
+            true => Some(
+                self.send_request(&request::GetProperty::new(
+                    window,
+                    atom::Atom::WmName.into(),
+                ))
+                .await?,
+            ),
+            false => None,
+        };
+
+        Ok(Cookies {
+            tree,
+            attributes,
+            geometry,
+            name,
+        })
+    }
+
+    /// Returns `None` if the window no longer exists. Every reply is waited
+    /// for before an error is returned, so none are left behind for
+    /// `next_message`. A name that isn't text is left out.
+    async fn wait_tree_replies(&mut self, cookies: Cookies) -> anyhow::Result<Option<Node>> {
+        let tree = self.wait_reply(cookies.tree).await;
+
+        let attributes = match cookies.attributes {
+            Some(cookie) => Some(self.wait_reply(cookie).await),
+            None => None,
+        };
+
+        let geometry = match cookies.geometry {
+            Some(cookie) => Some(self.wait_reply(cookie).await),
+            None => None,
+        };
+
+        let name = match cookies.name {
+            Some(cookie) => Some(self.wait_reply(cookie).await),
+            None => None,
+        };
+
+        let tree = match gone(tree)? {
+            Some(tree) => tree,
+            None => return Ok(None),
+        };
+
+        let name = name
+            .map(gone)
+            .transpose()?
+            .flatten()
+            .filter(|reply| reply.exists())
+            .and_then(|reply| reply.string().ok());
+
+        Ok(Some(Node {
+            attributes: attributes.map(gone).transpose()?.flatten(),
+            geometry: geometry.map(gone).transpose()?.flatten(),
+            name,
+            children: tree.children,
+        }))
+    }
+}
+
+/// Turns errors about a window that has been destroyed into `None`.
+fn gone<T>(result: anyhow::Result<T>) -> anyhow::Result<Option<T>> {
+    match result {
+        Ok(reply) => Ok(Some(reply)),
+        Err(err) => match err.downcast_ref::<error::Error>() {
+            Some(error::Error::Window(_)) | Some(error::Error::Drawable(_)) => Ok(None),
+            _ => Err(err),
+        },
+    }
+}
+
+fn build(window: proto::Window, nodes: &mut HashMap<proto::Window, Node>) -> Option<Tree> {
+    let node = nodes.remove(&window)?;
+
+    let children = node
+        .children
+        .iter()
+        .filter_map(|&child| build(child, nodes))
+        .collect();
+
+    Some(Tree {
+        window,
+        attributes: node.attributes,
+        geometry: node.geometry,
+        name: node.name,
+        children,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::Request;
+    use crate::{event, fake};
+
+    fn query_tree(parent: proto::Window, children: &[proto::Window]) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend(fake::ROOT.0.to_be_bytes());
+        body.extend(parent.0.to_be_bytes());
+        body.extend((children.len() as u16).to_be_bytes());
+        body.extend([0; 14]);
+        body.extend(children.iter().flat_map(|child| child.0.to_be_bytes()));
+        body
+    }
+
+    #[tokio::test]
+    async fn windows_destroyed_during_the_walk_are_left_out() -> anyhow::Result<()> {
+        let (mut display, mut server) = fake::connect()?;
+        let (kept, destroyed) = (proto::Window(7), proto::Window(8));
+        let options = Options {
+            geometry: true,
+            name: true,
+            ..Options::default()
+        };
+
+        let (tree, ()) = tokio::try_join!(display.walk_tree(fake::ROOT, options), async {
+            server.expect(request::QueryTree::OPCODE).await?;
+            server
+                .reply(0, &query_tree(proto::Window(0), &[kept, destroyed]))
+                .await?;
+            server.expect(request::GetGeometry::OPCODE).await?;
+            server.reply(24, &fake::geometry(1920)).await?;
+            server.expect(request::GetProperty::OPCODE).await?;
+            server.reply_property(0, proto::Atom(0), 0, &[]).await?;
+
+            server.expect(request::QueryTree::OPCODE).await?;
+            server.reply(0, &query_tree(fake::ROOT, &[])).await?;
+            server.expect(request::GetGeometry::OPCODE).await?;
+            server.reply(24, &fake::geometry(100)).await?;
+            server.expect(request::GetProperty::OPCODE).await?;
+            server
+                .reply_property(8, atom::Atom::String.into(), 0, b"kept")
+                .await?;
+
+            // The second child is gone by the time its requests arrive.
+            server.expect(request::QueryTree::OPCODE).await?;
+            server.error(3, destroyed.0).await?;
+            server.expect(request::GetGeometry::OPCODE).await?;
+            server.error(9, destroyed.0).await?;
+            server.expect(request::GetProperty::OPCODE).await?;
+            server.error(3, destroyed.0).await?;
+            anyhow::Ok(())
+        })?;
+
+        let tree = tree.expect("the root exists");
+        assert_eq!(tree.geometry.map(|geometry| geometry.width), Some(1920));
+        assert_eq!(tree.name, None);
+        assert_eq!(tree.children.len(), 1);
+
+        let child = &tree.children[0];
+        assert_eq!(child.window, kept);
+        assert_eq!(child.name.as_deref(), Some("kept"));
+        assert!(child.children.is_empty());
+
+        // No error was left behind: the next reply and event are the next
+        // messages.
+        let (geometry, ()) = tokio::try_join!(display.get_geometry(kept), async {
+            server.expect(request::GetGeometry::OPCODE).await?;
+            server.reply(24, &fake::geometry(200)).await?;
+            let message = event::ClientMessage::new(kept, proto::Atom(1), [0; 5]);
+            server.send(&message.encode()).await
+        })?;
+        assert_eq!(geometry.width, 200);
+
+        assert!(matches!(
+            display.next_message().await?,
+            display::Message::Event(event::Event::ClientMessage(_))
+        ));
+
+        Ok(())
+    }
+}